@@ -13,6 +13,9 @@ pub struct OpenBoundaries {
 }
 
 impl OpenBoundaries {
+    pub fn nodes(&self) -> &Nodes {
+        &self.nodes
+    }
     pub fn nodes_ids(&self) -> &Vec<Vec<u32>> {
         &self.nodes_ids
     }
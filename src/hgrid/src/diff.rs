@@ -0,0 +1,400 @@
+use super::{boundaries::BoundaryType, hgrid::Hgrid};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Thresholds below which node positions and depths are considered unchanged.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DiffTolerance {
+    pub position: f64,
+    pub depth: f64,
+}
+
+impl Default for DiffTolerance {
+    fn default() -> Self {
+        Self {
+            position: 1e-6,
+            depth: 1e-6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MovedNode {
+    pub id: u32,
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+    pub distance: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeDiff {
+    pub added: Vec<u32>,
+    pub removed: Vec<u32>,
+    pub moved: Vec<MovedNode>,
+}
+
+/// Depth change at a node, positive when the other grid is deeper.
+#[derive(Debug, Clone, Serialize)]
+pub struct DepthChange {
+    pub id: u32,
+    pub x: f64,
+    pub y: f64,
+    pub before: f64,
+    pub after: f64,
+    pub change: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DepthChangeStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub rms: f64,
+    pub max_abs: DepthChange,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DepthDiff {
+    /// Every node present in both grids whose depth changed.
+    pub changed: Vec<DepthChange>,
+    pub stats: Option<DepthChangeStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ElementDiff {
+    pub added: Vec<u32>,
+    pub removed: Vec<u32>,
+    /// Elements present in both grids with different node ids.
+    pub connectivity_changed: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoundaryTypeDiff {
+    pub count_before: usize,
+    pub count_after: usize,
+    /// Indices of boundaries whose node lists differ (including added or removed ones).
+    pub changed: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoundaryDiff {
+    pub open: BoundaryTypeDiff,
+    pub land: BoundaryTypeDiff,
+    pub interior: BoundaryTypeDiff,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueChange {
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Structural differences between two [`Hgrid`]s, as returned by [`Hgrid::diff`].
+///
+/// Nodes and elements are matched by id.
+#[derive(Debug, Clone, Serialize)]
+pub struct HgridDiff {
+    pub tolerance: DiffTolerance,
+    pub nodes: NodeDiff,
+    pub depths: DepthDiff,
+    pub elements: ElementDiff,
+    pub boundaries: BoundaryDiff,
+    pub crs: Option<ValueChange>,
+    pub description: Option<ValueChange>,
+}
+
+impl HgridDiff {
+    /// Node positions or connectivity changed.
+    pub fn geometry_changed(&self) -> bool {
+        !(self.nodes.added.is_empty()
+            && self.nodes.removed.is_empty()
+            && self.nodes.moved.is_empty()
+            && self.elements.added.is_empty()
+            && self.elements.removed.is_empty()
+            && self.elements.connectivity_changed.is_empty())
+    }
+
+    pub fn depths_changed(&self) -> bool {
+        !self.depths.changed.is_empty()
+    }
+
+    pub fn boundaries_changed(&self) -> bool {
+        [
+            &self.boundaries.open,
+            &self.boundaries.land,
+            &self.boundaries.interior,
+        ]
+        .iter()
+        .any(|diff| !diff.changed.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.geometry_changed()
+            || self.depths_changed()
+            || self.boundaries_changed()
+            || self.crs.is_some()
+            || self.description.is_some())
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for HgridDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Hgrids are identical.");
+        }
+        writeln!(
+            f,
+            "Nodes: {} added, {} removed, {} moved",
+            self.nodes.added.len(),
+            self.nodes.removed.len(),
+            self.nodes.moved.len()
+        )?;
+        if let Some(moved) = self
+            .nodes
+            .moved
+            .iter()
+            .max_by(|a, b| a.distance.total_cmp(&b.distance))
+        {
+            writeln!(
+                f,
+                "  largest move: node {} by {:.6} to ({}, {})",
+                moved.id, moved.distance, moved.to.0, moved.to.1
+            )?;
+        }
+        writeln!(f, "Depths: {} changed", self.depths.changed.len())?;
+        if let Some(stats) = &self.depths.stats {
+            writeln!(
+                f,
+                "  change min {:.4}, max {:.4}, mean {:.4}, rms {:.4}",
+                stats.min, stats.max, stats.mean, stats.rms
+            )?;
+            writeln!(
+                f,
+                "  largest change: node {} at ({}, {}): {:.4} -> {:.4}",
+                stats.max_abs.id,
+                stats.max_abs.x,
+                stats.max_abs.y,
+                stats.max_abs.before,
+                stats.max_abs.after
+            )?;
+        }
+        writeln!(
+            f,
+            "Elements: {} added, {} removed, {} with changed connectivity",
+            self.elements.added.len(),
+            self.elements.removed.len(),
+            self.elements.connectivity_changed.len()
+        )?;
+        for (name, diff) in [
+            ("Open", &self.boundaries.open),
+            ("Land", &self.boundaries.land),
+            ("Interior", &self.boundaries.interior),
+        ] {
+            writeln!(
+                f,
+                "{} boundaries: {} -> {}, {} changed",
+                name,
+                diff.count_before,
+                diff.count_after,
+                diff.changed.len()
+            )?;
+        }
+        for (name, change) in [("CRS", &self.crs), ("Description", &self.description)] {
+            if let Some(change) = change {
+                writeln!(
+                    f,
+                    "{} changed: {:?} -> {:?}",
+                    name, change.before, change.after
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn depth_of(values: &Option<Vec<f64>>) -> Option<f64> {
+    // node values are stored with the sign reversed
+    values.as_ref().and_then(|v| v.first()).map(|value| -value)
+}
+
+fn crs_definition(hgrid: &Hgrid) -> Option<String> {
+    hgrid.crs().and_then(|crs| crs.proj_info().definition)
+}
+
+fn boundary_type_diff(
+    before: &Hgrid,
+    after: &Hgrid,
+    boundary_type: BoundaryType,
+) -> BoundaryTypeDiff {
+    let lists = |hgrid: &Hgrid| -> Vec<Vec<u32>> {
+        hgrid
+            .boundaries()
+            .and_then(|boundaries| {
+                boundaries
+                    .to_boundary_type_map()
+                    .get(&boundary_type)
+                    .map(|nodes_ids| nodes_ids.to_vec())
+            })
+            .unwrap_or_default()
+    };
+    let before = lists(before);
+    let after = lists(after);
+    let changed = (0..before.len().max(after.len()))
+        .filter(|&i| before.get(i) != after.get(i))
+        .collect();
+    BoundaryTypeDiff {
+        count_before: before.len(),
+        count_after: after.len(),
+        changed,
+    }
+}
+
+impl Hgrid {
+    /// Compare against `other` using the default [`DiffTolerance`].
+    pub fn diff(&self, other: &Hgrid) -> HgridDiff {
+        self.diff_with_tolerance(other, DiffTolerance::default())
+    }
+
+    pub fn diff_with_tolerance(&self, other: &Hgrid, tolerance: DiffTolerance) -> HgridDiff {
+        let before_nodes = self.nodes().hash_map();
+        let after_nodes = other.nodes().hash_map();
+        let mut nodes = NodeDiff {
+            added: after_nodes
+                .keys()
+                .filter(|id| !before_nodes.contains_key(id))
+                .copied()
+                .collect(),
+            removed: before_nodes
+                .keys()
+                .filter(|id| !after_nodes.contains_key(id))
+                .copied()
+                .collect(),
+            moved: Vec::new(),
+        };
+        let mut changed = Vec::new();
+        for (&id, (coords, values)) in before_nodes.iter() {
+            let Some((other_coords, other_values)) = after_nodes.get(&id) else {
+                continue;
+            };
+            let distance = (other_coords[0] - coords[0]).hypot(other_coords[1] - coords[1]);
+            if distance > tolerance.position {
+                nodes.moved.push(MovedNode {
+                    id,
+                    from: (coords[0], coords[1]),
+                    to: (other_coords[0], other_coords[1]),
+                    distance,
+                });
+            }
+            if let (Some(before), Some(after)) = (depth_of(values), depth_of(other_values)) {
+                if (after - before).abs() > tolerance.depth {
+                    changed.push(DepthChange {
+                        id,
+                        x: other_coords[0],
+                        y: other_coords[1],
+                        before,
+                        after,
+                        change: after - before,
+                    });
+                }
+            }
+        }
+        let stats = changed
+            .iter()
+            .max_by(|a, b| a.change.abs().total_cmp(&b.change.abs()))
+            .map(|max_abs| {
+                let n = changed.len() as f64;
+                DepthChangeStats {
+                    min: changed.iter().map(|c| c.change).fold(f64::MAX, f64::min),
+                    max: changed.iter().map(|c| c.change).fold(f64::MIN, f64::max),
+                    mean: changed.iter().map(|c| c.change).sum::<f64>() / n,
+                    rms: (changed.iter().map(|c| c.change.powi(2)).sum::<f64>() / n).sqrt(),
+                    max_abs: max_abs.clone(),
+                }
+            });
+
+        let before_elements = self.elements().hash_map();
+        let after_elements = other.elements().hash_map();
+        let before_ids: HashSet<u32> = before_elements.keys().copied().collect();
+        let elements = ElementDiff {
+            added: after_elements
+                .keys()
+                .filter(|id| !before_ids.contains(id))
+                .copied()
+                .collect(),
+            removed: before_elements
+                .keys()
+                .filter(|id| !after_elements.contains_key(id))
+                .copied()
+                .collect(),
+            connectivity_changed: before_elements
+                .iter()
+                .filter(|(id, node_ids)| {
+                    after_elements
+                        .get(id)
+                        .is_some_and(|other_ids| other_ids != *node_ids)
+                })
+                .map(|(id, _)| *id)
+                .collect(),
+        };
+
+        let boundaries = BoundaryDiff {
+            open: boundary_type_diff(self, other, BoundaryType::Open),
+            land: boundary_type_diff(self, other, BoundaryType::Land),
+            interior: boundary_type_diff(self, other, BoundaryType::Interior),
+        };
+        let value_change = |before: Option<String>, after: Option<String>| {
+            (before != after).then_some(ValueChange { before, after })
+        };
+        HgridDiff {
+            tolerance,
+            nodes,
+            depths: DepthDiff { changed, stats },
+            elements,
+            boundaries,
+            crs: value_change(crs_definition(self), crs_definition(other)),
+            description: value_change(self.description().cloned(), other.description().cloned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generator::{Bathymetry, MeshGeneratorBuilder, SizeFunction};
+
+    #[test]
+    fn test_diff_depth_and_description() {
+        let mut builder = MeshGeneratorBuilder::default();
+        builder
+            .outer(vec![(0., 0.), (4., 0.), (4., 4.), (0., 4.)])
+            .size_function(SizeFunction::Constant(1.))
+            .bathymetry(Some(Bathymetry::Constant(5.)));
+        let before = builder.build().unwrap().generate().unwrap();
+        assert!(before.diff(&before).is_empty());
+
+        let after = builder
+            .bathymetry(Some(Bathymetry::Constant(7.)))
+            .description(Some("deepened".to_string()))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let diff = before.diff(&after);
+        assert!(!diff.geometry_changed());
+        assert!(!diff.boundaries_changed());
+        assert_eq!(diff.depths.changed.len(), before.nodes().len());
+        let stats = diff.depths.stats.as_ref().unwrap();
+        assert!((stats.mean - 2.).abs() < 1e-12);
+        assert_eq!(
+            diff.description.as_ref().unwrap().after.as_deref(),
+            Some("deepened")
+        );
+        assert!(diff.to_string().contains("Depths:"));
+        let json: serde_json::Value = serde_json::from_str(&diff.to_json().unwrap()).unwrap();
+        assert_eq!(json["depths"]["stats"]["max"], 2.);
+    }
+}
@@ -0,0 +1,127 @@
+// schismrs-hgrid/src/geometry.rs
+
+//! Planar geometry primitives shared by the mesh tools.
+
+/// Twice the signed area of triangle (a, b, c). Positive when counter-clockwise.
+pub(crate) fn orient2d(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Positive when `d` lies inside the circumcircle of the counter-clockwise triangle (a, b, c).
+pub(crate) fn incircle(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> f64 {
+    let adx = a[0] - d[0];
+    let ady = a[1] - d[1];
+    let bdx = b[0] - d[0];
+    let bdy = b[1] - d[1];
+    let cdx = c[0] - d[0];
+    let cdy = c[1] - d[1];
+    let ad = adx * adx + ady * ady;
+    let bd = bdx * bdx + bdy * bdy;
+    let cd = cdx * cdx + cdy * cdy;
+    adx * (bdy * cd - bd * cdy) - ady * (bdx * cd - bd * cdx) + ad * (bdx * cdy - bdy * cdx)
+}
+
+pub(crate) fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+pub(crate) fn midpoint(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [0.5 * (a[0] + b[0]), 0.5 * (a[1] + b[1])]
+}
+
+pub(crate) fn centroid(points: &[[f64; 2]]) -> [f64; 2] {
+    let n = points.len() as f64;
+    let (sx, sy) = points
+        .iter()
+        .fold((0., 0.), |(sx, sy), p| (sx + p[0], sy + p[1]));
+    [sx / n, sy / n]
+}
+
+/// Circumcenter of triangle (a, b, c), or `None` if the triangle is degenerate.
+pub(crate) fn circumcenter(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> Option<[f64; 2]> {
+    let bx = b[0] - a[0];
+    let by = b[1] - a[1];
+    let cx = c[0] - a[0];
+    let cy = c[1] - a[1];
+    let d = 2. * (bx * cy - by * cx);
+    if d == 0. {
+        return None;
+    }
+    let b2 = bx * bx + by * by;
+    let c2 = cx * cx + cy * cy;
+    Some([
+        a[0] + (cy * b2 - by * c2) / d,
+        a[1] + (bx * c2 - cx * b2) / d,
+    ])
+}
+
+/// True if `p` lies strictly inside the diametral circle of segment (a, b).
+pub(crate) fn encroaches(a: [f64; 2], b: [f64; 2], p: [f64; 2]) -> bool {
+    (a[0] - p[0]) * (b[0] - p[0]) + (a[1] - p[1]) * (b[1] - p[1]) < 0.
+}
+
+/// Signed area of a closed ring. Positive when counter-clockwise.
+pub(crate) fn ring_signed_area(ring: &[[f64; 2]]) -> f64 {
+    let n = ring.len();
+    let mut area = 0.;
+    for i in 0..n {
+        let a = ring[i];
+        let b = ring[(i + 1) % n];
+        area += a[0] * b[1] - b[0] * a[1];
+    }
+    0.5 * area
+}
+
+/// Even-odd point in polygon test for a closed ring.
+pub(crate) fn point_in_ring(p: [f64; 2], ring: &[[f64; 2]]) -> bool {
+    let n = ring.len();
+    let mut inside = false;
+    let mut j = n - 1;
+    for i in 0..n {
+        let a = ring[i];
+        let b = ring[j];
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Minimum interior angle of triangle (a, b, c) in degrees.
+pub(crate) fn min_angle_deg(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    let la = distance(b, c);
+    let lb = distance(c, a);
+    let lc = distance(a, b);
+    let angle = |opp: f64, s1: f64, s2: f64| -> f64 {
+        let cos = ((s1 * s1 + s2 * s2 - opp * opp) / (2. * s1 * s2)).clamp(-1., 1.);
+        cos.acos().to_degrees()
+    };
+    angle(la, lb, lc)
+        .min(angle(lb, lc, la))
+        .min(angle(lc, la, lb))
+}
+
+/// Interior angles in degrees of a simple polygon given in either orientation.
+///
+/// Angles at reflex corners are larger than 180 degrees.
+pub(crate) fn polygon_angles_deg(points: &[[f64; 2]]) -> Vec<f64> {
+    let n = points.len();
+    let orientation = ring_signed_area(points).signum();
+    (0..n)
+        .map(|i| {
+            let prev = points[(i + n - 1) % n];
+            let p = points[i];
+            let next = points[(i + 1) % n];
+            let u = [prev[0] - p[0], prev[1] - p[1]];
+            let v = [next[0] - p[0], next[1] - p[1]];
+            // turning from `next` to `prev` is through the interior for a
+            // counter-clockwise polygon
+            let cross = orientation * (v[0] * u[1] - v[1] * u[0]);
+            let dot = u[0] * v[0] + u[1] * v[1];
+            cross.atan2(dot).to_degrees().rem_euclid(360.)
+        })
+        .collect()
+}
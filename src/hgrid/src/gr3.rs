@@ -186,7 +186,7 @@ impl fmt::Display for Gr3ParserOutput {
             if let Some(interior) = &self.interior_boundaries {
                 for interior_bnd in interior.iter() {
                     total_number_of_non_ocean_boundaries += 1;
-                    total_number_of_non_ocean_boundaries += interior_bnd.len();
+                    total_number_of_non_ocean_boundaries_nodes += interior_bnd.len();
                 }
             }
             lines.push(format!(
@@ -202,7 +202,7 @@ impl fmt::Display for Gr3ParserOutput {
                 for (local_index, this_land_bound) in land.iter().enumerate() {
                     let fortran_index = local_index + 1;
                     lines.push(format!(
-                        "{} ! number of nodes for land_boundary_{}",
+                        "{} 0 ! number of nodes for land_boundary_{}",
                         this_land_bound.len(),
                         fortran_index
                     ));
@@ -218,7 +218,7 @@ impl fmt::Display for Gr3ParserOutput {
                 for (local_index, this_interior_bound) in interior.iter().enumerate() {
                     let fortran_index = local_index + 1;
                     lines.push(format!(
-                        "{} ! number of nodes for interior_boundary_{}",
+                        "{} 1 ! number of nodes for interior_boundary_{}",
                         this_interior_bound.len(),
                         fortran_index
                     ));
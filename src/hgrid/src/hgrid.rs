@@ -92,10 +92,16 @@ impl Hgrid {
         gr3_parser_output_builder.crs(self.crs().clone());
         if let Some(boundaries) = &self.boundaries {
             let the_type_map = boundaries.to_boundary_type_map();
-            gr3_parser_output_builder.open_boundaries(the_type_map[&BoundaryType::Open].clone());
-            gr3_parser_output_builder.land_boundaries(the_type_map[&BoundaryType::Land].clone());
-            gr3_parser_output_builder
-                .interior_boundaries(the_type_map[&BoundaryType::Interior].clone());
+            // boundary types without any boundary are absent from the map
+            let nodes_ids = |boundary_type: BoundaryType| {
+                the_type_map
+                    .get(&boundary_type)
+                    .map(|nodes_ids| (*nodes_ids).clone())
+                    .unwrap_or_default()
+            };
+            gr3_parser_output_builder.open_boundaries(nodes_ids(BoundaryType::Open));
+            gr3_parser_output_builder.land_boundaries(nodes_ids(BoundaryType::Land));
+            gr3_parser_output_builder.interior_boundaries(nodes_ids(BoundaryType::Interior));
         } else {
             gr3_parser_output_builder.open_boundaries(Vec::new());
             gr3_parser_output_builder.land_boundaries(Vec::new());
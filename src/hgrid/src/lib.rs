@@ -3,7 +3,18 @@ pub use hgrid::HgridBuilder;
 pub use hgrid::HgridTryFromError;
 
 pub mod boundaries;
+pub mod diff;
 pub mod elements;
 pub mod gr3;
 pub mod hgrid;
+pub mod mesh_generator;
 pub mod nodes;
+pub mod open_boundary_geometry;
+pub mod partition;
+pub mod quads;
+pub mod raster;
+pub mod transect;
+
+mod geometry;
+mod locator;
+mod triangulation;
@@ -0,0 +1,122 @@
+use super::{geometry::orient2d, hgrid::Hgrid};
+use std::collections::HashMap;
+
+/// Uniform bucket grid over element bounding boxes for point location.
+pub(crate) struct ElementLocator {
+    xmin: f64,
+    ymin: f64,
+    cell: f64,
+    ncols: usize,
+    nrows: usize,
+    buckets: Vec<Vec<usize>>,
+    elements: Vec<(u32, Vec<u32>, Vec<[f64; 2]>)>,
+}
+
+impl ElementLocator {
+    pub(crate) fn new(hgrid: &Hgrid) -> Self {
+        let nodes = hgrid.nodes();
+        let elements: Vec<(u32, Vec<u32>, Vec<[f64; 2]>)> = hgrid
+            .elements()
+            .hash_map()
+            .iter()
+            .map(|(&id, node_ids)| {
+                let points = node_ids
+                    .iter()
+                    .map(|node_id| {
+                        let (x, y) = nodes.get_node(*node_id).unwrap();
+                        [x, y]
+                    })
+                    .collect();
+                (id, node_ids.clone(), points)
+            })
+            .collect();
+        let (xmin, ymin, xmax, ymax) = elements.iter().flat_map(|(_, _, p)| p.iter()).fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(xmin, ymin, xmax, ymax), p| {
+                (
+                    xmin.min(p[0]),
+                    ymin.min(p[1]),
+                    xmax.max(p[0]),
+                    ymax.max(p[1]),
+                )
+            },
+        );
+        // about one element per bucket
+        let n = elements.len().max(1) as f64;
+        let cell = (((xmax - xmin) * (ymax - ymin)) / n)
+            .sqrt()
+            .max(f64::EPSILON);
+        let ncols = ((xmax - xmin) / cell).ceil().max(1.) as usize;
+        let nrows = ((ymax - ymin) / cell).ceil().max(1.) as usize;
+        let mut locator = Self {
+            xmin,
+            ymin,
+            cell,
+            ncols,
+            nrows,
+            buckets: vec![Vec::new(); ncols * nrows],
+            elements: Vec::new(),
+        };
+        for (index, (_, _, points)) in elements.iter().enumerate() {
+            let (c0, r0) = locator.bucket(
+                points
+                    .iter()
+                    .fold([f64::MAX; 2], |m, p| [m[0].min(p[0]), m[1].min(p[1])]),
+            );
+            let (c1, r1) = locator.bucket(
+                points
+                    .iter()
+                    .fold([f64::MIN; 2], |m, p| [m[0].max(p[0]), m[1].max(p[1])]),
+            );
+            for r in r0..=r1 {
+                for c in c0..=c1 {
+                    locator.buckets[r * ncols + c].push(index);
+                }
+            }
+        }
+        locator.elements = elements;
+        locator
+    }
+
+    fn bucket(&self, p: [f64; 2]) -> (usize, usize) {
+        let c = ((p[0] - self.xmin) / self.cell).floor().max(0.) as usize;
+        let r = ((p[1] - self.ymin) / self.cell).floor().max(0.) as usize;
+        (c.min(self.ncols - 1), r.min(self.nrows - 1))
+    }
+
+    /// Element containing `p` and the interpolation weights of its nodes.
+    ///
+    /// Quads are treated as the two triangles (1, 2, 3) and (1, 3, 4).
+    pub(crate) fn locate(&self, p: [f64; 2]) -> Option<(u32, HashMap<u32, f64>)> {
+        if p[0] < self.xmin
+            || p[1] < self.ymin
+            || p[0] > self.xmin + self.cell * self.ncols as f64
+            || p[1] > self.ymin + self.cell * self.nrows as f64
+        {
+            return None;
+        }
+        let (c, r) = self.bucket(p);
+        for &index in self.buckets[r * self.ncols + c].iter() {
+            let (id, node_ids, points) = &self.elements[index];
+            for k in 1..points.len() - 1 {
+                let (a, b, c) = (points[0], points[k], points[k + 1]);
+                let area = orient2d(a, b, c);
+                if area == 0. {
+                    continue;
+                }
+                let wa = orient2d(b, c, p) / area;
+                let wb = orient2d(c, a, p) / area;
+                let wc = orient2d(a, b, p) / area;
+                let tolerance = -1e-12;
+                if wa >= tolerance && wb >= tolerance && wc >= tolerance {
+                    let mut weights = HashMap::new();
+                    weights.insert(node_ids[0], wa);
+                    weights.insert(node_ids[k], wb);
+                    weights.insert(node_ids[k + 1], wc);
+                    return Some((*id, weights));
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,875 @@
+use super::{
+    boundaries::{
+        BoundariesBuilder, BoundariesBuilderError, InteriorBoundariesBuilder,
+        InteriorBoundariesBuilderError, LandBoundariesBuilder, LandBoundariesBuilderError,
+        OpenBoundariesBuilder, OpenBoundariesBuilderError,
+    },
+    elements::{ElementsBuilder, ElementsBuilderError},
+    geometry::{
+        centroid, circumcenter, distance, encroaches, incircle, midpoint, orient2d, point_in_ring,
+        ring_signed_area,
+    },
+    hgrid::{Hgrid, HgridBuilder, HgridBuilderError},
+    nodes::{NodesBuilder, NodesBuilderError},
+    raster::Raster,
+    triangulation::{Triangulation, SUPER_VERTICES},
+};
+use derive_builder::Builder;
+use linked_hash_map::LinkedHashMap;
+use proj::Proj;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Target element edge length.
+#[derive(Debug, Clone)]
+pub enum SizeFunction {
+    /// The same edge length everywhere.
+    Constant(f64),
+    /// `coefficient * sqrt(depth)` clamped to `[min_size, max_size]`, so that the
+    /// resolution follows the shallow water wave speed. Requires a bathymetry.
+    DepthBased {
+        coefficient: f64,
+        min_size: f64,
+        max_size: f64,
+    },
+    /// Edge length sampled from a raster. NODATA cells impose no size constraint.
+    Raster(Raster),
+}
+
+/// Still water depth, positive down.
+#[derive(Debug, Clone)]
+pub enum Bathymetry {
+    Constant(f64),
+    Raster(Raster),
+}
+
+impl Bathymetry {
+    fn depth(&self, p: [f64; 2]) -> Option<f64> {
+        match self {
+            Bathymetry::Constant(depth) => Some(*depth),
+            Bathymetry::Raster(raster) => raster.sample(p[0], p[1]),
+        }
+    }
+}
+
+/// A run of outer polygon edges that becomes an open boundary.
+///
+/// `start` and `end` are vertex indices of the outer polygon as given; the
+/// segment covers the edges from `start` to `end`, wrapping past the last
+/// vertex when `end < start`. These edges are not resampled, so the input
+/// vertices along them are kept as the open boundary nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenBoundarySegment {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Constrained Delaunay triangular mesh generator.
+///
+/// The outer polygon and islands are resampled to the target size, the
+/// boundary segments are recovered in a Delaunay triangulation and the
+/// interior is refined by circumcenter insertion (Ruppert) until every
+/// triangle meets the size and minimum angle targets. The result is smoothed
+/// and returned as an [`Hgrid`] with open, land and interior (island)
+/// boundaries classified.
+#[derive(Builder, Debug, Clone)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+pub struct MeshGenerator {
+    outer: Vec<(f64, f64)>,
+    #[builder(default)]
+    islands: Vec<Vec<(f64, f64)>>,
+    #[builder(default)]
+    open_boundaries: Vec<OpenBoundarySegment>,
+    size_function: SizeFunction,
+    #[builder(default)]
+    bathymetry: Option<Bathymetry>,
+    #[builder(default = "25.")]
+    min_angle: f64,
+    #[builder(default = "5")]
+    smoothing_iterations: usize,
+    #[builder(default = "5_000_000")]
+    max_nodes: usize,
+    #[builder(default)]
+    crs: Option<Arc<Proj>>,
+    #[builder(default)]
+    description: Option<String>,
+}
+
+impl MeshGeneratorBuilder {
+    pub fn validate(&self) -> Result<(), MeshGeneratorBuilderError> {
+        let error = |msg: &str| Err(MeshGeneratorBuilderError::ValidationError(msg.to_string()));
+        let outer_len = match &self.outer {
+            Some(outer) if outer.len() >= 3 => outer.len(),
+            Some(_) => return error("The outer polygon must have at least 3 vertices."),
+            None => return Ok(()),
+        };
+        if let Some(islands) = &self.islands {
+            if islands.iter().any(|island| island.len() < 3) {
+                return error("Island polygons must have at least 3 vertices.");
+            }
+        }
+        if let Some(open_boundaries) = &self.open_boundaries {
+            let mut covered = HashSet::new();
+            for segment in open_boundaries {
+                if segment.start >= outer_len || segment.end >= outer_len {
+                    return error("Open boundary segment index out of range of the outer polygon.");
+                }
+                if segment.start == segment.end {
+                    return error("Open boundary segments must span at least one edge.");
+                }
+                for edge in segment_edges(*segment, outer_len) {
+                    if !covered.insert(edge) {
+                        return error("Open boundary segments must not overlap.");
+                    }
+                }
+            }
+        }
+        if let Some(min_angle) = self.min_angle {
+            if !(min_angle > 0. && min_angle <= 34.) {
+                return error("min_angle must be in (0, 34] degrees.");
+            }
+        }
+        let has_bathymetry = matches!(self.bathymetry, Some(Some(_)));
+        match &self.size_function {
+            Some(SizeFunction::Constant(size)) if *size <= 0. => {
+                return error("Constant size must be positive.")
+            }
+            Some(SizeFunction::DepthBased {
+                coefficient,
+                min_size,
+                max_size,
+            }) => {
+                if !has_bathymetry {
+                    return error("A depth-based size function requires a bathymetry.");
+                }
+                if !(*coefficient > 0. && *min_size > 0. && max_size >= min_size) {
+                    return error("Depth-based size requires positive coefficient and 0 < min_size <= max_size.");
+                }
+            }
+            Some(SizeFunction::Raster(raster)) if raster.values().iter().any(|&v| v <= 0.) => {
+                return error("Size raster values must be positive.")
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SegmentTag {
+    Land,
+    Open(usize),
+    Island,
+}
+
+/// Indices of the outer polygon edges covered by an open boundary segment.
+fn segment_edges(segment: OpenBoundarySegment, n: usize) -> Vec<usize> {
+    let mut edges = Vec::new();
+    let mut i = segment.start;
+    while i != segment.end {
+        edges.push(i);
+        i = (i + 1) % n;
+    }
+    edges
+}
+
+impl MeshGenerator {
+    pub fn generate(&self) -> Result<Hgrid, MeshGeneratorError> {
+        let (outer_ring, outer_tags) = self.outer_ring();
+        let mut rings = vec![self.resample(&outer_ring, &outer_tags)];
+        for island in &self.islands {
+            let mut ring: Vec<[f64; 2]> = island.iter().map(|&(x, y)| [x, y]).collect();
+            if ring_signed_area(&ring) > 0. {
+                ring.reverse();
+            }
+            let tags = vec![SegmentTag::Island; ring.len()];
+            rings.push(self.resample(&ring, &tags));
+        }
+
+        let (xmin, ymin, xmax, ymax) = rings[0].iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(xmin, ymin, xmax, ymax), (p, _)| {
+                (
+                    xmin.min(p[0]),
+                    ymin.min(p[1]),
+                    xmax.max(p[0]),
+                    ymax.max(p[1]),
+                )
+            },
+        );
+        let mut mesher = Mesher {
+            generator: self,
+            triangulation: Triangulation::new(xmin, ymin, xmax, ymax),
+            polygons: rings
+                .iter()
+                .map(|ring| ring.iter().map(|(p, _)| *p).collect())
+                .collect(),
+            next: HashMap::new(),
+            prev: HashMap::new(),
+            min_split_length: 1e-9 * (xmax - xmin).max(ymax - ymin),
+        };
+        let mut ring_starts = Vec::with_capacity(rings.len());
+        for ring in rings.iter() {
+            let ids: Vec<usize> = ring
+                .iter()
+                .map(|(p, _)| mesher.triangulation.insert(*p))
+                .collect();
+            for (i, (_, tag)) in ring.iter().enumerate() {
+                let (a, b) = (ids[i], ids[(i + 1) % ids.len()]);
+                if a != b {
+                    mesher.next.insert(a, (b, *tag));
+                    mesher.prev.insert(b, a);
+                }
+            }
+            ring_starts.push(ids[0]);
+        }
+        mesher.recover_segments(mesher.segments())?;
+        mesher.refine()?;
+
+        let (mut points, mut triangles, index) = mesher.extract();
+        if triangles.is_empty() {
+            return Err(MeshGeneratorError::EmptyMesh);
+        }
+        let fixed_edges: HashSet<(usize, usize)> = mesher
+            .segments()
+            .into_iter()
+            .map(|(a, b)| (index[&a].min(index[&b]), index[&a].max(index[&b])))
+            .collect();
+        let boundary_nodes: HashSet<usize> =
+            fixed_edges.iter().flat_map(|&(a, b)| [a, b]).collect();
+        for _ in 0..self.smoothing_iterations {
+            laplacian_smoothing(&mut points, &triangles, &boundary_nodes);
+            flip_edges(&points, &mut triangles, &fixed_edges);
+        }
+
+        let rings: Vec<Vec<(usize, SegmentTag)>> = ring_starts
+            .iter()
+            .map(|&start| {
+                mesher
+                    .walk_ring(start)
+                    .into_iter()
+                    .map(|(v, tag)| (index[&v] + 1, tag))
+                    .collect()
+            })
+            .collect();
+        self.build_hgrid(&points, &triangles, &rings)
+    }
+
+    /// Outer ring as counter-clockwise coordinates with a tag per edge.
+    fn outer_ring(&self) -> (Vec<[f64; 2]>, Vec<SegmentTag>) {
+        let n = self.outer.len();
+        let mut ring: Vec<[f64; 2]> = self.outer.iter().map(|&(x, y)| [x, y]).collect();
+        let mut open_boundaries = self.open_boundaries.clone();
+        if ring_signed_area(&ring) < 0. {
+            ring.reverse();
+            for segment in open_boundaries.iter_mut() {
+                *segment = OpenBoundarySegment {
+                    start: n - 1 - segment.end,
+                    end: n - 1 - segment.start,
+                };
+            }
+            // keep the user's numbering of the open boundaries
+        }
+        let mut tags = vec![SegmentTag::Land; n];
+        for (k, segment) in open_boundaries.iter().enumerate() {
+            for edge in segment_edges(*segment, n) {
+                tags[edge] = SegmentTag::Open(k);
+            }
+        }
+        (ring, tags)
+    }
+
+    /// Split the edges of a ring to the target size, except fixed open boundary edges.
+    fn resample(&self, ring: &[[f64; 2]], tags: &[SegmentTag]) -> Vec<([f64; 2], SegmentTag)> {
+        let n = ring.len();
+        let mut out = Vec::new();
+        for i in 0..n {
+            let a = ring[i];
+            let b = ring[(i + 1) % n];
+            if distance(a, b) == 0. {
+                continue;
+            }
+            let pieces = match tags[i] {
+                SegmentTag::Open(_) => 1,
+                _ => {
+                    let size = self.target_size(midpoint(a, b));
+                    ((distance(a, b) / size).ceil() as usize).max(1)
+                }
+            };
+            for k in 0..pieces {
+                let t = k as f64 / pieces as f64;
+                out.push((
+                    [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])],
+                    tags[i],
+                ));
+            }
+        }
+        out
+    }
+
+    fn target_size(&self, p: [f64; 2]) -> f64 {
+        match &self.size_function {
+            SizeFunction::Constant(size) => *size,
+            SizeFunction::DepthBased {
+                coefficient,
+                min_size,
+                max_size,
+            } => self
+                .bathymetry
+                .as_ref()
+                .and_then(|bathymetry| bathymetry.depth(p))
+                .map_or(*max_size, |depth| {
+                    (coefficient * depth.max(0.).sqrt()).clamp(*min_size, *max_size)
+                }),
+            SizeFunction::Raster(raster) => raster.sample(p[0], p[1]).unwrap_or(f64::INFINITY),
+        }
+    }
+
+    fn build_hgrid(
+        &self,
+        points: &[[f64; 2]],
+        triangles: &[[usize; 3]],
+        rings: &[Vec<(usize, SegmentTag)>],
+    ) -> Result<Hgrid, MeshGeneratorError> {
+        let mut nodes_hash_map = LinkedHashMap::new();
+        for (i, p) in points.iter().enumerate() {
+            let value = match &self.bathymetry {
+                Some(bathymetry) => {
+                    let depth = bathymetry
+                        .depth(*p)
+                        .ok_or(MeshGeneratorError::MissingDepth(p[0], p[1]))?;
+                    // hgrid node values are stored with the sign reversed
+                    Some(vec![-depth])
+                }
+                None => None,
+            };
+            nodes_hash_map.insert(i as u32 + 1, (vec![p[0], p[1]], value));
+        }
+        let nodes = NodesBuilder::default()
+            .hash_map(nodes_hash_map)
+            .crs(self.crs.clone())
+            .build()
+            .map(Arc::new)?;
+        let elements = ElementsBuilder::default()
+            .nodes(nodes.clone())
+            .hash_map(
+                triangles
+                    .iter()
+                    .enumerate()
+                    .map(|(i, tri)| (i as u32 + 1, tri.iter().map(|&v| v as u32 + 1).collect()))
+                    .collect::<LinkedHashMap<u32, Vec<u32>>>(),
+            )
+            .build()?;
+
+        let mut open: BTreeMap<usize, Vec<u32>> = BTreeMap::new();
+        let mut land = Vec::new();
+        for (tag, run) in boundary_runs(&rings[0]) {
+            match tag {
+                SegmentTag::Open(k) => {
+                    open.insert(k, run);
+                }
+                _ => land.push(run),
+            }
+        }
+        let interior: Vec<Vec<u32>> = rings[1..]
+            .iter()
+            .map(|ring| ring.iter().map(|&(v, _)| v as u32).collect())
+            .collect();
+
+        let mut boundaries_builder = BoundariesBuilder::default();
+        if !open.is_empty() {
+            boundaries_builder.open(Some(
+                OpenBoundariesBuilder::default()
+                    .nodes(nodes.clone())
+                    .nodes_ids(open.into_values().collect::<Vec<_>>())
+                    .build()?,
+            ));
+        }
+        if !land.is_empty() {
+            boundaries_builder.land(Some(
+                LandBoundariesBuilder::default()
+                    .nodes(nodes.clone())
+                    .nodes_ids(land)
+                    .build()?,
+            ));
+        }
+        if !interior.is_empty() {
+            boundaries_builder.interior(Some(
+                InteriorBoundariesBuilder::default()
+                    .nodes(nodes.clone())
+                    .nodes_ids(interior)
+                    .build()?,
+            ));
+        }
+        Ok(HgridBuilder::default()
+            .nodes(nodes)
+            .elements(elements)
+            .boundaries(Some(boundaries_builder.build()?))
+            .description(self.description.clone())
+            .build()?)
+    }
+}
+
+/// Split a ring into runs of equally tagged edges, each including both end nodes.
+///
+/// A ring made of a single run is returned without repeating its first node.
+fn boundary_runs(ring: &[(usize, SegmentTag)]) -> Vec<(SegmentTag, Vec<u32>)> {
+    let n = ring.len();
+    let Some(offset) = (0..n).find(|&i| ring[i].1 != ring[(i + n - 1) % n].1) else {
+        return vec![(ring[0].1, ring.iter().map(|&(v, _)| v as u32).collect())];
+    };
+    let mut runs: Vec<(SegmentTag, Vec<u32>)> = Vec::new();
+    for k in 0..n {
+        let (v, tag) = ring[(offset + k) % n];
+        match runs.last_mut() {
+            Some((last_tag, run)) if *last_tag == tag => run.push(v as u32),
+            _ => {
+                if let Some((_, run)) = runs.last_mut() {
+                    run.push(v as u32);
+                }
+                runs.push((tag, vec![v as u32]));
+            }
+        }
+    }
+    if let Some((_, run)) = runs.last_mut() {
+        run.push(ring[offset].0 as u32);
+    }
+    runs
+}
+
+/// Points, triangles and the triangulation-to-mesh vertex map.
+type ExtractedMesh = (Vec<[f64; 2]>, Vec<[usize; 3]>, HashMap<usize, usize>);
+
+/// Working state of the triangulation while segments are recovered and refined.
+struct Mesher<'a> {
+    generator: &'a MeshGenerator,
+    triangulation: Triangulation,
+    polygons: Vec<Vec<[f64; 2]>>,
+    next: HashMap<usize, (usize, SegmentTag)>,
+    prev: HashMap<usize, usize>,
+    min_split_length: f64,
+}
+
+impl<'a> Mesher<'a> {
+    fn segments(&self) -> Vec<(usize, usize)> {
+        self.next.iter().map(|(&a, &(b, _))| (a, b)).collect()
+    }
+
+    fn in_domain(&self, p: [f64; 2]) -> bool {
+        point_in_ring(p, &self.polygons[0])
+            && !self.polygons[1..]
+                .iter()
+                .any(|island| point_in_ring(p, island))
+    }
+
+    fn is_fixed(&self, a: usize) -> bool {
+        matches!(self.next.get(&a), Some((_, SegmentTag::Open(_))))
+    }
+
+    fn check_size(&self) -> Result<(), MeshGeneratorError> {
+        let count = self.triangulation.points().len() - SUPER_VERTICES;
+        if count > self.generator.max_nodes {
+            return Err(MeshGeneratorError::MaxNodesExceeded(
+                self.generator.max_nodes,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Insert the midpoint of segment (a, b) and return the new vertex.
+    fn split_segment(&mut self, a: usize, b: usize) -> Result<usize, MeshGeneratorError> {
+        let points = self.triangulation.points();
+        let m = self.triangulation.insert(midpoint(points[a], points[b]));
+        let tag = self.next[&a].1;
+        self.next.insert(a, (m, tag));
+        self.next.insert(m, (b, tag));
+        self.prev.insert(m, a);
+        self.prev.insert(b, m);
+        self.check_size()?;
+        Ok(m)
+    }
+
+    /// Segments with an endpoint among the vertices adjacent to `v`.
+    fn segments_near(&self, v: usize) -> Vec<(usize, usize)> {
+        let mut near = Vec::new();
+        for t in self.triangulation.triangles_around(v) {
+            for u in self.triangulation.triangle(t) {
+                if let Some(&(w, _)) = self.next.get(&u) {
+                    near.push((u, w));
+                }
+                if let Some(&w) = self.prev.get(&u) {
+                    near.push((w, u));
+                }
+            }
+        }
+        near
+    }
+
+    /// Split segments that are missing from the triangulation or encroached
+    /// upon by an opposite vertex until every segment is a Delaunay edge.
+    ///
+    /// Fixed open boundary segments are only split when they are missing.
+    fn recover_segments(
+        &mut self,
+        queue: Vec<(usize, usize)>,
+    ) -> Result<Vec<usize>, MeshGeneratorError> {
+        let mut queue: VecDeque<(usize, usize)> = queue.into();
+        let mut inserted = Vec::new();
+        while let Some((a, b)) = queue.pop_front() {
+            if self.next.get(&a).map(|&(w, _)| w) != Some(b) {
+                continue;
+            }
+            let points = self.triangulation.points();
+            if distance(points[a], points[b]) < self.min_split_length {
+                continue;
+            }
+            let split = match self.triangulation.edge_apexes(a, b) {
+                None => true,
+                Some(apexes) => {
+                    !self.is_fixed(a)
+                        && apexes
+                            .iter()
+                            .any(|&c| encroaches(points[a], points[b], points[c]))
+                }
+            };
+            if split {
+                let m = self.split_segment(a, b)?;
+                queue.extend(self.segments_near(m));
+                inserted.push(m);
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// Ruppert refinement of the triangles inside the domain.
+    fn refine(&mut self) -> Result<(), MeshGeneratorError> {
+        let quality_bound = 1. / (2. * self.generator.min_angle.to_radians().sin());
+        let mut queue: VecDeque<usize> = self.triangulation.live_triangles().collect();
+        while let Some(t) = queue.pop_front() {
+            if !self.triangulation.is_alive(t) || self.triangulation.touches_super(t) {
+                continue;
+            }
+            let [a, b, c] = self.triangulation.triangle_points(t);
+            if !self.in_domain(centroid(&[a, b, c])) {
+                continue;
+            }
+            let Some(center) = circumcenter(a, b, c) else {
+                continue;
+            };
+            let radius = distance(center, a);
+            let lmin = distance(a, b).min(distance(b, c)).min(distance(c, a));
+            let size = self.generator.target_size(centroid(&[a, b, c]));
+            let too_large = radius > size / 3f64.sqrt();
+            let too_skinny = radius / lmin > quality_bound && lmin > 0.01 * size;
+            if !(too_large || too_skinny) {
+                continue;
+            }
+            let start = self.triangulation.locate(center);
+            let cavity = self.triangulation.cavity(center, start);
+            let points = self.triangulation.points();
+            let mut encroached = Vec::new();
+            for &s in cavity.iter() {
+                for u in self.triangulation.triangle(s) {
+                    if let Some(&(w, _)) = self.next.get(&u) {
+                        if encroaches(points[u], points[w], center) && !encroached.contains(&(u, w))
+                        {
+                            encroached.push((u, w));
+                        }
+                    }
+                }
+            }
+            let mut inserted = Vec::new();
+            if encroached.is_empty() {
+                if !self.in_domain(center) {
+                    continue;
+                }
+                inserted.push(self.triangulation.insert_into_cavity(center, &cavity));
+                self.check_size()?;
+                let near = self.segments_near(inserted[0]);
+                inserted.extend(self.recover_segments(near)?);
+            } else {
+                let splittable: Vec<(usize, usize)> = encroached
+                    .into_iter()
+                    .filter(|&(u, _)| !self.is_fixed(u))
+                    .collect();
+                if splittable.is_empty() {
+                    continue;
+                }
+                for (u, w) in splittable {
+                    if self.next.get(&u).map(|&(x, _)| x) == Some(w) {
+                        let m = self.split_segment(u, w)?;
+                        inserted.push(m);
+                        let near = self.segments_near(m);
+                        inserted.extend(self.recover_segments(near)?);
+                    }
+                }
+                queue.push_back(t);
+            }
+            for v in inserted {
+                queue.extend(self.triangulation.triangles_around(v));
+            }
+        }
+        Ok(())
+    }
+
+    /// Domain triangles with vertices renumbered from zero.
+    fn extract(&self) -> ExtractedMesh {
+        let domain: Vec<[usize; 3]> = self
+            .triangulation
+            .live_triangles()
+            .filter(|&t| !self.triangulation.touches_super(t))
+            .filter(|&t| self.in_domain(centroid(&self.triangulation.triangle_points(t))))
+            .map(|t| self.triangulation.triangle(t))
+            .collect();
+        let mut used: Vec<usize> = domain.iter().flatten().copied().collect();
+        used.sort_unstable();
+        used.dedup();
+        let index: HashMap<usize, usize> = used.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+        let all_points = self.triangulation.points();
+        let points = used.iter().map(|&v| all_points[v]).collect();
+        let triangles = domain
+            .iter()
+            .map(|tri| [index[&tri[0]], index[&tri[1]], index[&tri[2]]])
+            .collect();
+        (points, triangles, index)
+    }
+
+    fn walk_ring(&self, start: usize) -> Vec<(usize, SegmentTag)> {
+        let mut ring = Vec::new();
+        let mut v = start;
+        loop {
+            let (w, tag) = self.next[&v];
+            ring.push((v, tag));
+            v = w;
+            if v == start {
+                break;
+            }
+        }
+        ring
+    }
+}
+
+/// Move each free node to the mean of its neighbours unless that inverts an element.
+fn laplacian_smoothing(
+    points: &mut [[f64; 2]],
+    triangles: &[[usize; 3]],
+    boundary_nodes: &HashSet<usize>,
+) {
+    let mut neighbors: Vec<HashSet<usize>> = vec![HashSet::new(); points.len()];
+    let mut node_triangles: Vec<Vec<usize>> = vec![Vec::new(); points.len()];
+    for (t, tri) in triangles.iter().enumerate() {
+        for i in 0..3 {
+            neighbors[tri[i]].insert(tri[(i + 1) % 3]);
+            neighbors[tri[i]].insert(tri[(i + 2) % 3]);
+            node_triangles[tri[i]].push(t);
+        }
+    }
+    for v in 0..points.len() {
+        if boundary_nodes.contains(&v) || neighbors[v].is_empty() {
+            continue;
+        }
+        let around: Vec<[f64; 2]> = neighbors[v].iter().map(|&u| points[u]).collect();
+        let old = points[v];
+        points[v] = centroid(&around);
+        let inverted = node_triangles[v].iter().any(|&t| {
+            let [a, b, c] = triangles[t];
+            orient2d(points[a], points[b], points[c]) <= 0.
+        });
+        if inverted {
+            points[v] = old;
+        }
+    }
+}
+
+/// One pass of Lawson flips over the non-boundary edges.
+fn flip_edges(
+    points: &[[f64; 2]],
+    triangles: &mut [[usize; 3]],
+    fixed_edges: &HashSet<(usize, usize)>,
+) {
+    let mut edges: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for (t, tri) in triangles.iter().enumerate() {
+        for i in 0..3 {
+            let (a, b) = (tri[i], tri[(i + 1) % 3]);
+            edges.entry((a.min(b), a.max(b))).or_default().push(t);
+        }
+    }
+    let mut touched = vec![false; triangles.len()];
+    for (edge, shared) in edges {
+        if shared.len() != 2 || fixed_edges.contains(&edge) {
+            continue;
+        }
+        let (t1, t2) = (shared[0], shared[1]);
+        if touched[t1] || touched[t2] {
+            continue;
+        }
+        // t1 = (c, a, b) and t2 = (d, b, a), both counter-clockwise
+        let i = (0..3)
+            .find(|&i| triangles[t1][i] != edge.0 && triangles[t1][i] != edge.1)
+            .unwrap();
+        let [c, a, b] = [0, 1, 2].map(|k| triangles[t1][(i + k) % 3]);
+        let d = triangles[t2]
+            .iter()
+            .copied()
+            .find(|&v| v != a && v != b)
+            .unwrap();
+        let (pa, pb, pc, pd) = (points[a], points[b], points[c], points[d]);
+        if incircle(pc, pa, pb, pd) > 0. && orient2d(pc, pa, pd) > 0. && orient2d(pc, pd, pb) > 0. {
+            triangles[t1] = [c, a, d];
+            triangles[t2] = [c, d, b];
+            touched[t1] = true;
+            touched[t2] = true;
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MeshGeneratorError {
+    #[error("Mesh generation exceeded the maximum of {0} nodes")]
+    MaxNodesExceeded(usize),
+
+    #[error("Mesh generation produced no elements inside the domain")]
+    EmptyMesh,
+
+    #[error("Bathymetry is undefined at node ({0}, {1})")]
+    MissingDepth(f64, f64),
+
+    #[error(transparent)]
+    NodesBuilderError(#[from] NodesBuilderError),
+
+    #[error(transparent)]
+    ElementsBuilderError(#[from] ElementsBuilderError),
+
+    #[error(transparent)]
+    BoundariesBuilderError(#[from] BoundariesBuilderError),
+
+    #[error(transparent)]
+    OpenBoundariesBuilderError(#[from] OpenBoundariesBuilderError),
+
+    #[error(transparent)]
+    LandBoundariesBuilderError(#[from] LandBoundariesBuilderError),
+
+    #[error(transparent)]
+    InteriorBoundariesBuilderError(#[from] InteriorBoundariesBuilderError),
+
+    #[error(transparent)]
+    HgridBuilderError(#[from] HgridBuilderError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundaries::BoundaryType;
+    use crate::geometry::min_angle_deg;
+
+    fn square(side: f64) -> Vec<(f64, f64)> {
+        vec![(0., 0.), (side, 0.), (side, side), (0., side)]
+    }
+
+    fn min_angle(hgrid: &Hgrid) -> f64 {
+        let nodes = hgrid.nodes();
+        hgrid
+            .elements()
+            .hash_map()
+            .values()
+            .map(|tri| {
+                let p = |i: usize| {
+                    let (x, y) = nodes.get_node(tri[i]).unwrap();
+                    [x, y]
+                };
+                min_angle_deg(p(0), p(1), p(2))
+            })
+            .fold(f64::MAX, f64::min)
+    }
+
+    #[test]
+    fn test_square_constant_size() {
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(square(10.))
+            .size_function(SizeFunction::Constant(1.))
+            .bathymetry(Some(Bathymetry::Constant(5.)))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        assert!(hgrid.nodes().len() > 100);
+        assert!(min_angle(&hgrid) > 20.);
+        assert!(hgrid.depths().iter().all(|&depth| depth == -5.));
+        let area: f64 = hgrid
+            .elements()
+            .hash_map()
+            .values()
+            .map(|tri| {
+                let p = |i: usize| {
+                    let (x, y) = hgrid.nodes().get_node(tri[i]).unwrap();
+                    [x, y]
+                };
+                0.5 * orient2d(p(0), p(1), p(2))
+            })
+            .sum();
+        assert!((area - 100.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        // no open boundary and no island, so only land boundaries are set
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(square(4.))
+            .size_function(SizeFunction::Constant(1.))
+            .bathymetry(Some(Bathymetry::Constant(5.)))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let gr3 = tempfile::NamedTempFile::new().unwrap();
+        hgrid.write(gr3.path()).unwrap();
+        let reloaded = Hgrid::try_from(&gr3.path().to_path_buf()).unwrap();
+        assert_eq!(reloaded.nodes().len(), hgrid.nodes().len());
+        assert_eq!(reloaded.elements().hash_map(), hgrid.elements().hash_map());
+        assert_eq!(reloaded.depths(), hgrid.depths());
+    }
+
+    #[test]
+    fn test_island_and_boundaries() {
+        let island = vec![(4., 4.), (6., 4.), (6., 6.), (4., 6.)];
+        let mut outer = vec![(0., 0.)];
+        outer.extend((0..=10).map(|i| (10., i as f64)));
+        outer.push((0., 10.));
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(outer)
+            .islands(vec![island])
+            .open_boundaries(vec![OpenBoundarySegment { start: 1, end: 11 }])
+            .size_function(SizeFunction::Constant(1.))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let nodes = hgrid.nodes();
+        for tri in hgrid.elements().hash_map().values() {
+            let (x, y) = tri
+                .iter()
+                .map(|&v| nodes.get_node(v).unwrap())
+                .fold((0., 0.), |(sx, sy), (x, y)| (sx + x / 3., sy + y / 3.));
+            assert!(!(x > 4. && x < 6. && y > 4. && y < 6.));
+        }
+        let map = hgrid.boundaries().unwrap().to_boundary_type_map();
+        let open = map[&BoundaryType::Open];
+        assert_eq!(open.len(), 1);
+        // the fixed open boundary edge is not resampled
+        assert_eq!(open[0].len(), 11);
+        assert_eq!(map[&BoundaryType::Land].len(), 1);
+        assert_eq!(map[&BoundaryType::Interior].len(), 1);
+        assert!(min_angle(&hgrid) > 15.);
+    }
+
+    #[test]
+    fn test_invalid_open_boundary() {
+        let result = MeshGeneratorBuilder::default()
+            .outer(square(10.))
+            .open_boundaries(vec![OpenBoundarySegment { start: 1, end: 7 }])
+            .size_function(SizeFunction::Constant(1.))
+            .build();
+        assert!(result.is_err());
+    }
+}
@@ -21,6 +21,18 @@ impl Nodes {
         self.crs.clone()
     }
 
+    /// True when the CRS is geographic (longitude, latitude in degrees).
+    pub fn is_geographic(&self) -> bool {
+        self.crs.as_ref().is_some_and(|crs| {
+            crs.proj_info().definition.is_some_and(|definition| {
+                let definition = definition.to_lowercase();
+                definition.contains("longlat")
+                    || definition.contains("latlong")
+                    || definition.contains("epsg:4326")
+            })
+        })
+    }
+
     // pub fn new(hash_maptree_map, crs }
     // }
 
@@ -0,0 +1,196 @@
+use super::{boundaries::OpenBoundaries, geometry::orient2d, hgrid::Hgrid};
+use ndarray::{Array1, Array2};
+use std::collections::HashMap;
+
+const EARTH_RADIUS: f64 = 6_371_000.;
+
+/// Geometry of a single open boundary, in the order of its node ids.
+///
+/// Lengths and areas are in metres when the mesh CRS is geographic, otherwise
+/// in the units of the mesh coordinates. Normals point out of the domain,
+/// assuming SCHISM's counter-clockwise ordering (domain on the left), and are
+/// expressed as (east, north) components for geographic meshes.
+#[derive(Debug, Clone)]
+pub struct OpenBoundaryGeometry {
+    pub node_ids: Vec<u32>,
+    /// Node coordinates, shape (n, 2).
+    pub coordinates: Array2<f64>,
+    /// Still water depth per node, positive down.
+    pub depths: Array1<f64>,
+    /// Outward unit normal per edge, shape (n - 1, 2).
+    pub edge_normals: Array2<f64>,
+    /// Outward unit normal per node (average of the adjacent edges), shape (n, 2).
+    pub node_normals: Array2<f64>,
+    pub segment_lengths: Array1<f64>,
+    pub total_length: f64,
+    /// Wetted area below mean sea level, integrating depths linearly along each edge.
+    pub cross_sectional_area: f64,
+}
+
+impl OpenBoundaryGeometry {
+    /// Mean normal velocity for a discharge through this boundary, positive outward.
+    pub fn velocity_from_discharge(&self, discharge: f64) -> f64 {
+        discharge / self.cross_sectional_area
+    }
+
+    /// Rotate a velocity at boundary node `index` into (normal, tangential)
+    /// components. The tangent follows the boundary ordering.
+    pub fn to_normal_tangential(&self, index: usize, u: f64, v: f64) -> (f64, f64) {
+        let nx = self.node_normals[[index, 0]];
+        let ny = self.node_normals[[index, 1]];
+        (u * nx + v * ny, -u * ny + v * nx)
+    }
+}
+
+impl OpenBoundaries {
+    pub fn geometry(&self) -> Vec<OpenBoundaryGeometry> {
+        let nodes = self.nodes();
+        let is_geographic = nodes.is_geographic();
+        self.nodes_ids()
+            .iter()
+            .map(|node_ids| {
+                let n = node_ids.len();
+                let mut coordinates = Array2::zeros((n, 2));
+                let mut depths = Array1::zeros(n);
+                for (i, id) in node_ids.iter().enumerate() {
+                    let (coords, values) = &nodes.hash_map()[id];
+                    coordinates[[i, 0]] = coords[0];
+                    coordinates[[i, 1]] = coords[1];
+                    // node values have their sign reversed, so depths are negated
+                    depths[i] = values
+                        .as_ref()
+                        .and_then(|v| v.first())
+                        .map_or(0., |value| -value);
+                }
+                let edges = n.saturating_sub(1);
+                let mut edge_normals = Array2::zeros((edges, 2));
+                let mut segment_lengths = Array1::zeros(edges);
+                let mut cross_sectional_area = 0.;
+                for i in 0..edges {
+                    let (dx, dy) = edge_vector(&coordinates, i, is_geographic);
+                    let length = dx.hypot(dy);
+                    segment_lengths[i] = length;
+                    if length > 0. {
+                        edge_normals[[i, 0]] = dy / length;
+                        edge_normals[[i, 1]] = -dx / length;
+                    }
+                    cross_sectional_area +=
+                        0.5 * length * (depths[i].max(0.) + depths[i + 1].max(0.));
+                }
+                let mut node_normals = Array2::zeros((n, 2));
+                for i in 0..n {
+                    let mut nx = 0.;
+                    let mut ny = 0.;
+                    for e in [i.wrapping_sub(1), i] {
+                        if e < edges {
+                            nx += edge_normals[[e, 0]];
+                            ny += edge_normals[[e, 1]];
+                        }
+                    }
+                    let norm = nx.hypot(ny);
+                    if norm > 0. {
+                        node_normals[[i, 0]] = nx / norm;
+                        node_normals[[i, 1]] = ny / norm;
+                    }
+                }
+                OpenBoundaryGeometry {
+                    node_ids: node_ids.clone(),
+                    coordinates,
+                    depths,
+                    edge_normals,
+                    node_normals,
+                    total_length: segment_lengths.sum(),
+                    segment_lengths,
+                    cross_sectional_area,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Vector along edge `i`, converted to metres on a local tangent plane for geographic coordinates.
+fn edge_vector(coordinates: &Array2<f64>, i: usize, is_geographic: bool) -> (f64, f64) {
+    let dx = coordinates[[i + 1, 0]] - coordinates[[i, 0]];
+    let dy = coordinates[[i + 1, 1]] - coordinates[[i, 1]];
+    if is_geographic {
+        let latitude = 0.5 * (coordinates[[i + 1, 1]] + coordinates[[i, 1]]);
+        (
+            EARTH_RADIUS * latitude.to_radians().cos() * dx.to_radians(),
+            EARTH_RADIUS * dy.to_radians(),
+        )
+    } else {
+        (dx, dy)
+    }
+}
+
+impl Hgrid {
+    /// For each open boundary, whether its nodes follow SCHISM's counter-clockwise
+    /// convention, i.e. every boundary edge has the domain on its left.
+    pub fn open_boundaries_are_counter_clockwise(&self) -> Vec<bool> {
+        let Some(open) = self.boundaries().and_then(|b| b.open()) else {
+            return Vec::new();
+        };
+        let mut edge_apex: HashMap<(u32, u32), u32> = HashMap::new();
+        for node_ids in self.elements().hash_map().values() {
+            let n = node_ids.len();
+            for i in 0..n {
+                edge_apex.insert((node_ids[i], node_ids[(i + 1) % n]), node_ids[(i + 2) % n]);
+            }
+        }
+        let nodes = self.nodes();
+        let point = |id: u32| {
+            let (x, y) = nodes.get_node(id).unwrap();
+            [x, y]
+        };
+        open.nodes_ids()
+            .iter()
+            .map(|node_ids| {
+                node_ids.windows(2).all(|edge| {
+                    let (a, b) = (edge[0], edge[1]);
+                    // elements are counter-clockwise, so the interior element
+                    // traverses the edge in the boundary direction
+                    match edge_apex.get(&(a, b)) {
+                        Some(&c) => orient2d(point(a), point(b), point(c)) > 0.,
+                        None => false,
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generator::{
+        Bathymetry, MeshGeneratorBuilder, OpenBoundarySegment, SizeFunction,
+    };
+
+    #[test]
+    fn test_square_open_boundary_geometry() {
+        let mut outer = vec![(0., 0.)];
+        outer.extend((0..=5).map(|i| (10., 2. * i as f64)));
+        outer.push((0., 10.));
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(outer)
+            .open_boundaries(vec![OpenBoundarySegment { start: 1, end: 6 }])
+            .size_function(SizeFunction::Constant(2.))
+            .bathymetry(Some(Bathymetry::Constant(5.)))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let geometry = hgrid.boundaries().unwrap().open().unwrap().geometry();
+        assert_eq!(geometry.len(), 1);
+        let boundary = &geometry[0];
+        assert!((boundary.total_length - 10.).abs() < 1e-9);
+        assert!((boundary.cross_sectional_area - 50.).abs() < 1e-9);
+        assert!(boundary
+            .node_normals
+            .outer_iter()
+            .all(|normal| (normal[0] - 1.).abs() < 1e-9 && normal[1].abs() < 1e-9));
+        assert!((boundary.velocity_from_discharge(100.) - 2.).abs() < 1e-9);
+        let (normal, tangential) = boundary.to_normal_tangential(0, 1., 2.);
+        assert!((normal - 1.).abs() < 1e-9 && (tangential - 2.).abs() < 1e-9);
+        assert_eq!(hgrid.open_boundaries_are_counter_clockwise(), vec![true]);
+    }
+}
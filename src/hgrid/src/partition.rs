@@ -0,0 +1,426 @@
+use super::{
+    elements::{ElementsBuilder, ElementsBuilderError},
+    hgrid::{Hgrid, HgridBuilder, HgridBuilderError},
+    nodes::{NodesBuilder, NodesBuilderError},
+};
+use derive_builder::Builder;
+use linked_hash_map::LinkedHashMap;
+use ndarray::Array1;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Node partitioner previewing an MPI domain decomposition.
+///
+/// Nodes are split by recursive coordinate bisection on their weights, then
+/// boundary nodes are moved greedily between neighbouring partitions to
+/// reduce the edge cut while keeping the load within `imbalance_tolerance`.
+/// Weights default to one per node; pass the number of vertical levels per
+/// node to balance 3D work.
+#[derive(Builder, Debug, Clone)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+pub struct Partitioner {
+    nparts: usize,
+    #[builder(default)]
+    weights: Option<Array1<f64>>,
+    #[builder(default = "4")]
+    refinement_passes: usize,
+    #[builder(default = "1.03")]
+    imbalance_tolerance: f64,
+}
+
+impl PartitionerBuilder {
+    pub fn validate(&self) -> Result<(), PartitionerBuilderError> {
+        if self.nparts == Some(0) {
+            return Err(PartitionerBuilderError::ValidationError(
+                "nparts must be at least 1.".to_string(),
+            ));
+        }
+        if let Some(Some(weights)) = &self.weights {
+            if weights.iter().any(|&w| w < 0. || !w.is_finite()) {
+                return Err(PartitionerBuilderError::ValidationError(
+                    "Node weights must be finite and non-negative.".to_string(),
+                ));
+            }
+        }
+        if self
+            .imbalance_tolerance
+            .is_some_and(|tolerance| tolerance < 1.)
+        {
+            return Err(PartitionerBuilderError::ValidationError(
+                "imbalance_tolerance must be at least 1.".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Load balance and communication statistics of a [`Partition`].
+#[derive(Debug, Clone)]
+pub struct PartitionReport {
+    pub nparts: usize,
+    pub owned_nodes: Vec<usize>,
+    pub ghost_nodes: Vec<usize>,
+    pub weights: Vec<f64>,
+    /// Mesh edges whose end nodes belong to different partitions.
+    pub edge_cut: usize,
+    /// Largest partition weight over the mean partition weight.
+    pub imbalance: f64,
+    /// Total ghost nodes over total owned nodes.
+    pub ghost_overhead: f64,
+}
+
+impl fmt::Display for PartitionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "partitions: {}", self.nparts)?;
+        writeln!(f, "edge cut: {}", self.edge_cut)?;
+        writeln!(f, "imbalance: {:.4}", self.imbalance)?;
+        writeln!(f, "ghost overhead: {:.2}%", 100. * self.ghost_overhead)?;
+        writeln!(
+            f,
+            "{:>6} {:>10} {:>10} {:>14}",
+            "part", "owned", "ghost", "weight"
+        )?;
+        for p in 0..self.nparts {
+            writeln!(
+                f,
+                "{:>6} {:>10} {:>10} {:>14.1}",
+                p, self.owned_nodes[p], self.ghost_nodes[p], self.weights[p]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Partition id per node, in the node order of the partitioned [`Hgrid`].
+#[derive(Debug, Clone)]
+pub struct Partition {
+    pub node_ids: Vec<u32>,
+    pub parts: Vec<usize>,
+    pub report: PartitionReport,
+}
+
+impl Partition {
+    /// Copy of `hgrid` whose node values are the partition ids.
+    pub fn to_hgrid(&self, hgrid: &Hgrid) -> Result<Hgrid, PartitionError> {
+        let part_of: HashMap<u32, usize> = self
+            .node_ids
+            .iter()
+            .copied()
+            .zip(self.parts.iter().copied())
+            .collect();
+        let hash_map: LinkedHashMap<u32, (Vec<f64>, Option<Vec<f64>>)> = hgrid
+            .nodes()
+            .hash_map()
+            .iter()
+            .map(|(&id, (coords, _))| {
+                // hgrid values are written with the sign reversed
+                let value = -(part_of[&id] as f64);
+                (id, (coords.clone(), Some(vec![value])))
+            })
+            .collect();
+        let nodes = NodesBuilder::default()
+            .hash_map(hash_map)
+            .crs(hgrid.crs())
+            .build()
+            .map(Arc::new)?;
+        let elements = ElementsBuilder::default()
+            .nodes(nodes.clone())
+            .hash_map(hgrid.elements().hash_map().clone())
+            .build()?;
+        Ok(HgridBuilder::default()
+            .nodes(nodes)
+            .elements(elements)
+            .boundaries(None)
+            .description(Some(format!(
+                "partition ids ({} parts)",
+                self.report.nparts
+            )))
+            .build()?)
+    }
+
+    /// Write the partition ids as a gr3 for inspection.
+    pub fn write_gr3(&self, hgrid: &Hgrid, path: &Path) -> Result<(), PartitionError> {
+        Ok(self.to_hgrid(hgrid)?.write(path)?)
+    }
+}
+
+impl Partitioner {
+    pub fn partition(&self, hgrid: &Hgrid) -> Result<Partition, PartitionError> {
+        let node_ids: Vec<u32> = hgrid.nodes().hash_map().keys().copied().collect();
+        let n = node_ids.len();
+        let weights: Vec<f64> = match &self.weights {
+            Some(weights) if weights.len() != n => {
+                return Err(PartitionError::WeightsLengthMismatch(weights.len(), n))
+            }
+            Some(weights) => weights.to_vec(),
+            None => vec![1.; n],
+        };
+        let nparts = self.nparts.min(n.max(1));
+        let index: HashMap<u32, usize> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+        let adjacency = node_adjacency(hgrid, &index);
+        let xy = hgrid.xy();
+
+        let mut parts = vec![0; n];
+        let mut order: Vec<usize> = (0..n).collect();
+        recursive_bisection(&mut order, &weights, &xy, 0, nparts, &mut parts);
+        let mut part_weights = vec![0.; nparts];
+        for i in 0..n {
+            part_weights[parts[i]] += weights[i];
+        }
+        let max_weight = self.imbalance_tolerance * weights.iter().sum::<f64>() / nparts as f64;
+        for _ in 0..self.refinement_passes {
+            let moved = refine(
+                &adjacency,
+                &weights,
+                max_weight,
+                &mut parts,
+                &mut part_weights,
+            );
+            if moved == 0 {
+                break;
+            }
+        }
+        let report = report(hgrid, &index, &adjacency, &parts, part_weights);
+        Ok(Partition {
+            node_ids,
+            parts,
+            report,
+        })
+    }
+}
+
+fn node_adjacency(hgrid: &Hgrid, index: &HashMap<u32, usize>) -> Vec<BTreeSet<usize>> {
+    let mut adjacency = vec![BTreeSet::new(); index.len()];
+    for node_ids in hgrid.elements().hash_map().values() {
+        let k = node_ids.len();
+        for i in 0..k {
+            let a = index[&node_ids[i]];
+            let b = index[&node_ids[(i + 1) % k]];
+            adjacency[a].insert(b);
+            adjacency[b].insert(a);
+        }
+    }
+    adjacency
+}
+
+/// Assign `first_part..first_part + nparts` to the nodes in `order` by
+/// splitting along the longer extent at the weighted median.
+fn recursive_bisection(
+    order: &mut [usize],
+    weights: &[f64],
+    xy: &ndarray::Array2<f64>,
+    first_part: usize,
+    nparts: usize,
+    parts: &mut [usize],
+) {
+    if nparts <= 1 || order.len() <= 1 {
+        for &i in order.iter() {
+            parts[i] = first_part;
+        }
+        return;
+    }
+    let extent = |axis: usize| {
+        let (min, max) = order.iter().fold((f64::MAX, f64::MIN), |(min, max), &i| {
+            (min.min(xy[[i, axis]]), max.max(xy[[i, axis]]))
+        });
+        max - min
+    };
+    let axis = if extent(0) >= extent(1) { 0 } else { 1 };
+    order.sort_by(|&a, &b| xy[[a, axis]].total_cmp(&xy[[b, axis]]));
+    let left_parts = nparts / 2;
+    let total: f64 = order.iter().map(|&i| weights[i]).sum();
+    let target = total * left_parts as f64 / nparts as f64;
+    let mut cumulative = 0.;
+    let mut split = 0;
+    while split < order.len() - 1 && cumulative + 0.5 * weights[order[split]] < target {
+        cumulative += weights[order[split]];
+        split += 1;
+    }
+    let split = split.max(1);
+    let (left, right) = order.split_at_mut(split);
+    recursive_bisection(left, weights, xy, first_part, left_parts, parts);
+    recursive_bisection(
+        right,
+        weights,
+        xy,
+        first_part + left_parts,
+        nparts - left_parts,
+        parts,
+    );
+}
+
+/// One greedy pass moving boundary nodes to the neighbouring partition they
+/// share most edges with. Returns the number of moved nodes.
+fn refine(
+    adjacency: &[BTreeSet<usize>],
+    weights: &[f64],
+    max_weight: f64,
+    parts: &mut [usize],
+    part_weights: &mut [f64],
+) -> usize {
+    let mut moved = 0;
+    for i in 0..parts.len() {
+        let own = parts[i];
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for &j in adjacency[i].iter() {
+            *counts.entry(parts[j]).or_default() += 1;
+        }
+        let own_count = counts.get(&own).copied().unwrap_or(0);
+        let best = counts
+            .iter()
+            .filter(|(&p, _)| p != own)
+            .max_by_key(|(&p, &count)| (count, std::cmp::Reverse(p)));
+        if let Some((&target, &count)) = best {
+            // keep partitions from emptying out and respect the balance limit
+            if count > own_count
+                && part_weights[target] + weights[i] <= max_weight
+                && part_weights[own] - weights[i] > 0.
+            {
+                parts[i] = target;
+                part_weights[own] -= weights[i];
+                part_weights[target] += weights[i];
+                moved += 1;
+            }
+        }
+    }
+    moved
+}
+
+fn report(
+    hgrid: &Hgrid,
+    index: &HashMap<u32, usize>,
+    adjacency: &[BTreeSet<usize>],
+    parts: &[usize],
+    weights: Vec<f64>,
+) -> PartitionReport {
+    let nparts = weights.len();
+    let mut owned_nodes = vec![0; nparts];
+    for &p in parts {
+        owned_nodes[p] += 1;
+    }
+    let edge_cut = adjacency
+        .iter()
+        .enumerate()
+        .map(|(i, neighbors)| {
+            neighbors
+                .iter()
+                .filter(|&&j| j > i && parts[j] != parts[i])
+                .count()
+        })
+        .sum();
+    // a ghost node is a node of another partition sharing an element with an owned node
+    let mut ghosts: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); nparts];
+    for node_ids in hgrid.elements().hash_map().values() {
+        let local: Vec<usize> = node_ids.iter().map(|id| index[id]).collect();
+        for &a in local.iter() {
+            for &b in local.iter() {
+                if parts[a] != parts[b] {
+                    ghosts[parts[a]].insert(b);
+                }
+            }
+        }
+    }
+    let ghost_nodes: Vec<usize> = ghosts.iter().map(|g| g.len()).collect();
+    let mean = weights.iter().sum::<f64>() / nparts as f64;
+    let imbalance = weights.iter().copied().fold(0., f64::max) / mean;
+    let ghost_overhead = ghost_nodes.iter().sum::<usize>() as f64 / parts.len() as f64;
+    PartitionReport {
+        nparts,
+        owned_nodes,
+        ghost_nodes,
+        weights,
+        edge_cut,
+        imbalance,
+        ghost_overhead,
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PartitionError {
+    #[error("Expected one weight per node: got {0} weights for {1} nodes")]
+    WeightsLengthMismatch(usize, usize),
+
+    #[error(transparent)]
+    NodesBuilderError(#[from] NodesBuilderError),
+
+    #[error(transparent)]
+    ElementsBuilderError(#[from] ElementsBuilderError),
+
+    #[error(transparent)]
+    HgridBuilderError(#[from] HgridBuilderError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh_generator::{MeshGeneratorBuilder, SizeFunction};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_partition_square() {
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(vec![(0., 0.), (20., 0.), (20., 10.), (0., 10.)])
+            .size_function(SizeFunction::Constant(1.))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let partition = PartitionerBuilder::default()
+            .nparts(4_usize)
+            .build()
+            .unwrap()
+            .partition(&hgrid)
+            .unwrap();
+        let report = &partition.report;
+        assert_eq!(
+            report.owned_nodes.iter().sum::<usize>(),
+            hgrid.nodes().len()
+        );
+        assert!(report.owned_nodes.iter().all(|&count| count > 0));
+        assert!(report.imbalance <= 1.03 + 1e-9);
+        assert!(report.edge_cut > 0);
+        assert!(report.ghost_nodes.iter().all(|&count| count > 0));
+
+        let temp_file = NamedTempFile::new().unwrap();
+        partition.write_gr3(&hgrid, temp_file.path()).unwrap();
+        let exported = Hgrid::try_from(&temp_file.path().to_path_buf()).unwrap();
+        // gr3 values are read back with the sign reversed
+        let max_id = exported
+            .depths()
+            .iter()
+            .map(|v| -v)
+            .fold(f64::MIN, f64::max);
+        assert_eq!(max_id, 3.);
+    }
+
+    #[test]
+    fn test_weights_length_mismatch() {
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(vec![(0., 0.), (2., 0.), (2., 2.), (0., 2.)])
+            .size_function(SizeFunction::Constant(1.))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let result = PartitionerBuilder::default()
+            .nparts(2_usize)
+            .weights(Some(Array1::from(vec![1.; 2])))
+            .build()
+            .unwrap()
+            .partition(&hgrid);
+        assert!(matches!(
+            result,
+            Err(PartitionError::WeightsLengthMismatch(2, _))
+        ));
+    }
+}
@@ -0,0 +1,369 @@
+use super::{
+    elements::{ElementsBuilder, ElementsBuilderError},
+    geometry::{min_angle_deg, orient2d, polygon_angles_deg},
+    hgrid::{Hgrid, HgridBuilder, HgridBuilderError},
+    nodes::Nodes,
+};
+use linked_hash_map::LinkedHashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Angle statistics over all elements of a mesh, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshQuality {
+    pub min_angle: f64,
+    pub max_angle: f64,
+    pub mean_min_angle: f64,
+    pub triangles: usize,
+    pub quads: usize,
+}
+
+impl MeshQuality {
+    pub fn from_hgrid(hgrid: &Hgrid) -> Self {
+        let nodes = hgrid.nodes();
+        let mut min_angle = f64::MAX;
+        let mut max_angle = f64::MIN;
+        let mut sum_min_angle = 0.;
+        let mut triangles = 0;
+        let mut quads = 0;
+        for node_ids in hgrid.elements().hash_map().values() {
+            let angles = polygon_angles_deg(&element_points(nodes, node_ids));
+            let element_min = angles.iter().copied().fold(f64::MAX, f64::min);
+            min_angle = min_angle.min(element_min);
+            max_angle = angles.iter().copied().fold(max_angle, f64::max);
+            sum_min_angle += element_min;
+            if node_ids.len() == 3 {
+                triangles += 1;
+            } else {
+                quads += 1;
+            }
+        }
+        let count = triangles + quads;
+        Self {
+            min_angle,
+            max_angle,
+            mean_min_angle: if count > 0 {
+                sum_min_angle / count as f64
+            } else {
+                f64::NAN
+            },
+            triangles,
+            quads,
+        }
+    }
+}
+
+/// Summary of a split or merge operation.
+#[derive(Debug, Clone, Copy)]
+pub struct QuadReport {
+    pub elements_before: usize,
+    pub elements_after: usize,
+    /// Number of input elements that were split or merged.
+    pub changed: usize,
+    pub quality_before: MeshQuality,
+    pub quality_after: MeshQuality,
+}
+
+/// Result of [`split_quads`].
+#[derive(Debug, Clone)]
+pub struct QuadSplit {
+    pub hgrid: Hgrid,
+    /// New element id to the id of the element it came from.
+    pub element_map: LinkedHashMap<u32, u32>,
+    pub report: QuadReport,
+}
+
+/// Result of [`merge_triangles`].
+#[derive(Debug, Clone)]
+pub struct TriangleMerge {
+    pub hgrid: Hgrid,
+    /// New element id to the ids of the elements it came from.
+    pub element_map: LinkedHashMap<u32, Vec<u32>>,
+    pub report: QuadReport,
+}
+
+/// Limits for pairing triangles into quads.
+#[derive(Debug, Clone, Copy)]
+pub struct MergeOptions {
+    /// Largest allowed deviation of a quad interior angle from 90 degrees.
+    pub max_angle_deviation: f64,
+    /// Largest allowed bottom warp `|h1 + h3 - h2 - h4| / 2` in depth units.
+    pub max_nonplanarity: f64,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            max_angle_deviation: 60.,
+            max_nonplanarity: 0.5,
+        }
+    }
+}
+
+fn element_points(nodes: &Nodes, node_ids: &[u32]) -> Vec<[f64; 2]> {
+    node_ids
+        .iter()
+        .map(|id| {
+            let (x, y) = nodes.get_node(*id).unwrap();
+            [x, y]
+        })
+        .collect()
+}
+
+fn node_depth(nodes: &Nodes, id: u32) -> f64 {
+    nodes
+        .hash_map()
+        .get(&id)
+        .and_then(|(_, values)| values.as_ref().and_then(|v| v.first().copied()))
+        .unwrap_or(0.)
+}
+
+fn rebuild(hgrid: &Hgrid, elements: LinkedHashMap<u32, Vec<u32>>) -> Result<Hgrid, QuadError> {
+    let nodes = Arc::new(hgrid.nodes().clone());
+    let elements = ElementsBuilder::default()
+        .nodes(nodes.clone())
+        .hash_map(elements)
+        .build()?;
+    Ok(HgridBuilder::default()
+        .nodes(nodes)
+        .elements(elements)
+        .boundaries(hgrid.boundaries().cloned())
+        .description(hgrid.description().cloned())
+        .build()?)
+}
+
+/// Split every quad into two triangles along the diagonal giving the larger
+/// minimum angle. Triangles are kept; elements are renumbered from 1.
+pub fn split_quads(hgrid: &Hgrid) -> Result<QuadSplit, QuadError> {
+    let nodes = hgrid.nodes();
+    let mut elements = LinkedHashMap::new();
+    let mut element_map = LinkedHashMap::new();
+    let mut changed = 0;
+    for (&element_id, node_ids) in hgrid.elements().hash_map().iter() {
+        let pieces = if node_ids.len() == 4 {
+            changed += 1;
+            let p = element_points(nodes, node_ids);
+            let quality_ac = min_angle_deg(p[0], p[1], p[2]).min(min_angle_deg(p[0], p[2], p[3]));
+            let quality_bd = min_angle_deg(p[0], p[1], p[3]).min(min_angle_deg(p[1], p[2], p[3]));
+            let [a, b, c, d] = [node_ids[0], node_ids[1], node_ids[2], node_ids[3]];
+            if quality_ac >= quality_bd {
+                vec![vec![a, b, c], vec![a, c, d]]
+            } else {
+                vec![vec![a, b, d], vec![b, c, d]]
+            }
+        } else {
+            vec![node_ids.clone()]
+        };
+        for piece in pieces {
+            let new_id = elements.len() as u32 + 1;
+            elements.insert(new_id, piece);
+            element_map.insert(new_id, element_id);
+        }
+    }
+    let split = rebuild(hgrid, elements)?;
+    let report = QuadReport {
+        elements_before: hgrid.elements().hash_map().len(),
+        elements_after: split.elements().hash_map().len(),
+        changed,
+        quality_before: MeshQuality::from_hgrid(hgrid),
+        quality_after: MeshQuality::from_hgrid(&split),
+    };
+    Ok(QuadSplit {
+        hgrid: split,
+        element_map,
+        report,
+    })
+}
+
+/// Pair adjacent triangles into quads where the quad is convex and meets the
+/// angle and planarity limits. Pairs are chosen greedily, best angles first.
+pub fn merge_triangles(hgrid: &Hgrid, options: &MergeOptions) -> Result<TriangleMerge, QuadError> {
+    let nodes = hgrid.nodes();
+    let element_hash_map = hgrid.elements().hash_map();
+
+    let mut edges: BTreeMap<(u32, u32), Vec<u32>> = BTreeMap::new();
+    for (&element_id, node_ids) in element_hash_map.iter() {
+        if node_ids.len() != 3 {
+            continue;
+        }
+        for i in 0..3 {
+            let (a, b) = (node_ids[i], node_ids[(i + 1) % 3]);
+            edges
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push(element_id);
+        }
+    }
+
+    let mut candidates: Vec<(f64, u32, u32, Vec<u32>)> = Vec::new();
+    for ((a, b), shared) in edges {
+        if shared.len() != 2 {
+            continue;
+        }
+        let (t1, t2) = (shared[0], shared[1]);
+        let tri1 = &element_hash_map[&t1];
+        let tri2 = &element_hash_map[&t2];
+        // counter-clockwise: apex of t1, one end of the shared edge, apex of t2, the other end
+        let i = (0..3).find(|&i| tri1[i] != a && tri1[i] != b).unwrap();
+        let apex = *tri2.iter().find(|&&v| v != a && v != b).unwrap();
+        let quad = vec![tri1[i], tri1[(i + 1) % 3], apex, tri1[(i + 2) % 3]];
+        let points = element_points(nodes, &quad);
+        // SCHISM rejects non-convex quads
+        let convex =
+            (0..4).all(|k| orient2d(points[(k + 3) % 4], points[k], points[(k + 1) % 4]) > 0.);
+        if !convex {
+            continue;
+        }
+        let angles = polygon_angles_deg(&points);
+        let deviation = angles
+            .iter()
+            .map(|angle| (angle - 90.).abs())
+            .fold(0., f64::max);
+        let depths: Vec<f64> = quad.iter().map(|&id| node_depth(nodes, id)).collect();
+        let nonplanarity = 0.5 * (depths[0] + depths[2] - depths[1] - depths[3]).abs();
+        if deviation <= options.max_angle_deviation && nonplanarity <= options.max_nonplanarity {
+            candidates.push((deviation, t1, t2, quad));
+        }
+    }
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut used = HashSet::new();
+    let mut quads: LinkedHashMap<u32, (u32, Vec<u32>)> = LinkedHashMap::new();
+    for (_, t1, t2, quad) in candidates {
+        if used.contains(&t1) || used.contains(&t2) {
+            continue;
+        }
+        used.insert(t1);
+        used.insert(t2);
+        quads.insert(t1.min(t2), (t1.max(t2), quad));
+    }
+
+    let mut elements = LinkedHashMap::new();
+    let mut element_map = LinkedHashMap::new();
+    for (&element_id, node_ids) in element_hash_map.iter() {
+        let new_id = elements.len() as u32 + 1;
+        if let Some((other, quad)) = quads.get(&element_id) {
+            elements.insert(new_id, quad.clone());
+            element_map.insert(new_id, vec![element_id, *other]);
+        } else if !used.contains(&element_id) {
+            elements.insert(new_id, node_ids.clone());
+            element_map.insert(new_id, vec![element_id]);
+        }
+    }
+    let merged = rebuild(hgrid, elements)?;
+    let report = QuadReport {
+        elements_before: element_hash_map.len(),
+        elements_after: merged.elements().hash_map().len(),
+        changed: used.len(),
+        quality_before: MeshQuality::from_hgrid(hgrid),
+        quality_after: MeshQuality::from_hgrid(&merged),
+    };
+    Ok(TriangleMerge {
+        hgrid: merged,
+        element_map,
+        report,
+    })
+}
+
+#[derive(Error, Debug)]
+pub enum QuadError {
+    #[error(transparent)]
+    ElementsBuilderError(#[from] ElementsBuilderError),
+
+    #[error(transparent)]
+    HgridBuilderError(#[from] HgridBuilderError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::NodesBuilder;
+
+    /// Mesh of (x, y, depth) points and elements given by 1-based node ids.
+    fn mesh(points: &[(f64, f64, f64)], elements: &[&[u32]]) -> Hgrid {
+        let mut nodes_hash_map = LinkedHashMap::new();
+        for (i, (x, y, depth)) in points.iter().enumerate() {
+            nodes_hash_map.insert(i as u32 + 1, (vec![*x, *y], Some(vec![-depth])));
+        }
+        let nodes = NodesBuilder::default()
+            .hash_map(nodes_hash_map)
+            .crs(None)
+            .build()
+            .map(Arc::new)
+            .unwrap();
+        let mut elements_hash_map = LinkedHashMap::new();
+        for (i, node_ids) in elements.iter().enumerate() {
+            elements_hash_map.insert(i as u32 + 1, node_ids.to_vec());
+        }
+        let elements = ElementsBuilder::default()
+            .nodes(nodes.clone())
+            .hash_map(elements_hash_map)
+            .build()
+            .unwrap();
+        HgridBuilder::default()
+            .nodes(nodes)
+            .elements(elements)
+            .boundaries(None)
+            .description(None)
+            .build()
+            .unwrap()
+    }
+
+    fn two_quads(center_depth: f64) -> Hgrid {
+        let points = [
+            (0., 0., 10.),
+            (1., 0., 10.),
+            (2., 0., 10.),
+            (0., 1., 10.),
+            (1., 1., center_depth),
+            (2., 1., 10.),
+        ];
+        mesh(&points, &[&[1, 2, 5, 4], &[2, 3, 6, 5]])
+    }
+
+    #[test]
+    fn test_split_and_merge_round_trip() {
+        let hgrid = two_quads(10.);
+        let split = split_quads(&hgrid).unwrap();
+        assert_eq!(split.report.elements_after, 4);
+        assert_eq!(split.report.changed, 2);
+        assert_eq!(split.report.quality_after.triangles, 4);
+        assert_eq!(split.element_map.values().filter(|&&id| id == 2).count(), 2);
+        assert!((split.report.quality_after.min_angle - 45.).abs() < 1e-9);
+
+        let merged = merge_triangles(&split.hgrid, &MergeOptions::default()).unwrap();
+        assert_eq!(merged.report.quality_after.quads, 2);
+        assert_eq!(merged.report.changed, 4);
+        assert!((merged.report.quality_after.min_angle - 90.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_merge_respects_nonplanarity() {
+        let split = split_quads(&two_quads(20.)).unwrap();
+        let merged = merge_triangles(&split.hgrid, &MergeOptions::default()).unwrap();
+        assert_eq!(merged.report.changed, 0);
+        assert_eq!(merged.report.elements_after, 4);
+    }
+
+    #[test]
+    fn test_merge_rejects_dart() {
+        // a dart with a 40 degree tip and wings and a 240 degree reflex corner
+        // at node 3, split along the diagonal from the tip to the reflex corner
+        let (sin, cos) = 20f64.to_radians().sin_cos();
+        let reflex = cos - sin / 60f64.to_radians().tan();
+        let points = [
+            (0., 0., 10.),
+            (cos, -sin, 10.),
+            (reflex, 0., 10.),
+            (cos, sin, 10.),
+        ];
+        let hgrid = mesh(&points, &[&[1, 2, 3], &[1, 3, 4]]);
+        let angles = polygon_angles_deg(&element_points(hgrid.nodes(), &[1, 2, 3, 4]));
+        assert!((angles[2] - 240.).abs() < 1e-9);
+        assert!((angles[0] - 40.).abs() < 1e-9);
+        let merged = merge_triangles(&hgrid, &MergeOptions::default()).unwrap();
+        assert_eq!(merged.report.changed, 0);
+        assert_eq!(merged.report.elements_after, 2);
+    }
+}
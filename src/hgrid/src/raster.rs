@@ -0,0 +1,170 @@
+use ndarray::Array2;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use thiserror::Error;
+
+/// Regular grid of values with cell-centred coordinates.
+///
+/// Row 0 of `values` lies at `ymin` and column 0 at `xmin`; rows increase northward.
+#[derive(Debug, Clone)]
+pub struct Raster {
+    xmin: f64,
+    ymin: f64,
+    dx: f64,
+    dy: f64,
+    values: Array2<f64>,
+}
+
+impl Raster {
+    pub fn new(
+        xmin: f64,
+        ymin: f64,
+        dx: f64,
+        dy: f64,
+        values: Array2<f64>,
+    ) -> Result<Self, RasterError> {
+        if !(dx > 0. && dy > 0.) {
+            return Err(RasterError::InvalidSpacing(dx, dy));
+        }
+        if values.is_empty() {
+            return Err(RasterError::Empty);
+        }
+        Ok(Self {
+            xmin,
+            ymin,
+            dx,
+            dy,
+            values,
+        })
+    }
+
+    /// Read an ESRI ASCII grid (`.asc`). NODATA cells are stored as NaN.
+    pub fn try_from_ascii_grid(path: &Path) -> Result<Self, RasterError> {
+        let file = File::open(path)?;
+        let mut lines = BufReader::new(file).lines();
+        let mut ncols = None;
+        let mut nrows = None;
+        let mut xll = None;
+        let mut yll = None;
+        let mut is_corner = true;
+        let mut cellsize = None;
+        let mut nodata = None;
+        let mut data: Vec<f64> = Vec::new();
+        for line in lines.by_ref() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let Some(key) = fields.next() else {
+                continue;
+            };
+            let parse_value = |value: Option<&str>| -> Result<f64, RasterError> {
+                value
+                    .and_then(|v| v.parse::<f64>().ok())
+                    .ok_or_else(|| RasterError::ParseError(line.clone()))
+            };
+            match key.to_lowercase().as_str() {
+                "ncols" => ncols = Some(parse_value(fields.next())? as usize),
+                "nrows" => nrows = Some(parse_value(fields.next())? as usize),
+                "xllcorner" => xll = Some(parse_value(fields.next())?),
+                "yllcorner" => yll = Some(parse_value(fields.next())?),
+                "xllcenter" => {
+                    xll = Some(parse_value(fields.next())?);
+                    is_corner = false;
+                }
+                "yllcenter" => {
+                    yll = Some(parse_value(fields.next())?);
+                    is_corner = false;
+                }
+                "cellsize" => cellsize = Some(parse_value(fields.next())?),
+                "nodata_value" => nodata = Some(parse_value(fields.next())?),
+                _ => {
+                    for token in line.split_whitespace() {
+                        data.push(parse_value(Some(token))?);
+                    }
+                    break;
+                }
+            }
+        }
+        for line in lines {
+            let line = line?;
+            for token in line.split_whitespace() {
+                data.push(
+                    token
+                        .parse::<f64>()
+                        .map_err(|_| RasterError::ParseError(line.clone()))?,
+                );
+            }
+        }
+        let header = |name: &str| RasterError::MissingHeader(name.to_string());
+        let ncols = ncols.ok_or_else(|| header("ncols"))?;
+        let nrows = nrows.ok_or_else(|| header("nrows"))?;
+        let cellsize = cellsize.ok_or_else(|| header("cellsize"))?;
+        let mut xmin = xll.ok_or_else(|| header("xllcorner"))?;
+        let mut ymin = yll.ok_or_else(|| header("yllcorner"))?;
+        if is_corner {
+            xmin += 0.5 * cellsize;
+            ymin += 0.5 * cellsize;
+        }
+        if data.len() != ncols * nrows {
+            return Err(RasterError::ShapeMismatch(ncols * nrows, data.len()));
+        }
+        // the file lists the northernmost row first
+        let values = Array2::from_shape_fn((nrows, ncols), |(i, j)| {
+            let value = data[(nrows - 1 - i) * ncols + j];
+            match nodata {
+                Some(nodata) if value == nodata => f64::NAN,
+                _ => value,
+            }
+        });
+        Self::new(xmin, ymin, cellsize, cellsize, values)
+    }
+
+    pub fn values(&self) -> &Array2<f64> {
+        &self.values
+    }
+
+    /// Bilinear interpolation at (x, y). Points outside the extent are clamped to it.
+    ///
+    /// Returns `None` when the interpolated value is undefined (NODATA).
+    pub fn sample(&self, x: f64, y: f64) -> Option<f64> {
+        let (nrows, ncols) = self.values.dim();
+        let fx = ((x - self.xmin) / self.dx).clamp(0., (ncols - 1) as f64);
+        let fy = ((y - self.ymin) / self.dy).clamp(0., (nrows - 1) as f64);
+        let j0 = fx.floor() as usize;
+        let i0 = fy.floor() as usize;
+        let j1 = (j0 + 1).min(ncols - 1);
+        let i1 = (i0 + 1).min(nrows - 1);
+        let tx = fx - j0 as f64;
+        let ty = fy - i0 as f64;
+        let value = (1. - tx) * (1. - ty) * self.values[[i0, j0]]
+            + tx * (1. - ty) * self.values[[i0, j1]]
+            + (1. - tx) * ty * self.values[[i1, j0]]
+            + tx * ty * self.values[[i1, j1]];
+        if value.is_nan() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RasterError {
+    #[error("Raster spacing must be positive, got dx={0}, dy={1}")]
+    InvalidSpacing(f64, f64),
+
+    #[error("Raster has no values")]
+    Empty,
+
+    #[error("Missing ASCII grid header: {0}")]
+    MissingHeader(String),
+
+    #[error("Unable to parse ASCII grid line: {0}")]
+    ParseError(String),
+
+    #[error("Expected {0} raster values, found {1}")]
+    ShapeMismatch(usize, usize),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,197 @@
+use super::{geometry::distance, hgrid::Hgrid, locator::ElementLocator};
+#[cfg(feature = "plot")]
+use plotly::color::NamedColor;
+#[cfg(feature = "plot")]
+use plotly::common::{Line, Mode, Title};
+#[cfg(feature = "plot")]
+use plotly::layout::{Axis, Layout};
+#[cfg(feature = "plot")]
+use plotly::{Plot, Scatter};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// A sample along a transect. Points outside the mesh are gaps with no
+/// element and no depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransectPoint {
+    pub distance: f64,
+    pub x: f64,
+    pub y: f64,
+    pub element_id: Option<u32>,
+    /// Depth interpolated within the element, positive down.
+    pub depth: Option<f64>,
+    /// Interpolation weights of the element nodes by node id, empty at gaps.
+    pub weights: HashMap<u32, f64>,
+}
+
+impl TransectPoint {
+    pub fn is_gap(&self) -> bool {
+        self.element_id.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transect {
+    pub points: Vec<TransectPoint>,
+}
+
+impl Transect {
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), TransectError> {
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(["distance", "x", "y", "element_id", "depth"])?;
+        for point in self.points.iter() {
+            wtr.write_record(&[
+                point.distance.to_string(),
+                point.x.to_string(),
+                point.y.to_string(),
+                point.element_id.map_or(String::new(), |id| id.to_string()),
+                point.depth.map_or(String::new(), |depth| depth.to_string()),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Bottom profile (negative depth) against distance; gaps break the line.
+    #[cfg(feature = "plot")]
+    pub fn make_plot(&self) -> Plot {
+        let mut plot = Plot::new();
+        let distances: Vec<f64> = self.points.iter().map(|p| p.distance).collect();
+        let bottom: Vec<Option<f64>> = self.points.iter().map(|p| p.depth.map(|d| -d)).collect();
+        let trace = Scatter::new(distances, bottom)
+            .mode(Mode::Lines)
+            .line(Line::new().color(NamedColor::SaddleBrown))
+            .name("bottom");
+        plot.add_trace(trace);
+        plot.set_layout(
+            Layout::new()
+                .x_axis(Axis::new().title(Title::new("distance")))
+                .y_axis(Axis::new().title(Title::new("elevation"))),
+        );
+        plot
+    }
+}
+
+impl Hgrid {
+    /// Sample depths along a polyline given in the grid CRS.
+    ///
+    /// Every polyline vertex is sampled, with intermediate points added so that
+    /// consecutive samples are at most `spacing` apart.
+    pub fn transect(
+        &self,
+        polyline: &[(f64, f64)],
+        spacing: f64,
+    ) -> Result<Transect, TransectError> {
+        if polyline.len() < 2 {
+            return Err(TransectError::TooFewVertices(polyline.len()));
+        }
+        if spacing.is_nan() || spacing <= 0. {
+            return Err(TransectError::InvalidSpacing(spacing));
+        }
+        let locator = ElementLocator::new(self);
+        let values = self.nodes().hash_map();
+        let mut samples = vec![([polyline[0].0, polyline[0].1], 0.)];
+        let mut along = 0.;
+        for segment in polyline.windows(2) {
+            let a = [segment[0].0, segment[0].1];
+            let b = [segment[1].0, segment[1].1];
+            let length = distance(a, b);
+            let pieces = ((length / spacing).ceil() as usize).max(1);
+            for k in 1..=pieces {
+                let t = k as f64 / pieces as f64;
+                samples.push((
+                    [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])],
+                    along + t * length,
+                ));
+            }
+            along += length;
+        }
+        let points = samples
+            .into_iter()
+            .map(|(p, distance)| {
+                let located = locator.locate(p);
+                let depth = located.as_ref().and_then(|(_, weights)| {
+                    weights.iter().try_fold(0., |sum, (node_id, weight)| {
+                        // node values are stored with the sign reversed
+                        values[node_id]
+                            .1
+                            .as_ref()
+                            .and_then(|v| v.first())
+                            .map(|value| sum - weight * value)
+                    })
+                });
+                let (element_id, weights) =
+                    located.map_or((None, HashMap::new()), |(id, weights)| (Some(id), weights));
+                TransectPoint {
+                    distance,
+                    x: p[0],
+                    y: p[1],
+                    element_id,
+                    depth,
+                    weights,
+                }
+            })
+            .collect();
+        Ok(Transect { points })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TransectError {
+    #[error("A transect needs at least 2 vertices, got {0}")]
+    TooFewVertices(usize),
+
+    #[error("Transect spacing must be positive, got {0}")]
+    InvalidSpacing(f64),
+
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh_generator::{Bathymetry, MeshGeneratorBuilder, SizeFunction};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_transect_with_gap() {
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(vec![(0., 0.), (10., 0.), (10., 10.), (0., 10.)])
+            .size_function(SizeFunction::Constant(1.))
+            .bathymetry(Some(Bathymetry::Constant(4.)))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let transect = hgrid.transect(&[(5., 5.), (15., 5.)], 0.5).unwrap();
+        assert_eq!(transect.len(), 21);
+        assert!((transect.points[20].distance - 10.).abs() < 1e-12);
+        for point in transect.points.iter() {
+            if point.x <= 10. {
+                assert!(point.element_id.is_some());
+                assert!((point.depth.unwrap() - 4.).abs() < 1e-9);
+                assert!((point.weights.values().sum::<f64>() - 1.).abs() < 1e-9);
+            } else {
+                assert!(point.is_gap());
+                assert!(point.depth.is_none());
+                assert!(point.weights.is_empty());
+            }
+        }
+        let temp_file = NamedTempFile::new().unwrap();
+        transect.write_csv(temp_file.path()).unwrap();
+        let contents = std::fs::read_to_string(temp_file.path()).unwrap();
+        assert_eq!(contents.lines().count(), 22);
+    }
+}
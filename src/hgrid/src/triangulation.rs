@@ -0,0 +1,283 @@
+// schismrs-hgrid/src/triangulation.rs
+
+//! Incremental (Bowyer-Watson) Delaunay triangulation.
+//!
+//! Points are inserted one at a time inside a large super triangle whose
+//! three vertices occupy indices 0, 1 and 2. Triangles are stored
+//! counter-clockwise, and `neighbors[t][i]` is the triangle across the edge
+//! opposite to vertex `i` of triangle `t`.
+
+use crate::geometry::{incircle, orient2d};
+use std::collections::{HashMap, HashSet};
+
+pub(crate) const NONE: usize = usize::MAX;
+pub(crate) const SUPER_VERTICES: usize = 3;
+
+pub(crate) struct Triangulation {
+    points: Vec<[f64; 2]>,
+    triangles: Vec<[usize; 3]>,
+    neighbors: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    free: Vec<usize>,
+    vertex_triangle: Vec<usize>,
+    last: usize,
+    tolerance: f64,
+}
+
+impl Triangulation {
+    /// Create an empty triangulation able to hold points within the given bounding box.
+    pub(crate) fn new(xmin: f64, ymin: f64, xmax: f64, ymax: f64) -> Self {
+        let dx = (xmax - xmin).max(f64::EPSILON);
+        let dy = (ymax - ymin).max(f64::EPSILON);
+        let d = dx.max(dy);
+        let cx = 0.5 * (xmin + xmax);
+        let cy = 0.5 * (ymin + ymax);
+        let points = vec![
+            [cx - 20. * d, cy - d],
+            [cx + 20. * d, cy - d],
+            [cx, cy + 20. * d],
+        ];
+        Self {
+            points,
+            triangles: vec![[0, 1, 2]],
+            neighbors: vec![[NONE, NONE, NONE]],
+            alive: vec![true],
+            free: Vec::new(),
+            vertex_triangle: vec![0, 0, 0],
+            last: 0,
+            tolerance: d * 1e-12,
+        }
+    }
+
+    pub(crate) fn points(&self) -> &[[f64; 2]] {
+        &self.points
+    }
+
+    pub(crate) fn triangle(&self, t: usize) -> [usize; 3] {
+        self.triangles[t]
+    }
+
+    pub(crate) fn is_alive(&self, t: usize) -> bool {
+        self.alive[t]
+    }
+
+    pub(crate) fn triangle_points(&self, t: usize) -> [[f64; 2]; 3] {
+        let [a, b, c] = self.triangles[t];
+        [self.points[a], self.points[b], self.points[c]]
+    }
+
+    /// Iterator over the ids of live triangles.
+    pub(crate) fn live_triangles(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.triangles.len()).filter(move |&t| self.alive[t])
+    }
+
+    /// True if the triangle touches one of the super triangle vertices.
+    pub(crate) fn touches_super(&self, t: usize) -> bool {
+        self.triangles[t].iter().any(|&v| v < SUPER_VERTICES)
+    }
+
+    /// Find the triangle containing `p` by walking from the last inserted triangle.
+    pub(crate) fn locate(&self, p: [f64; 2]) -> usize {
+        let mut t = if self.alive[self.last] {
+            self.last
+        } else {
+            self.live_triangles().next().unwrap()
+        };
+        let max_steps = self.triangles.len() + 10;
+        for step in 0..max_steps {
+            let tri = self.triangles[t];
+            let mut moved = false;
+            // rotate the starting edge to avoid cycling on degenerate walks
+            for k in 0..3 {
+                let i = (k + step) % 3;
+                let a = self.points[tri[(i + 1) % 3]];
+                let b = self.points[tri[(i + 2) % 3]];
+                if orient2d(a, b, p) < 0. {
+                    let n = self.neighbors[t][i];
+                    if n != NONE {
+                        t = n;
+                        moved = true;
+                        break;
+                    }
+                }
+            }
+            if !moved {
+                return t;
+            }
+        }
+        // fall back to an exhaustive search
+        self.live_triangles()
+            .find(|&t| {
+                let [a, b, c] = self.triangle_points(t);
+                orient2d(a, b, p) >= 0. && orient2d(b, c, p) >= 0. && orient2d(c, a, p) >= 0.
+            })
+            .unwrap_or(t)
+    }
+
+    /// Triangles whose circumcircle contains `p`, starting from the triangle containing it.
+    pub(crate) fn cavity(&self, p: [f64; 2], start: usize) -> Vec<usize> {
+        let mut cavity = vec![start];
+        let mut visited = HashSet::new();
+        visited.insert(start);
+        let mut i = 0;
+        while i < cavity.len() {
+            let t = cavity[i];
+            i += 1;
+            for &n in self.neighbors[t].iter() {
+                if n == NONE || !visited.insert(n) {
+                    continue;
+                }
+                let [a, b, c] = self.triangle_points(n);
+                if incircle(a, b, c, p) > 0. {
+                    cavity.push(n);
+                }
+            }
+        }
+        cavity
+    }
+
+    /// Index of an existing vertex coincident with `p` in triangle `t`, if any.
+    fn coincident_vertex(&self, t: usize, p: [f64; 2]) -> Option<usize> {
+        self.triangles[t].iter().copied().find(|&v| {
+            let q = self.points[v];
+            (q[0] - p[0]).abs() <= self.tolerance && (q[1] - p[1]).abs() <= self.tolerance
+        })
+    }
+
+    /// Insert a point and return its vertex index. Coincident points are merged.
+    pub(crate) fn insert(&mut self, p: [f64; 2]) -> usize {
+        let start = self.locate(p);
+        if let Some(v) = self.coincident_vertex(start, p) {
+            return v;
+        }
+        let cavity = self.cavity(p, start);
+        self.insert_into_cavity(p, &cavity)
+    }
+
+    /// Insert a point using a precomputed cavity (see [`Triangulation::cavity`]).
+    pub(crate) fn insert_into_cavity(&mut self, p: [f64; 2], cavity: &[usize]) -> usize {
+        let pi = self.points.len();
+        self.points.push(p);
+        self.vertex_triangle.push(NONE);
+
+        let in_cavity: HashSet<usize> = cavity.iter().copied().collect();
+        let mut boundary = Vec::new();
+        for &t in cavity {
+            let tri = self.triangles[t];
+            for i in 0..3 {
+                let n = self.neighbors[t][i];
+                if n == NONE || !in_cavity.contains(&n) {
+                    boundary.push((tri[(i + 1) % 3], tri[(i + 2) % 3], n));
+                }
+            }
+        }
+        for &t in cavity {
+            self.alive[t] = false;
+            self.free.push(t);
+        }
+
+        let mut by_start: HashMap<usize, usize> = HashMap::with_capacity(boundary.len());
+        let mut created = Vec::with_capacity(boundary.len());
+        for &(a, b, outer) in boundary.iter() {
+            let t = self.allocate([a, b, pi], [NONE, NONE, outer]);
+            if outer != NONE {
+                // slots are recycled, so match the shared edge rather than the old id
+                if let Some(j) = Self::edge_in_triangle(self.triangles[outer], a, b) {
+                    self.neighbors[outer][j] = t;
+                }
+            }
+            by_start.insert(a, t);
+            self.vertex_triangle[a] = t;
+            self.vertex_triangle[b] = t;
+            created.push(t);
+        }
+        for &t in created.iter() {
+            let b = self.triangles[t][1];
+            if let Some(&next) = by_start.get(&b) {
+                self.neighbors[t][0] = next;
+                self.neighbors[next][1] = t;
+            }
+        }
+        self.vertex_triangle[pi] = created[0];
+        self.last = created[0];
+        pi
+    }
+
+    fn allocate(&mut self, tri: [usize; 3], neighbors: [usize; 3]) -> usize {
+        match self.free.pop() {
+            Some(t) => {
+                self.triangles[t] = tri;
+                self.neighbors[t] = neighbors;
+                self.alive[t] = true;
+                t
+            }
+            None => {
+                self.triangles.push(tri);
+                self.neighbors.push(neighbors);
+                self.alive.push(true);
+                self.triangles.len() - 1
+            }
+        }
+    }
+
+    /// Live triangles sharing vertex `v`.
+    pub(crate) fn triangles_around(&self, v: usize) -> Vec<usize> {
+        let start = self.vertex_triangle[v];
+        if start == NONE || !self.alive[start] || !self.triangles[start].contains(&v) {
+            return self
+                .live_triangles()
+                .filter(|&t| self.triangles[t].contains(&v))
+                .collect();
+        }
+        let mut around = vec![start];
+        // rotate counter-clockwise, then clockwise if the hull was reached
+        for direction in 1..3 {
+            let mut t = start;
+            loop {
+                let iv = self.triangles[t].iter().position(|&u| u == v).unwrap();
+                let next = self.neighbors[t][(iv + direction) % 3];
+                if next == NONE || next == start {
+                    break;
+                }
+                around.push(next);
+                t = next;
+            }
+            if around.len() > 1 && self.neighbors[*around.last().unwrap()].contains(&start) {
+                break;
+            }
+        }
+        around.sort_unstable();
+        around.dedup();
+        around
+    }
+
+    /// Find a triangle containing the undirected edge (a, b).
+    ///
+    /// Returns the triangle id and the local index of the vertex opposite to the edge.
+    pub(crate) fn find_edge(&self, a: usize, b: usize) -> Option<(usize, usize)> {
+        self.triangles_around(a)
+            .into_iter()
+            .find_map(|t| Self::edge_in_triangle(self.triangles[t], a, b).map(|i| (t, i)))
+    }
+
+    fn edge_in_triangle(tri: [usize; 3], a: usize, b: usize) -> Option<usize> {
+        (0..3).find(|&i| {
+            let u = tri[(i + 1) % 3];
+            let v = tri[(i + 2) % 3];
+            (u == a && v == b) || (u == b && v == a)
+        })
+    }
+
+    /// Vertices opposite to edge (a, b) in the (up to two) triangles sharing it.
+    pub(crate) fn edge_apexes(&self, a: usize, b: usize) -> Option<Vec<usize>> {
+        let (t, i) = self.find_edge(a, b)?;
+        let mut apexes = vec![self.triangles[t][i]];
+        let n = self.neighbors[t][i];
+        if n != NONE {
+            if let Some(j) = Self::edge_in_triangle(self.triangles[n], a, b) {
+                apexes.push(self.triangles[n][j]);
+            }
+        }
+        Some(apexes)
+    }
+}
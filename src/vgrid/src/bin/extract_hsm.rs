@@ -3,7 +3,10 @@
 use clap::{Parser, ValueEnum};
 use pretty_env_logger;
 use schismrs_hgrid::hgrid::Hgrid;
-use schismrs_vgrid::vqs::VQS;
+use schismrs_vgrid::recipe::VQSRecipe;
+use schismrs_vgrid::transforms::transforms::ReconstructedOpts;
+use schismrs_vgrid::transforms::StretchingFunction;
+use schismrs_vgrid::vqs::{RoundTripReport, DEFAULT_SIGMA_TOLERANCE, VQS};
 use std::error::Error;
 use std::fs::File;
 use std::io::Write;
@@ -39,6 +42,25 @@ struct Cli {
     
     #[clap(long, help = "Save analysis statistics to file")]
     stats_output: Option<PathBuf>,
+    
+    #[clap(long, action, help = "Regenerate the VQS from the extracted master grids and compare it node by node")]
+    verify: bool,
+    
+    #[clap(long, default_value_t = DEFAULT_SIGMA_TOLERANCE, help = "Largest sigma difference still counted as identical")]
+    verify_tolerance: f64,
+    
+    #[clap(long, help = "VQS recipe whose stretching function is used to regenerate the grid. \
+                         Defaults to a linear reconstruction of the master grids")]
+    verify_config: Option<PathBuf>,
+    
+    #[clap(long, default_value_t = 0.0, allow_hyphen_values = true, help = "etal of the default linear reconstruction used by --verify")]
+    verify_etal: f64,
+    
+    #[clap(long, default_value_t = 0.0, allow_hyphen_values = true, help = "a_vqs0 of the default linear reconstruction used by --verify")]
+    verify_a_vqs0: f64,
+    
+    #[clap(long, help = "Save the nodes that differ after regeneration to a CSV file")]
+    verify_output: Option<PathBuf>,
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -115,6 +137,12 @@ fn entrypoint() -> Result<(), Box<dyn Error>> {
     let (master_depths, master_levels) = vqs.extract_master_grids(&hgrid)
         .map_err(|e| format!("Failed to extract master grids: {}", e))?;
     
+    let verification = if cli.verify || cli.verify_output.is_some() {
+        Some(verify_master_grids(&cli, &vqs, &hgrid, &master_depths, &master_levels)?)
+    } else {
+        None
+    };
+    
     // Prepare results
     let results = prepare_extraction_results(&vqs, &hgrid, master_depths, master_levels)?;
     
@@ -136,6 +164,15 @@ fn entrypoint() -> Result<(), Box<dyn Error>> {
         }
     }
     
+    if let Some(report) = verification {
+        println!("\n=== Round-trip Verification ===");
+        print!("{}", report);
+        if let Some(ref path) = cli.verify_output {
+            report.write_csv(path)?;
+            println!("Differing nodes written to: {}", path.display());
+        }
+    }
+    
     // Generate statistics if requested
     if cli.stats || cli.stats_output.is_some() {
         if cli.verbose {
@@ -159,6 +196,39 @@ fn entrypoint() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn verify_master_grids(
+    cli: &Cli,
+    vqs: &VQS,
+    hgrid: &Hgrid,
+    master_depths: &Vec<f64>,
+    master_levels: &Vec<usize>,
+) -> Result<RoundTripReport, Box<dyn Error>> {
+    if cli.verbose {
+        println!("Regenerating VQS from {} master grids...", master_depths.len());
+    }
+    let recipe = match &cli.verify_config {
+        Some(path) => Some(VQSRecipe::from_file(path)?),
+        None => None,
+    };
+    let stretching = match &recipe {
+        Some(recipe) => recipe.stretching.stretching_function(),
+        None => StretchingFunction::Reconstructed(ReconstructedOpts {
+            master_depths: master_depths.clone(),
+            master_levels: master_levels.clone(),
+            etal: cli.verify_etal,
+            a_vqs0: cli.verify_a_vqs0,
+        }),
+    };
+    let report = vqs.verify_master_grids(
+        hgrid,
+        master_depths,
+        master_levels,
+        &stretching,
+        cli.verify_tolerance,
+    )?;
+    Ok(report)
+}
+
 fn prepare_extraction_results(
     vqs: &VQS, 
     hgrid: &Hgrid, 
@@ -1,7 +1,9 @@
 use clap::Parser;
 use pretty_env_logger;
 use schismrs_hgrid::hgrid::Hgrid;
-use schismrs_vgrid::sz::SZBuilder;
+use schismrs_vgrid::param_nml::ParamNml;
+use schismrs_vgrid::recipe::SZRecipe;
+use schismrs_vgrid::summary::VgridSummary;
 use std::process::ExitCode;
 use std::{error::Error, path::PathBuf};
 
@@ -26,6 +28,43 @@ struct Cli {
     hgrid_path: PathBuf,
     #[clap(short, long)]
     output_filepath: Option<PathBuf>,
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "slevels",
+            "zlevels",
+            "theta_f",
+            "theta_b",
+            "critical_depth",
+            "etal",
+        ],
+        help = "Read every generation option from a YAML (or .json) recipe file"
+    )]
+    config: Option<PathBuf>,
+    #[clap(
+        long = "2d",
+        conflicts_with_all = [
+            "config",
+            "slevels",
+            "zlevels",
+            "theta_f",
+            "theta_b",
+            "critical_depth",
+            "etal",
+        ],
+        help = "Generate the single layer (nvrt = 2) grid of a 2D barotropic run"
+    )]
+    two_d: bool,
+    #[clap(
+        long,
+        help = "Check that the settings of this param.nml are consistent with the generated grid"
+    )]
+    param_nml: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Write the recipe of the generated grid to a YAML (or .json) file"
+    )]
+    save_config: Option<PathBuf>,
     #[clap(
         long,
         help = "Number of sigma-levels. Must be an integer greater or equal than 0.",
@@ -71,8 +110,17 @@ struct Cli {
         help = "Water level offset. Not typically needed."
     )]
     etal: Option<f64>,
+    #[clap(long, help = "Write the 3D size and layer thickness summary as JSON")]
+    summary_json: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "vgrid.in to compare the compute cost of the generated grid against"
+    )]
+    reference_vgrid: Option<PathBuf>,
+    #[cfg(feature = "plot")]
     #[clap(long, action)]
     show_plot: bool,
+    #[cfg(feature = "plot")]
     #[clap(long)]
     save_plot: Option<PathBuf>,
 }
@@ -81,25 +129,44 @@ fn entrypoint() -> Result<(), Box<dyn Error>> {
     pretty_env_logger::init();
     let cli = Cli::parse();
     let hgrid = Hgrid::try_from(&cli.hgrid_path)?;
-    let mut builder = SZBuilder::default();
-    builder.hgrid(&hgrid);
-    builder.slevels(&cli.slevels.as_ref().unwrap());
-    builder.theta_f(&cli.theta_f.as_ref().unwrap());
-    builder.theta_b(&cli.theta_b.as_ref().unwrap());
-    builder.critical_depth(&cli.critical_depth);
-    builder.etal(&cli.etal.as_ref().unwrap());
-    if cli.zlevels.is_some() {
-        builder.zlevels(cli.zlevels.as_ref().unwrap());
+    let recipe = match &cli.config {
+        Some(config) => SZRecipe::from_file(config)?,
+        None if cli.two_d => SZRecipe::barotropic(),
+        None => SZRecipe {
+            slevels: cli.slevels.unwrap(),
+            zlevels: cli.zlevels.clone(),
+            theta_f: cli.theta_f.unwrap(),
+            theta_b: cli.theta_b.unwrap(),
+            critical_depth: cli.critical_depth,
+            etal: cli.etal.unwrap(),
+        },
+    };
+    if let Some(save_config) = &cli.save_config {
+        recipe.write_to_file(save_config)?;
     }
-    let sz = builder.build()?;
+    let sz = recipe.build(&hgrid)?;
     if cli.output_filepath.is_some() {
         sz.write_to_file(&cli.output_filepath.as_ref().unwrap())?;
     } else {
         println!("{}", sz)
     };
+    if let Some(param_nml) = &cli.param_nml {
+        ParamNml::try_from_file(param_nml)?.validate(&sz)?;
+    }
+
+    let summary =
+        VgridSummary::compute_with_reference(&hgrid, &sz, cli.reference_vgrid.as_deref())?;
+    print!("{}", summary);
+    if let Some(summary_json) = &cli.summary_json {
+        summary.write_json(summary_json)?;
+    }
 
+    #[cfg(feature = "plot")]
     if cli.show_plot || cli.save_plot.is_some() {
         let zcor_plot = sz.make_vertical_distribution_plot(10)?;
+        if let Some(save_path) = &cli.save_plot {
+            zcor_plot.write_html(save_path);
+        }
         if cli.show_plot {
             zcor_plot.show();
         }
@@ -1,10 +1,16 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use pretty_env_logger;
 use schismrs_hgrid::hgrid::Hgrid;
-use schismrs_vgrid::transforms::quadratic::QuadraticTransformOpts;
-use schismrs_vgrid::transforms::s::STransformOpts;
-use schismrs_vgrid::transforms::StretchingFunction;
-use schismrs_vgrid::vqs::{VQSAutoBuilder, VQSBuilder, VQSKMeansBuilder};
+use schismrs_vgrid::diagnostics::PressureGradientDiagnostics;
+use schismrs_vgrid::param_nml::ParamNml;
+use schismrs_vgrid::recipe::{
+    HsmStrategyRecipe, MasterGridsRecipe, QuadraticRecipe, RegionRecipe, RomsRecipe, SRecipe,
+    StretchingRecipe, VQSRecipe,
+};
+use schismrs_vgrid::summary::VgridSummary;
+use schismrs_vgrid::sz::SZ;
+use schismrs_vgrid::tuning::{ParameterSweep, ScoreWeights, TuningReport};
+use schismrs_vgrid::vqs::VgridFormat;
 use std::process::ExitCode;
 use std::{error::Error, path::PathBuf};
 
@@ -14,8 +20,63 @@ struct Cli {
     hgrid_path: PathBuf,
     #[clap(short, long)]
     output_filepath: Option<PathBuf>,
-    #[clap(short, long)]
-    transform: StretchingFunctionKind,
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "transform",
+            "a_vqs0",
+            "etal",
+            "skew_decay_rate",
+            "theta_f",
+            "theta_b",
+            "theta_s",
+            "dz_bottom_min",
+            "max_level_jump",
+            "dz_surface_target",
+            "dz_max",
+            "regions",
+            "blending_width",
+        ],
+        help = "Read every generation option from a YAML (or .json) recipe file"
+    )]
+    config: Option<PathBuf>,
+    #[clap(
+        long = "2d",
+        conflicts_with_all = [
+            "config",
+            "save_config",
+            "transform",
+            "a_vqs0",
+            "etal",
+            "skew_decay_rate",
+            "theta_f",
+            "theta_b",
+            "theta_s",
+            "dz_bottom_min",
+            "max_level_jump",
+            "dz_surface_target",
+            "dz_max",
+            "regions",
+            "blending_width",
+            "legacy_format",
+            "diagnostics",
+        ],
+        help = "Write the single layer (nvrt = 2) grid of a 2D barotropic run. \
+                SCHISM reads 2D grids in the ivcor=2 format, so no VQS is built"
+    )]
+    two_d: bool,
+    #[clap(
+        long,
+        help = "Check that the settings of this param.nml are consistent with the generated grid"
+    )]
+    param_nml: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Write the recipe of the generated grid to a YAML (or .json) file"
+    )]
+    save_config: Option<PathBuf>,
+    #[clap(short, long, required_unless_present_any = ["config", "two_d"])]
+    transform: Option<StretchingFunctionKind>,
     #[clap(
         short,
         long,
@@ -37,44 +98,133 @@ struct Cli {
     theta_f: Option<f64>,
     #[clap(
         long,
-        help = "Range is [0., 1.]. For values closer to 0. the surface is \
-                resolved. For values closer to 1., but the surface and bottom \
-                are resolved.",
+        help = "For the S transform the range is [0., 1.]. For values closer \
+                to 0. the surface is resolved. For values closer to 1., but the \
+                surface and bottom are resolved. For the ROMS transforms it is \
+                the bottom stretching parameter in [0., 4.], and the bottom \
+                exponent (> 0.) for geyer.",
         default_value = "0.5"
     )]
     theta_b: Option<f64>,
+    #[clap(
+        long,
+        help = "Surface stretching parameter of the ROMS transforms, in [0., 10.]. \
+                For geyer it is the surface exponent and must be > 0.",
+        default_value = "5."
+    )]
+    theta_s: Option<f64>,
     #[clap(long)]
     dz_bottom_min: Option<f64>,
+    #[clap(
+        long,
+        help = "Largest allowed difference in the number of levels between \
+                neighbouring nodes. Nodes with too few levels are refined."
+    )]
+    max_level_jump: Option<usize>,
+    #[clap(
+        long,
+        help = "Thickness of the surface layer where the water column allows it"
+    )]
+    dz_surface_target: Option<f64>,
+    #[clap(
+        long,
+        help = "Largest allowed layer thickness. Also sets the level counts of \
+                the max-thickness strategy"
+    )]
+    dz_max: Option<f64>,
+    #[clap(
+        long,
+        help = "YAML (or .json) list of region polygons with their own master \
+                grids and/or stretching, in the format of the regions of a recipe"
+    )]
+    regions: Option<PathBuf>,
+    #[clap(
+        long,
+        requires = "regions",
+        help = "Width of the band across region outlines over which the grids \
+                are blended, in hgrid coordinate units"
+    )]
+    blending_width: Option<f64>,
+    #[clap(
+        long,
+        action,
+        help = "Write the per-node vgrid.in layout used before SCHISM 5.10"
+    )]
+    legacy_format: bool,
+    #[clap(
+        long,
+        action,
+        help = "Print rx0 and rx1 pressure-gradient diagnostics of the generated grid"
+    )]
+    diagnostics: bool,
+    #[clap(long, help = "Write the 3D size and layer thickness summary as JSON")]
+    summary_json: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "vgrid.in to compare the compute cost of the generated grid against"
+    )]
+    reference_vgrid: Option<PathBuf>,
+    #[cfg(feature = "plot")]
     #[clap(long, action)]
     show_zmas_plot: bool,
+    #[cfg(feature = "plot")]
     #[clap(long)]
     save_zmas_plot: Option<PathBuf>,
     #[clap(subcommand)]
-    mode: Modes,
+    mode: Option<Modes>,
 }
 
 #[derive(ValueEnum, Clone, Debug)]
 enum StretchingFunctionKind {
     Quadratic,
     S,
-    // Shchepetkin2005,
-    // Geyer,
-    // Shchepetkin2010,
+    Shchepetkin2005,
+    Geyer,
+    Shchepetkin2010,
+    Souza,
     // FixedZ
     // MultiMaster
 }
 
+#[derive(ValueEnum, Clone, Debug)]
+enum HsmStrategyKind {
+    Kmeans,
+    Quantiles,
+    LogSpaced,
+    Jenks,
+    MaxThickness,
+}
+
 #[derive(Subcommand, Debug)]
 enum Modes {
     Kmeans(KmeansCliOpts),
     Hsm(HsmCliOpts),
     Auto(AutoCliOpts),
+    Compare(CompareCliOpts),
 }
 
 #[derive(Args, Debug)]
 struct KmeansCliOpts {
-    #[clap(short, long, help = "Number of clusters. Must be an interger >= 1")]
+    #[clap(
+        short,
+        long,
+        help = "Number of clusters (master grids). Must be an interger >= 1"
+    )]
     clusters: usize,
+    #[clap(
+        long,
+        default_value = "kmeans",
+        help = "How the master grid depths are chosen"
+    )]
+    strategy: HsmStrategyKind,
+    #[clap(long, help = "Seed that makes the kmeans strategy deterministic")]
+    seed: Option<u64>,
+    #[clap(
+        long,
+        default_value = "1.",
+        help = "First master depth of the log-spaced and max-thickness strategies"
+    )]
+    initial_depth: f64,
     #[clap(
         short,
         long,
@@ -123,78 +273,214 @@ struct AutoCliOpts {
     max_levels: Option<usize>,
 }
 
-fn entrypoint() -> Result<(), Box<dyn Error>> {
-    pretty_env_logger::init();
-    let cli = Cli::parse();
-    let hgrid = Hgrid::try_from(&cli.hgrid_path)?;
-    let transform = match cli.transform {
-        StretchingFunctionKind::Quadratic => {
-            let quadratic_opts = QuadraticTransformOpts {
-                a_vqs0: cli.a_vqs0.as_ref().unwrap(),
-                etal: cli.etal.as_ref().unwrap(),
-                skew_decay_rate: cli.skew_decay_rate.as_ref().unwrap(),
-            };
-            StretchingFunction::Quadratic(quadratic_opts)
-        }
-        StretchingFunctionKind::S => {
-            let s_opts = STransformOpts {
-                a_vqs0: cli.a_vqs0.as_ref().unwrap(),
-                etal: cli.etal.as_ref().unwrap(),
-                theta_b: cli.theta_b.as_ref().unwrap(),
-                theta_f: cli.theta_f.as_ref().unwrap(),
-            };
-            StretchingFunction::S(s_opts)
-        }
+/// Candidate grids of the auto mode, or of the --config recipe, with every
+/// combination of the listed values.
+#[derive(Args, Debug)]
+struct CompareCliOpts {
+    #[clap(
+        long,
+        value_delimiter = ' ',
+        num_args = 1..,
+        help = "Numbers of master grids (kmeans clusters with a kmeans --config) to try. \
+                Required unless --config is given"
+    )]
+    ngrids: Vec<usize>,
+    #[clap(
+        long,
+        default_value = "1.",
+        help = "This is the first depth below etal. This input is positive down."
+    )]
+    initial_depth: f64,
+    #[clap(long, value_delimiter = ' ', num_args = 1..)]
+    shallow_levels: Vec<usize>,
+    #[clap(long, value_delimiter = ' ', num_args = 1..)]
+    max_levels: Vec<usize>,
+    #[clap(long, value_delimiter = ' ', num_args = 1.., help = "Only for the S transform")]
+    theta_f: Vec<f64>,
+    #[clap(
+        long,
+        value_delimiter = ' ',
+        num_args = 1..,
+        help = "Only for the S and ROMS transforms"
+    )]
+    theta_b: Vec<f64>,
+    #[clap(long, value_delimiter = ' ', num_args = 1..)]
+    a_vqs0: Vec<f64>,
+    #[clap(
+        long,
+        help = "Write the ranking as HTML, or as CSV if the file name ends in .csv. \
+                The best candidate is written to --output-filepath and --save-config"
+    )]
+    report: Option<PathBuf>,
+}
+
+fn recipe_from_cli(cli: &Cli) -> Result<VQSRecipe, Box<dyn Error>> {
+    let transform = cli
+        .transform
+        .as_ref()
+        .ok_or("--transform is required unless --config is given")?;
+    let etal = cli.etal.unwrap();
+    let a_vqs0 = cli.a_vqs0.unwrap();
+    let roms = RomsRecipe {
+        etal,
+        a_vqs0,
+        theta_s: cli.theta_s.unwrap(),
+        theta_b: cli.theta_b.unwrap(),
     };
-    let vqs = match &cli.mode {
-        Modes::Hsm(opts) => {
-            let mut builder = VQSBuilder::default();
-            builder.hgrid(&hgrid);
-            builder.depths(&opts.depths);
-            builder.nlevels(&opts.nlevels);
-            if cli.dz_bottom_min.is_some() {
-                builder.dz_bottom_min(cli.dz_bottom_min.as_ref().unwrap());
-            }
-            builder.stretching(&transform);
-            builder.build()?
-        }
-        Modes::Kmeans(opts) => {
-            let mut builder = VQSKMeansBuilder::default();
-            builder.hgrid(&hgrid);
-            builder.stretching(&transform);
-            builder.nclusters(&opts.clusters);
-            if cli.dz_bottom_min.is_some() {
-                builder.dz_bottom_min(cli.dz_bottom_min.as_ref().unwrap());
-            }
-            builder.etal(cli.etal.as_ref().unwrap());
-            if let Some(shallow_levels) = &opts.shallow_levels {
-                builder.shallow_levels(shallow_levels);
-            }
-            if let Some(max_levels) = &opts.max_levels {
-                builder.max_levels(max_levels);
-            }
-            builder.build()?
-        }
-        Modes::Auto(opts) => {
-            let mut builder = VQSAutoBuilder::default();
-            builder.hgrid(&hgrid);
-            builder.stretching(&transform);
-            builder.ngrids(&opts.ngrids);
-            if cli.dz_bottom_min.is_some() {
-                builder.dz_bottom_min(cli.dz_bottom_min.as_ref().unwrap());
-            }
-            builder.initial_depth(&opts.initial_depth.as_ref().unwrap());
-            builder.shallow_levels(&opts.shallow_levels.as_ref().unwrap());
-            if let Some(max_levels) = &opts.max_levels {
-                builder.max_levels(max_levels);
-            }
-            builder.build()?
-        }
+    let stretching = match transform {
+        StretchingFunctionKind::Quadratic => StretchingRecipe::Quadratic(QuadraticRecipe {
+            etal,
+            a_vqs0,
+            skew_decay_rate: cli.skew_decay_rate.unwrap(),
+        }),
+        StretchingFunctionKind::S => StretchingRecipe::S(SRecipe {
+            etal,
+            a_vqs0,
+            theta_b: cli.theta_b.unwrap(),
+            theta_f: cli.theta_f.unwrap(),
+        }),
+        StretchingFunctionKind::Shchepetkin2005 => StretchingRecipe::Shchepetkin2005(roms),
+        StretchingFunctionKind::Geyer => StretchingRecipe::Geyer(roms),
+        StretchingFunctionKind::Shchepetkin2010 => StretchingRecipe::Shchepetkin2010(roms),
+        StretchingFunctionKind::Souza => StretchingRecipe::Souza(roms),
+    };
+    let mode = cli
+        .mode
+        .as_ref()
+        .ok_or("a mode subcommand is required unless --config is given")?;
+    let master_grids = match mode {
+        Modes::Hsm(opts) => MasterGridsRecipe::Hsm {
+            depths: opts.depths.clone(),
+            nlevels: opts.nlevels.clone(),
+        },
+        Modes::Kmeans(opts) => MasterGridsRecipe::Kmeans {
+            clusters: opts.clusters,
+            strategy: match opts.strategy {
+                HsmStrategyKind::Kmeans => HsmStrategyRecipe::Kmeans,
+                HsmStrategyKind::Quantiles => HsmStrategyRecipe::Quantiles,
+                HsmStrategyKind::LogSpaced => HsmStrategyRecipe::LogSpaced,
+                HsmStrategyKind::Jenks => HsmStrategyRecipe::Jenks,
+                HsmStrategyKind::MaxThickness => HsmStrategyRecipe::MaxThickness,
+            },
+            seed: opts.seed,
+            initial_depth: opts.initial_depth,
+            shallow_levels: opts.shallow_levels.unwrap(),
+            max_levels: opts.max_levels,
+        },
+        Modes::Auto(opts) => MasterGridsRecipe::Auto {
+            ngrids: opts.ngrids,
+            initial_depth: opts.initial_depth.unwrap(),
+            shallow_levels: opts.shallow_levels.unwrap(),
+            max_levels: opts.max_levels,
+        },
+        // the sweep sets the tuned values, these are placeholders
+        Modes::Compare(opts) => MasterGridsRecipe::Auto {
+            ngrids: *opts
+                .ngrids
+                .first()
+                .ok_or("--ngrids is required unless --config is given")?,
+            initial_depth: opts.initial_depth,
+            shallow_levels: opts.shallow_levels.first().copied().unwrap_or(2),
+            max_levels: None,
+        },
+    };
+    Ok(VQSRecipe {
+        stretching,
+        master_grids,
+        dz_bottom_min: cli.dz_bottom_min,
+        max_level_jump: cli.max_level_jump,
+        dz_surface_target: cli.dz_surface_target,
+        dz_max: cli.dz_max,
+        regions: match &cli.regions {
+            Some(path) => Some(RegionRecipe::list_from_file(path)?),
+            None => None,
+        },
+        blending_width: cli.blending_width,
+    })
+}
+
+fn write_barotropic(cli: &Cli, hgrid: &Hgrid) -> Result<(), Box<dyn Error>> {
+    if cli.mode.is_some() {
+        return Err("a mode subcommand cannot be combined with --2d".into());
+    }
+    let sz = SZ::barotropic();
+    match &cli.output_filepath {
+        Some(output_filepath) => sz.write_to_file(output_filepath)?,
+        None => println!("{}", sz),
+    }
+    if let Some(param_nml) = &cli.param_nml {
+        ParamNml::try_from_file(param_nml)?.validate(&sz)?;
+    }
+    let summary = VgridSummary::compute_with_reference(hgrid, &sz, cli.reference_vgrid.as_deref())?;
+    print!("{}", summary);
+    if let Some(summary_json) = &cli.summary_json {
+        summary.write_json(summary_json)?;
+    }
+    Ok(())
+}
+
+fn compare(cli: &Cli, opts: &CompareCliOpts, hgrid: &Hgrid) -> Result<(), Box<dyn Error>> {
+    let base = match &cli.config {
+        Some(config) => VQSRecipe::from_file(config)?,
+        None => recipe_from_cli(cli)?,
+    };
+    let sweep = ParameterSweep {
+        ngrids: opts.ngrids.clone(),
+        shallow_levels: opts.shallow_levels.clone(),
+        max_levels: opts.max_levels.clone(),
+        theta_f: opts.theta_f.clone(),
+        theta_b: opts.theta_b.clone(),
+        a_vqs0: opts.a_vqs0.clone(),
     };
-    if cli.output_filepath.is_some() {
-        vqs.write_to_file(&cli.output_filepath.as_ref().unwrap())?;
+    let report = TuningReport::compute(hgrid, &base, &sweep, &ScoreWeights::default())?;
+    print!("{}", report);
+    if let Some(path) = &opts.report {
+        report.write_to_file(path)?;
+    }
+    if cli.output_filepath.is_none() && cli.save_config.is_none() {
+        return Ok(());
+    }
+    let best = report
+        .best()
+        .ok_or("none of the candidate grids could be built")?;
+    println!();
+    generate(cli, hgrid, &best.recipe)
+}
+
+fn generate(cli: &Cli, hgrid: &Hgrid, recipe: &VQSRecipe) -> Result<(), Box<dyn Error>> {
+    // the saved recipe must pin the k-means clustering to rebuild this grid
+    let recipe = &recipe.seeded();
+    if let Some(save_config) = &cli.save_config {
+        recipe.write_to_file(save_config)?;
+    }
+    let (vqs, master_grids) = recipe.build_with_master_grids(hgrid)?;
+    if let Some(master_grids) = master_grids {
+        print!("{}", master_grids);
+    }
+    if let Some(output_filepath) = &cli.output_filepath {
+        let format = if cli.legacy_format {
+            VgridFormat::Legacy
+        } else {
+            VgridFormat::ColumnMajor
+        };
+        vqs.write_to_file_with_format(output_filepath, format)?;
     };
+    if let Some(param_nml) = &cli.param_nml {
+        ParamNml::try_from_file(param_nml)?.validate(&vqs)?;
+    }
 
+    let summary =
+        VgridSummary::compute_with_reference(hgrid, &vqs, cli.reference_vgrid.as_deref())?;
+    print!("{}", summary);
+    if let Some(summary_json) = &cli.summary_json {
+        summary.write_json(summary_json)?;
+    }
+
+    if cli.diagnostics {
+        print!("{}", PressureGradientDiagnostics::compute(hgrid, &vqs)?);
+    }
+
+    #[cfg(feature = "plot")]
     if cli.show_zmas_plot || cli.save_zmas_plot.is_some() {
         let zmas_plot = vqs.make_z_mas_plot()?;
         if let Some(save_path) = &cli.save_zmas_plot {
@@ -209,6 +495,26 @@ fn entrypoint() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn entrypoint() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::init();
+    let cli = Cli::parse();
+    let hgrid = Hgrid::try_from(&cli.hgrid_path)?;
+    if cli.two_d {
+        return write_barotropic(&cli, &hgrid);
+    }
+    if let Some(Modes::Compare(opts)) = &cli.mode {
+        return compare(&cli, opts, &hgrid);
+    }
+    let recipe = match &cli.config {
+        Some(_) if cli.mode.is_some() => {
+            return Err("a mode subcommand cannot be combined with --config".into())
+        }
+        Some(config) => VQSRecipe::from_file(config)?,
+        None => recipe_from_cli(&cli)?,
+    };
+    generate(&cli, &hgrid, &recipe)
+}
+
 fn main() -> ExitCode {
     let exit_code = match entrypoint() {
         Err(e) => {
@@ -0,0 +1,93 @@
+use clap::Parser;
+use schismrs_hgrid::hgrid::Hgrid;
+use schismrs_vgrid::load_vertical_grid;
+use schismrs_vgrid::transect::TransectBuilder;
+use std::process::ExitCode;
+use std::{error::Error, path::PathBuf};
+
+fn parse_point(s: &str) -> Result<(f64, f64), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("`{}` is not an x,y pair", s))?;
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("`{}` is not a valid coordinate", value))
+    };
+    Ok((parse(x)?, parse(y)?))
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    about = "Plot the layers of a vertical grid along a transect through the mesh",
+    long_about = None
+)]
+struct Cli {
+    hgrid_path: PathBuf,
+    vgrid_path: PathBuf,
+    #[clap(
+        long,
+        value_delimiter = ' ',
+        num_args = 2..,
+        value_parser = clap::builder::ValueParser::new(parse_point),
+        help = "Space delimited x,y vertices of the transect, in hgrid coordinates"
+    )]
+    polyline: Vec<(f64, f64)>,
+    #[clap(
+        long,
+        help = "Largest distance between consecutive samples. In hgrid coordinate units"
+    )]
+    spacing: f64,
+    #[cfg(feature = "plot")]
+    #[clap(long, help = "Save the plot as plotly HTML")]
+    html: Option<PathBuf>,
+    #[clap(long, help = "Save the level elevations of every sample as CSV")]
+    csv: Option<PathBuf>,
+    #[cfg(feature = "plot")]
+    #[clap(long, action)]
+    show: bool,
+}
+
+fn entrypoint() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::init();
+    let cli = Cli::parse();
+    let hgrid = Hgrid::try_from(&cli.hgrid_path)?;
+    let vgrid = load_vertical_grid(&hgrid, &cli.vgrid_path)?;
+    let transect = TransectBuilder::default()
+        .hgrid(&hgrid)
+        .vgrid(vgrid.as_ref())
+        .polyline(&cli.polyline)
+        .spacing(&cli.spacing)
+        .build()?;
+    println!(
+        "Sampled {} points along {:.3} units of transect",
+        transect.points().len(),
+        transect.distance().last().unwrap_or(&0.)
+    );
+    if let Some(csv) = &cli.csv {
+        transect.write_csv(csv)?;
+    }
+    #[cfg(feature = "plot")]
+    if cli.html.is_some() || cli.show {
+        let plot = transect.make_plot();
+        if let Some(html) = &cli.html {
+            plot.write_html(html);
+        }
+        if cli.show {
+            plot.show();
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match entrypoint() {
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+        Ok(_) => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,327 @@
+use crate::vertical_grid::{VerticalGrid, ZcorError};
+use ndarray::{Array1, Array2};
+use rayon::prelude::*;
+use schismrs_hgrid::gr3::{write_to_path, Gr3ParserOutputBuilder, Gr3ParserOutputBuilderError};
+use schismrs_hgrid::hgrid::Hgrid;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// rx0 above which pressure-gradient errors are usually considered a risk.
+pub const RX0_THRESHOLD: f64 = 0.2;
+/// rx1 above which pressure-gradient errors are usually considered a risk.
+pub const RX1_THRESHOLD: f64 = 8.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureGradientMetric {
+    Rx0,
+    Rx1,
+}
+
+/// Largest value of a metric and where it occurs.
+#[derive(Debug, Clone, Copy)]
+pub struct DiagnosticMaximum {
+    pub value: f64,
+    pub element_id: u32,
+    /// SCHISM level number, 1 at the bottom.
+    pub level: usize,
+    /// Element centroid.
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Slope parameter rx0 (Beckmann and Haidvogel, 1993) and Haney number rx1
+/// (Haney, 1991) of a vertical grid, per element and level, computed at
+/// eta = 0 from the level elevations of adjacent nodes.
+///
+/// For an edge between nodes i and j, at level k:
+///
+/// - rx0 = |h_i - h_j| / (h_i + h_j), with h the depth of level k below the surface;
+/// - rx1 = |z_i,k - z_j,k + z_i,k-1 - z_j,k-1| / (z_i,k + z_j,k - z_i,k-1 - z_j,k-1).
+///
+/// Element values are the maximum over the element edges. Levels below the
+/// bottom of any node of an edge, and layers of zero thickness, are skipped.
+pub struct PressureGradientDiagnostics {
+    pub element_ids: Vec<u32>,
+    /// Shape (nvrt, ne), NaN where undefined.
+    pub rx0: Array2<f64>,
+    /// Shape (nvrt, ne). Row k - 1 holds the layer between levels k - 1 and k,
+    /// so the first row is NaN.
+    pub rx1: Array2<f64>,
+    pub rx0_max: Option<DiagnosticMaximum>,
+    pub rx1_max: Option<DiagnosticMaximum>,
+}
+
+impl PressureGradientDiagnostics {
+    pub fn compute(
+        hgrid: &Hgrid,
+        vgrid: &dyn VerticalGrid,
+    ) -> Result<Self, PressureGradientDiagnosticsError> {
+        let zcor = vgrid.zcor(hgrid, None)?;
+        let nvrt = vgrid.nvrt();
+        let nodes = hgrid.nodes().hash_map();
+        let index: HashMap<u32, usize> = nodes.keys().enumerate().map(|(i, &id)| (id, i)).collect();
+        let elements: Vec<(u32, Vec<usize>)> = hgrid
+            .elements()
+            .hash_map()
+            .iter()
+            .map(|(&id, node_ids)| (id, node_ids.iter().map(|id| index[id]).collect()))
+            .collect();
+        let centroids: Vec<(f64, f64)> = hgrid
+            .elements()
+            .hash_map()
+            .values()
+            .map(|node_ids| {
+                let n = node_ids.len() as f64;
+                let (x, y) = node_ids.iter().fold((0., 0.), |(x, y), id| {
+                    let coords = &nodes[id].0;
+                    (x + coords[0], y + coords[1])
+                });
+                (x / n, y / n)
+            })
+            .collect();
+
+        let columns: Vec<(Vec<f64>, Vec<f64>)> = elements
+            .par_iter()
+            .map(|(_, vertices)| {
+                let mut rx0 = vec![f64::NAN; nvrt];
+                let mut rx1 = vec![f64::NAN; nvrt];
+                for e in 0..vertices.len() {
+                    let i = vertices[e];
+                    let j = vertices[(e + 1) % vertices.len()];
+                    for k in 0..nvrt {
+                        let (zi, zj) = (zcor[[k, i]], zcor[[k, j]]);
+                        let (hi, hj) = (-zi, -zj);
+                        if hi + hj > 0. {
+                            rx0[k] = nan_max(rx0[k], (hi - hj).abs() / (hi + hj));
+                        }
+                        if k > 0 {
+                            let (zi0, zj0) = (zcor[[k - 1, i]], zcor[[k - 1, j]]);
+                            let thickness = zi + zj - zi0 - zj0;
+                            if thickness > 0. {
+                                let value = (zi - zj + zi0 - zj0).abs() / thickness;
+                                rx1[k] = nan_max(rx1[k], value);
+                            }
+                        }
+                    }
+                }
+                (rx0, rx1)
+            })
+            .collect();
+
+        let ne = elements.len();
+        let mut rx0 = Array2::from_elem((nvrt, ne), f64::NAN);
+        let mut rx1 = Array2::from_elem((nvrt, ne), f64::NAN);
+        for (e, (rx0_column, rx1_column)) in columns.into_iter().enumerate() {
+            rx0.column_mut(e).assign(&Array1::from(rx0_column));
+            rx1.column_mut(e).assign(&Array1::from(rx1_column));
+        }
+        let element_ids: Vec<u32> = elements.iter().map(|(id, _)| *id).collect();
+        let maximum = |values: &Array2<f64>| {
+            values
+                .indexed_iter()
+                .filter(|(_, value)| !value.is_nan())
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|((k, e), &value)| DiagnosticMaximum {
+                    value,
+                    element_id: element_ids[e],
+                    level: k + 1,
+                    x: centroids[e].0,
+                    y: centroids[e].1,
+                })
+        };
+        Ok(Self {
+            rx0_max: maximum(&rx0),
+            rx1_max: maximum(&rx1),
+            element_ids,
+            rx0,
+            rx1,
+        })
+    }
+
+    fn values(&self, metric: PressureGradientMetric) -> &Array2<f64> {
+        match metric {
+            PressureGradientMetric::Rx0 => &self.rx0,
+            PressureGradientMetric::Rx1 => &self.rx1,
+        }
+    }
+
+    /// Worst value over all levels of each element, 0 where undefined.
+    pub fn worst_per_element(&self, metric: PressureGradientMetric) -> Array1<f64> {
+        self.values(metric)
+            .columns()
+            .into_iter()
+            .map(|column| column.iter().fold(0., |max: f64, &v| nan_max(max, v)))
+            .collect()
+    }
+
+    /// Number of elements whose worst value exceeds `threshold`.
+    pub fn count_above(&self, metric: PressureGradientMetric, threshold: f64) -> usize {
+        self.worst_per_element(metric)
+            .iter()
+            .filter(|&&value| value > threshold)
+            .count()
+    }
+
+    /// Write the worst value of each element as a SCHISM .prop file.
+    pub fn write_prop(
+        &self,
+        path: &Path,
+        metric: PressureGradientMetric,
+    ) -> Result<(), PressureGradientDiagnosticsError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for (id, value) in self
+            .element_ids
+            .iter()
+            .zip(self.worst_per_element(metric).iter())
+        {
+            writeln!(file, "{} {}", id, value)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Write a gr3 holding, at each node, the worst value of the elements around it.
+    pub fn write_gr3(
+        &self,
+        hgrid: &Hgrid,
+        path: &Path,
+        metric: PressureGradientMetric,
+    ) -> Result<(), PressureGradientDiagnosticsError> {
+        let worst = self.worst_per_element(metric);
+        let mut node_values: HashMap<u32, f64> = HashMap::new();
+        let elements = hgrid.elements().hash_map();
+        for (id, value) in self.element_ids.iter().zip(worst.iter()) {
+            for node_id in elements[id].iter() {
+                let entry = node_values.entry(*node_id).or_insert(0.);
+                *entry = entry.max(*value);
+            }
+        }
+        let mut nodes = hgrid.nodes().hash_map().clone();
+        for (id, (_, values)) in nodes.iter_mut() {
+            *values = Some(vec![node_values.get(id).copied().unwrap_or(0.)]);
+        }
+        let name = match metric {
+            PressureGradientMetric::Rx0 => "rx0",
+            PressureGradientMetric::Rx1 => "rx1",
+        };
+        let gr3 = Gr3ParserOutputBuilder::default()
+            .description(Some(format!("{} (worst per element)", name)))
+            .crs(hgrid.crs())
+            .nodes(nodes)
+            .elements(Some(elements.clone()))
+            .open_boundaries(Vec::new())
+            .land_boundaries(Vec::new())
+            .interior_boundaries(Vec::new())
+            .build()?;
+        write_to_path(path, &gr3)?;
+        Ok(())
+    }
+}
+
+fn nan_max(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b > a {
+        b
+    } else {
+        a
+    }
+}
+
+impl fmt::Display for PressureGradientDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, metric, maximum, threshold) in [
+            (
+                "rx0",
+                PressureGradientMetric::Rx0,
+                &self.rx0_max,
+                RX0_THRESHOLD,
+            ),
+            (
+                "rx1",
+                PressureGradientMetric::Rx1,
+                &self.rx1_max,
+                RX1_THRESHOLD,
+            ),
+        ] {
+            match maximum {
+                Some(maximum) => writeln!(
+                    f,
+                    "{} max {:.4} at element {} level {} ({}, {}); {} elements above {}",
+                    name,
+                    maximum.value,
+                    maximum.element_id,
+                    maximum.level,
+                    maximum.x,
+                    maximum.y,
+                    self.count_above(metric, threshold),
+                    threshold
+                )?,
+                None => writeln!(f, "{} undefined (no wet layers)", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PressureGradientDiagnosticsError {
+    #[error(transparent)]
+    ZcorError(#[from] ZcorError),
+    #[error(transparent)]
+    Gr3ParserOutputBuilderError(#[from] Gr3ParserOutputBuilderError),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PressureGradientDiagnostics, PressureGradientMetric};
+    use crate::sz::SZ;
+    use crate::test_support::sloping_hgrid;
+    use schismrs_hgrid::hgrid::Hgrid;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_sigma_grid_rx0_and_rx1() {
+        let hgrid = sloping_hgrid(2.);
+        // pure, evenly spaced sigma levels
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "2\n5 1 200.\nZ levels\n1 -200.\nS levels\n10. 0. 1e-6\n\
+             1 -1.\n2 -0.75\n3 -0.5\n4 -0.25\n5 0.\n"
+        )
+        .unwrap();
+        let sz = SZ::try_from_file(file.path()).unwrap();
+        let diagnostics = PressureGradientDiagnostics::compute(&hgrid, &sz).unwrap();
+
+        let rx0_max = diagnostics.rx0_max.unwrap();
+        let rx1_max = diagnostics.rx1_max.unwrap();
+        assert!(rx0_max.value > 0. && rx0_max.value < 1.);
+        // with sigma levels, rx1 of the bottom layer is rx0 scaled by
+        // (sigma_1 + sigma_2) / (sigma_2 - sigma_1)
+        assert_eq!(rx1_max.level, 2);
+        assert!((rx1_max.value - 7. * rx0_max.value).abs() < 1e-4);
+        assert!(diagnostics.rx0.row(4).iter().all(|v| v.is_nan()));
+        assert!(diagnostics.rx1.row(0).iter().all(|v| v.is_nan()));
+        assert!(diagnostics.to_string().contains("rx1 max"));
+
+        let ne = hgrid.elements().hash_map().len();
+        let prop = NamedTempFile::new().unwrap();
+        diagnostics
+            .write_prop(prop.path(), PressureGradientMetric::Rx1)
+            .unwrap();
+        let contents = std::fs::read_to_string(prop.path()).unwrap();
+        assert_eq!(contents.lines().count(), ne);
+        let gr3 = NamedTempFile::new().unwrap();
+        diagnostics
+            .write_gr3(&hgrid, gr3.path(), PressureGradientMetric::Rx0)
+            .unwrap();
+        let reloaded = Hgrid::try_from(&gr3.path().to_path_buf()).unwrap();
+        assert_eq!(reloaded.nodes().len(), hgrid.nodes().len());
+    }
+}
@@ -0,0 +1,414 @@
+use crate::kmeans_hsm::{cluster_minima, KMeansHSMCreateError};
+use ndarray::Array1;
+use schismrs_hgrid::Hgrid;
+use std::fmt;
+use thiserror::Error;
+
+/// Jenks breaks are computed on at most this many depth quantiles, which keeps
+/// the O(k n²) optimisation cheap on large meshes.
+const JENKS_MAX_SAMPLES: usize = 1000;
+
+/// A way of choosing the master grids (`hsm` depths and level counts) of a VQS.
+///
+/// Depths are positive down. Each master depth is the deep end of a band of
+/// wet nodes, so the last master depth is the deepest node.
+pub trait HsmStrategy {
+    /// Master depths for the wet node depths, given sorted in increasing order.
+    fn depths(&self, wet_depths: &[f64]) -> Result<Vec<f64>, HsmStrategyError>;
+
+    /// Level counts of the master grids. By default they are spaced evenly
+    /// from `shallow_levels` to `max_levels`, which defaults to
+    /// `shallow_levels + depths.len() - 1`.
+    fn nlevels(
+        &self,
+        depths: &[f64],
+        shallow_levels: usize,
+        max_levels: Option<usize>,
+    ) -> Vec<usize> {
+        let max_levels = max_levels.unwrap_or(shallow_levels + depths.len() - 1);
+        Array1::linspace(shallow_levels as f64, max_levels as f64, depths.len())
+            .iter()
+            .map(|level| (level.round() as usize).max(shallow_levels))
+            .collect()
+    }
+}
+
+/// Master grids chosen by a [`HsmStrategy`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MasterGrids {
+    pub depths: Vec<f64>,
+    pub nlevels: Vec<usize>,
+    /// Wet nodes deeper than the previous master depth and at most as deep as
+    /// this one.
+    pub band_counts: Vec<usize>,
+}
+
+impl fmt::Display for MasterGrids {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>12} {:>8} {:>10}",
+            "grid", "depth", "nlevels", "wet nodes"
+        )?;
+        for (m, ((depth, nlevels), count)) in self
+            .depths
+            .iter()
+            .zip(self.nlevels.iter())
+            .zip(self.band_counts.iter())
+            .enumerate()
+        {
+            writeln!(
+                f,
+                "{:>6} {:>12.3} {:>8} {:>10}",
+                m + 1,
+                depth,
+                nlevels,
+                count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Choose the master grids of `hgrid` with `strategy`.
+///
+/// Nodes are wet when they are at least `-etal` deep, as in [`crate::kmeans_hsm`].
+pub fn master_grids(
+    strategy: &dyn HsmStrategy,
+    hgrid: &Hgrid,
+    etal: f64,
+    shallow_levels: usize,
+    max_levels: Option<usize>,
+) -> Result<MasterGrids, HsmStrategyError> {
+    let mut wet_depths: Vec<f64> = hgrid
+        .depths()
+        .iter()
+        .map(|depth| -depth)
+        .filter(|&depth| depth >= -etal)
+        .collect();
+    if wet_depths.is_empty() {
+        return Err(HsmStrategyError::NoWetNodes);
+    }
+    wet_depths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut depths: Vec<f64> = strategy
+        .depths(&wet_depths)?
+        .into_iter()
+        .filter(|depth| depth.is_finite())
+        .collect();
+    depths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    depths.dedup();
+    if depths.is_empty() {
+        return Err(HsmStrategyError::NoMasterDepths);
+    }
+    let nlevels = strategy.nlevels(&depths, shallow_levels, max_levels);
+    let mut band_counts = vec![0; depths.len()];
+    for depth in wet_depths.iter() {
+        let band = depths.partition_point(|master| master < depth);
+        band_counts[band.min(depths.len() - 1)] += 1;
+    }
+    for (m, count) in band_counts.iter().enumerate() {
+        log::info!(
+            "Master grid {}: depth {:.3}, {} levels, {} wet nodes",
+            m + 1,
+            depths[m],
+            nlevels[m],
+            count
+        );
+    }
+    Ok(MasterGrids {
+        depths,
+        nlevels,
+        band_counts,
+    })
+}
+
+/// k-means clusters of the distinct wet depths. With a `seed` the clustering
+/// is deterministic.
+#[derive(Clone, Debug)]
+pub struct KMeansStrategy {
+    pub nclusters: usize,
+    pub seed: Option<u64>,
+}
+
+impl HsmStrategy for KMeansStrategy {
+    fn depths(&self, wet_depths: &[f64]) -> Result<Vec<f64>, HsmStrategyError> {
+        validate_ngrids(self.nclusters)?;
+        // clustering the negative depths makes each minimum the deep end of its cluster
+        let mut depths: Vec<f64> = wet_depths.iter().map(|depth| -depth).collect();
+        depths.dedup();
+        Ok(
+            cluster_minima(Array1::from(depths), self.nclusters, self.seed)?
+                .into_iter()
+                .map(|depth| -depth)
+                .collect(),
+        )
+    }
+}
+
+/// Bands holding about the same number of wet nodes.
+#[derive(Clone, Debug)]
+pub struct QuantileStrategy {
+    pub ngrids: usize,
+}
+
+impl HsmStrategy for QuantileStrategy {
+    fn depths(&self, wet_depths: &[f64]) -> Result<Vec<f64>, HsmStrategyError> {
+        validate_ngrids(self.ngrids)?;
+        Ok(quantiles(wet_depths, self.ngrids))
+    }
+}
+
+/// Master depths in geometric progression from `initial_depth` to the
+/// deepest node.
+#[derive(Clone, Debug)]
+pub struct LogSpacedStrategy {
+    pub ngrids: usize,
+    pub initial_depth: f64,
+}
+
+impl HsmStrategy for LogSpacedStrategy {
+    fn depths(&self, wet_depths: &[f64]) -> Result<Vec<f64>, HsmStrategyError> {
+        validate_ngrids(self.ngrids)?;
+        log_spaced(wet_depths, self.ngrids, self.initial_depth)
+    }
+}
+
+/// Jenks natural breaks of the wet depths, which minimise the depth variance
+/// within each band.
+#[derive(Clone, Debug)]
+pub struct JenksStrategy {
+    pub nclasses: usize,
+}
+
+impl HsmStrategy for JenksStrategy {
+    fn depths(&self, wet_depths: &[f64]) -> Result<Vec<f64>, HsmStrategyError> {
+        validate_ngrids(self.nclasses)?;
+        let samples = if wet_depths.len() > JENKS_MAX_SAMPLES {
+            quantiles(wet_depths, JENKS_MAX_SAMPLES)
+        } else {
+            wet_depths.to_vec()
+        };
+        Ok(jenks_upper_bounds(&samples, self.nclasses))
+    }
+}
+
+/// Log-spaced master depths with as many levels as needed to keep every
+/// layer of the master grids at most `dz_max` thick. `max_levels`, when set,
+/// caps the level counts.
+#[derive(Clone, Debug)]
+pub struct TargetMaxThicknessStrategy {
+    pub ngrids: usize,
+    pub initial_depth: f64,
+    pub dz_max: f64,
+}
+
+impl HsmStrategy for TargetMaxThicknessStrategy {
+    fn depths(&self, wet_depths: &[f64]) -> Result<Vec<f64>, HsmStrategyError> {
+        validate_ngrids(self.ngrids)?;
+        if self.dz_max.is_nan() || self.dz_max <= 0. {
+            return Err(HsmStrategyError::InvalidParameter(format!(
+                "dz_max must be > 0, but got {}",
+                self.dz_max
+            )));
+        }
+        log_spaced(wet_depths, self.ngrids, self.initial_depth)
+    }
+
+    fn nlevels(
+        &self,
+        depths: &[f64],
+        shallow_levels: usize,
+        max_levels: Option<usize>,
+    ) -> Vec<usize> {
+        let mut previous = shallow_levels;
+        depths
+            .iter()
+            .map(|depth| {
+                let mut levels = ((depth / self.dz_max).ceil() as usize + 1).max(previous);
+                if let Some(max_levels) = max_levels {
+                    if levels > max_levels {
+                        log::warn!(
+                            "{} levels are needed for dz_max={} at depth {:.3}, capped to max_levels={}",
+                            levels,
+                            self.dz_max,
+                            depth,
+                            max_levels
+                        );
+                        levels = max_levels;
+                    }
+                }
+                previous = levels;
+                levels
+            })
+            .collect()
+    }
+}
+
+fn validate_ngrids(ngrids: usize) -> Result<(), HsmStrategyError> {
+    if ngrids < 1 {
+        return Err(HsmStrategyError::InvalidParameter(
+            "the number of master grids must be >= 1".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Upper ends of `n` equally populated bands of the sorted `values`.
+fn quantiles(values: &[f64], n: usize) -> Vec<f64> {
+    (1..=n)
+        .map(|m| {
+            let index = ((m * values.len()) as f64 / n as f64).ceil() as usize;
+            values[index.clamp(1, values.len()) - 1]
+        })
+        .collect()
+}
+
+fn log_spaced(
+    wet_depths: &[f64],
+    ngrids: usize,
+    initial_depth: f64,
+) -> Result<Vec<f64>, HsmStrategyError> {
+    let max_depth = wet_depths[wet_depths.len() - 1];
+    if initial_depth.is_nan() || initial_depth <= 0. || initial_depth >= max_depth {
+        return Err(HsmStrategyError::InvalidParameter(format!(
+            "initial_depth must be in (0, {}), but got {}",
+            max_depth, initial_depth
+        )));
+    }
+    if ngrids == 1 {
+        return Ok(vec![max_depth]);
+    }
+    let ratio = (max_depth / initial_depth).powf(1. / (ngrids - 1) as f64);
+    let mut depths: Vec<f64> = (0..ngrids)
+        .map(|m| initial_depth * ratio.powi(m as i32))
+        .collect();
+    depths[ngrids - 1] = max_depth;
+    Ok(depths)
+}
+
+/// Fisher–Jenks optimal partition of the sorted `values` into `nclasses`
+/// classes, returned as the largest value of each class.
+fn jenks_upper_bounds(values: &[f64], nclasses: usize) -> Vec<f64> {
+    let n = values.len();
+    let k = nclasses.min(n);
+    let mut s1 = vec![0.; n + 1];
+    let mut s2 = vec![0.; n + 1];
+    for (i, value) in values.iter().enumerate() {
+        s1[i + 1] = s1[i] + value;
+        s2[i + 1] = s2[i] + value * value;
+    }
+    // sum of squared deviations of values[i..j]
+    let ssd = |i: usize, j: usize| {
+        let sum = s1[j] - s1[i];
+        s2[j] - s2[i] - sum * sum / (j - i) as f64
+    };
+    let mut cost = vec![vec![f64::INFINITY; n + 1]; k + 1];
+    let mut start = vec![vec![0; n + 1]; k + 1];
+    for (j, cost) in cost[1].iter_mut().enumerate().skip(1) {
+        *cost = ssd(0, j);
+    }
+    for c in 2..=k {
+        for j in c..=n {
+            for i in c - 1..j {
+                let candidate = cost[c - 1][i] + ssd(i, j);
+                if candidate < cost[c][j] {
+                    cost[c][j] = candidate;
+                    start[c][j] = i;
+                }
+            }
+        }
+    }
+    let mut bounds = vec![0.; k];
+    let mut j = n;
+    for c in (1..=k).rev() {
+        bounds[c - 1] = values[j - 1];
+        j = start[c][j];
+    }
+    bounds
+}
+
+#[derive(Error, Debug)]
+pub enum HsmStrategyError {
+    #[error("The hgrid has no wet nodes")]
+    NoWetNodes,
+    #[error("The strategy did not produce any master depth")]
+    NoMasterDepths,
+    #[error("Invalid master grid strategy parameter: {0}")]
+    InvalidParameter(String),
+    #[error(transparent)]
+    KMeansHSMCreateError(#[from] KMeansHSMCreateError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        jenks_upper_bounds, master_grids, JenksStrategy, KMeansStrategy, LogSpacedStrategy,
+        QuantileStrategy, TargetMaxThicknessStrategy,
+    };
+    use crate::test_support::sloping_hgrid;
+
+    #[test]
+    fn test_jenks_upper_bounds() {
+        let values = [1., 1.1, 1.2, 10., 10.5, 100., 101.];
+        assert_eq!(jenks_upper_bounds(&values, 3), vec![1.2, 10.5, 101.]);
+        assert_eq!(jenks_upper_bounds(&values, 1), vec![101.]);
+    }
+
+    #[test]
+    fn test_strategies_on_hgrid() {
+        let hgrid = sloping_hgrid(2.);
+        let depths = hgrid.depths().mapv(|depth| -depth);
+        let max_depth = depths.iter().cloned().fold(f64::MIN, f64::max);
+        let nwet = depths.iter().filter(|&&depth| depth >= 0.).count();
+
+        let quantile = master_grids(&QuantileStrategy { ngrids: 4 }, &hgrid, 0., 3, None).unwrap();
+        assert_eq!(quantile.depths.len(), 4);
+        assert_eq!(quantile.nlevels, vec![3, 4, 5, 6]);
+        assert_eq!(*quantile.depths.last().unwrap(), max_depth);
+        assert_eq!(quantile.band_counts.iter().sum::<usize>(), nwet);
+        let largest = *quantile.band_counts.iter().max().unwrap();
+        let smallest = *quantile.band_counts.iter().min().unwrap();
+        assert!(largest - smallest <= nwet / 4);
+
+        let log_spaced = LogSpacedStrategy {
+            ngrids: 3,
+            initial_depth: 2.,
+        };
+        let grids = master_grids(&log_spaced, &hgrid, 0., 3, Some(9)).unwrap();
+        assert_eq!(grids.depths[0], 2.);
+        assert!((grids.depths[1] - (2. * max_depth).sqrt()).abs() < 1e-9);
+        assert_eq!(grids.nlevels, vec![3, 6, 9]);
+
+        let jenks = master_grids(&JenksStrategy { nclasses: 3 }, &hgrid, 0., 3, None).unwrap();
+        assert_eq!(jenks.depths.len(), 3);
+        assert_eq!(jenks.band_counts.iter().sum::<usize>(), nwet);
+
+        let kmeans = KMeansStrategy {
+            nclusters: 3,
+            seed: Some(42),
+        };
+        let first = master_grids(&kmeans, &hgrid, 0., 3, None).unwrap();
+        let second = master_grids(&kmeans, &hgrid, 0., 3, None).unwrap();
+        assert_eq!(first.depths, second.depths);
+        assert_eq!(first.nlevels, second.nlevels);
+        assert_eq!(first.band_counts, second.band_counts);
+        assert_eq!(first.band_counts.iter().sum::<usize>(), nwet);
+
+        let thickness = TargetMaxThicknessStrategy {
+            ngrids: 4,
+            initial_depth: 2.,
+            dz_max: 10.,
+        };
+        let grids = master_grids(&thickness, &hgrid, 0., 3, None).unwrap();
+        for (depth, nlevels) in grids.depths.iter().zip(grids.nlevels.iter()) {
+            assert!(depth / (*nlevels - 1) as f64 <= 10.);
+        }
+        assert!(grids.nlevels.windows(2).all(|w| w[0] <= w[1]));
+
+        let invalid = LogSpacedStrategy {
+            ngrids: 3,
+            initial_depth: 2. * max_depth,
+        };
+        assert!(master_grids(&invalid, &hgrid, 0., 3, None).is_err());
+    }
+}
@@ -4,6 +4,8 @@ use linfa::DatasetBase;
 use linfa_clustering::{KMeans, KMeansError};
 use log;
 use ndarray::{Array1, ShapeError};
+use rand_xoshiro::rand_core::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
 use schismrs_hgrid::Hgrid;
 use std::cmp::Ordering;
 use std::time::Instant;
@@ -24,14 +26,33 @@ pub fn kmeans_hsm(
     depths.dedup();
     // keep only the underwater numbers.
     depths.retain(|&x| x <= *etal);
-    let depths = Array1::from(depths);
+    let mut hsm = cluster_minima(Array1::from(depths), *nclusters, None)?;
+    hsm.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    log::debug!(
+        "Took {} to compute vertical distribution.",
+        format_duration(now.elapsed())
+    );
+    Ok(hsm)
+}
+
+/// Minimum value of each k-means cluster of `depths`, in cluster order.
+/// A `seed` makes the clustering deterministic.
+pub(crate) fn cluster_minima(
+    depths: Array1<f64>,
+    nclusters: usize,
+    seed: Option<u64>,
+) -> Result<Vec<f64>, KMeansHSMCreateError> {
     let depth_len = depths.len();
     let observations = DatasetBase::from(depths.clone().into_shape((depth_len, 1))?);
-    let model = KMeans::params(*nclusters).fit(&observations)?;
+    let model = match seed {
+        Some(seed) => KMeans::params_with_rng(nclusters, Xoshiro256Plus::seed_from_u64(seed))
+            .fit(&observations)?,
+        None => KMeans::params(nclusters).fit(&observations)?,
+    };
     let predictions = model.predict(observations);
     let targets = predictions.targets();
     let centroids = model.centroids().to_owned();
-    let mut hsm = Vec::with_capacity(centroids.len());
+    let mut minima = Vec::with_capacity(centroids.len());
     // find the minimum depth associated to each computed centroid
     for (index, _) in centroids.iter().enumerate() {
         let mut min_depth = f64::INFINITY;
@@ -44,14 +65,9 @@ pub fn kmeans_hsm(
                 }
             }
         }
-        hsm.push(min_depth);
+        minima.push(min_depth);
     }
-    hsm.sort_by(|a, b| b.partial_cmp(a).unwrap());
-    log::debug!(
-        "Took {} to compute vertical distribution.",
-        format_duration(now.elapsed())
-    );
-    Ok(hsm)
+    Ok(minima)
 }
 
 #[derive(Error, Debug)]
@@ -1,5 +1,19 @@
 pub use kmeans_hsm::{kmeans_hsm, KMeansHSMCreateError};
+pub use vertical_grid::{
+    load_vertical_grid, VerticalGrid, VerticalGridLoadError, ZcorError, DEFAULT_H0,
+};
+pub mod diagnostics;
+pub mod hsm;
 pub mod kmeans_hsm;
+pub mod param_nml;
+pub mod recipe;
+pub mod remap;
+pub mod summary;
 pub mod sz;
+#[cfg(test)]
+mod test_support;
+pub mod transect;
 pub mod transforms;
+pub mod tuning;
+pub mod vertical_grid;
 pub mod vqs;
@@ -0,0 +1,181 @@
+use crate::vertical_grid::VerticalGrid;
+use f90nmlrs::{F90nmlError, Namelist};
+use std::fmt;
+use std::path::Path;
+use thiserror::Error;
+
+/// A param.nml setting that does not fit the vertical grid it is run with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamNmlIssue {
+    pub key: &'static str,
+    pub value: i64,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParamNmlIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}: {}", self.key, self.value, self.reason)
+    }
+}
+
+/// Settings of a SCHISM param.nml.
+///
+/// Settings are looked up by name in every group, in file order, since
+/// SCHISM versions keep some of them in different groups.
+#[derive(Debug, Clone, Default)]
+pub struct ParamNml {
+    namelist: Namelist,
+}
+
+impl ParamNml {
+    pub fn try_from_file(path: &Path) -> Result<Self, ParamNmlError> {
+        Ok(Self {
+            namelist: f90nmlrs::read(path)?,
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, ParamNmlError> {
+        Ok(Self {
+            namelist: f90nmlrs::reads(contents)?,
+        })
+    }
+
+    pub fn integer(&self, key: &str) -> Result<Option<i64>, ParamNmlError> {
+        self.namelist
+            .groups()
+            .find_map(|(_, group)| group.get(key))
+            .map(|value| {
+                value
+                    .as_integer()
+                    .map_err(|_| ParamNmlError::InvalidInteger(key.to_string(), value.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Settings that SCHISM rejects, or that make no sense, with `vgrid`.
+    ///
+    /// A 2D run (nvrt = 2) must be barotropic (`ibc = 1`, `ibtp = 0`) and
+    /// cannot use a bottom roughness length (`nchi = 1`), which needs a
+    /// resolved bottom layer. The `im2d` switch of older SCHISM versions must
+    /// agree with nvrt. Missing settings are left to the SCHISM defaults and
+    /// not reported.
+    pub fn check(&self, vgrid: &dyn VerticalGrid) -> Result<Vec<ParamNmlIssue>, ParamNmlError> {
+        let mut issues = Vec::new();
+        let mut expect = |key: &'static str, valid: fn(i64) -> bool, reason: &'static str| {
+            if let Some(value) = self.integer(key)? {
+                if !valid(value) {
+                    issues.push(ParamNmlIssue { key, value, reason });
+                }
+            }
+            Ok::<(), ParamNmlError>(())
+        };
+        if vgrid.nvrt() == 2 {
+            expect("ibc", |v| v == 1, "2D runs must be barotropic (ibc = 1)")?;
+            expect(
+                "ibtp",
+                |v| v == 0,
+                "2D runs cannot transport tracers (ibtp = 0)",
+            )?;
+            expect(
+                "nchi",
+                |v| v != 1,
+                "roughness length drag needs a 3D bottom layer, use nchi = 0 or -1",
+            )?;
+            expect("im2d", |v| v == 1, "vgrid.in has 2 levels, set im2d = 1")?;
+        } else {
+            expect("im2d", |v| v == 0, "vgrid.in is 3D, set im2d = 0")?;
+        }
+        Ok(issues)
+    }
+
+    /// Like [`ParamNml::check`], but any issue is an error.
+    pub fn validate(&self, vgrid: &dyn VerticalGrid) -> Result<(), ParamNmlError> {
+        let issues = self.check(vgrid)?;
+        if issues.is_empty() {
+            return Ok(());
+        }
+        Err(ParamNmlError::Inconsistent(
+            issues
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect::<Vec<_>>()
+                .join("; "),
+        ))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ParamNmlError {
+    #[error("Failed to read param.nml: {0}")]
+    NamelistError(#[from] F90nmlError),
+    #[error("param.nml setting {0} = {1} is not an integer")]
+    InvalidInteger(String, String),
+    #[error("param.nml does not match the vertical grid: {0}")]
+    Inconsistent(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParamNml, ParamNmlError};
+    use crate::sz::{BAROTROPIC_H_S, SZ};
+    use crate::VerticalGrid;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    const PARAM_NML: &str = "&CORE
+  ipre = 0 !Pre-process flag
+  ibc = 0, ibtp = 1
+  rnday = 30
+  dt = 100.5
+/
+&OPT
+  nchi = 1
+/
+";
+
+    #[test]
+    fn test_barotropic_param_nml() {
+        let sz = SZ::barotropic();
+        assert_eq!(sz.nvrt(), 2);
+        assert_eq!(sz.h_s(), BAROTROPIC_H_S);
+        let file = NamedTempFile::new().unwrap();
+        sz.write_to_file(&file.path().to_path_buf()).unwrap();
+        let reloaded = SZ::try_from_file(file.path()).unwrap();
+        assert!(reloaded.is_barotropic());
+        assert_eq!(reloaded.to_string(), sz.to_string());
+        assert_eq!(reloaded.zcor_at_node(0, 12., 0.5).to_vec(), vec![-12., 0.5]);
+
+        let param = ParamNml::parse(PARAM_NML).unwrap();
+        assert_eq!(param.integer("RNDAY").unwrap(), Some(30));
+        assert_eq!(param.integer("ihot").unwrap(), None);
+        assert!(matches!(
+            param.integer("dt"),
+            Err(ParamNmlError::InvalidInteger(..))
+        ));
+        let issues = param.check(&sz).unwrap();
+        let keys: Vec<&str> = issues.iter().map(|issue| issue.key).collect();
+        assert_eq!(keys, vec!["ibc", "ibtp", "nchi"]);
+        assert!(matches!(
+            param.validate(&sz),
+            Err(ParamNmlError::Inconsistent(_))
+        ));
+
+        let fixed = PARAM_NML
+            .replace("ibc = 0, ibtp = 1", "ibc = 1, ibtp = 0")
+            .replace("nchi = 1", "nchi = -1");
+        ParamNml::parse(&fixed).unwrap().validate(&sz).unwrap();
+
+        let legacy = ParamNml::parse(&fixed.replace("nchi = -1", "nchi = -1\n  im2d = 1")).unwrap();
+        legacy.validate(&sz).unwrap();
+        let mut three_d = NamedTempFile::new().unwrap();
+        write!(
+            three_d,
+            "2\n3 1 1e6\nZ levels\n1 -1e6\nS levels\n40. 1. 1e-4\n1 -1.\n2 -0.5\n3 0.\n"
+        )
+        .unwrap();
+        let three_d = SZ::try_from_file(three_d.path()).unwrap();
+        let issues = legacy.check(&three_d).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].key, issues[0].value), ("im2d", 1));
+    }
+}
@@ -0,0 +1,765 @@
+// schismrs-vgrid/src/recipe.rs
+
+//! Owned, serializable descriptions of how a vertical grid is generated.
+//!
+//! The builders borrow their inputs, so they cannot be stored or read from
+//! disk. A recipe holds every builder and transform option by value and can
+//! be kept next to the hgrid under version control, e.g.
+//!
+//! ```yaml
+//! stretching:
+//!   kind: quadratic
+//!   skew_decay_rate: 0.03
+//! master_grids:
+//!   mode: hsm
+//!   depths: [5., 20., 60., 110.]
+//!   nlevels: [3, 6, 10, 14]
+//! dz_max: 10.
+//! ```
+//!
+//! Files with a `.json` extension are read and written as JSON, anything
+//! else as YAML.
+
+use crate::hsm::{
+    HsmStrategy, JenksStrategy, KMeansStrategy, LogSpacedStrategy, MasterGrids, QuantileStrategy,
+    TargetMaxThicknessStrategy,
+};
+use crate::sz::{SZBuilder, SZBuilderError, BAROTROPIC_H_S, SZ};
+use crate::transforms::quadratic::QuadraticTransformOpts;
+use crate::transforms::roms::RomsTransformOpts;
+use crate::transforms::s::STransformOpts;
+use crate::transforms::transforms::ReconstructedOpts;
+use crate::transforms::StretchingFunction;
+use crate::vqs::{
+    VQSAutoBuilder, VQSAutoBuilderError, VQSBuilder, VQSBuilderError, VQSKMeansBuilder,
+    VQSKMeansBuilderError, VQSRegion, VQS,
+};
+use schismrs_hgrid::hgrid::Hgrid;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+use thiserror::Error;
+
+/// Stretching function of a [`VQSRecipe`], tagged by `kind`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum StretchingRecipe {
+    Quadratic(QuadraticRecipe),
+    S(SRecipe),
+    Shchepetkin2005(RomsRecipe),
+    Geyer(RomsRecipe),
+    Shchepetkin2010(RomsRecipe),
+    Souza(RomsRecipe),
+    Reconstructed(ReconstructedOpts),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuadraticRecipe {
+    pub etal: f64,
+    pub a_vqs0: f64,
+    pub skew_decay_rate: f64,
+}
+
+impl Default for QuadraticRecipe {
+    fn default() -> Self {
+        let opts = QuadraticTransformOpts::new();
+        Self {
+            etal: *opts.etal,
+            a_vqs0: *opts.a_vqs0,
+            skew_decay_rate: *opts.skew_decay_rate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SRecipe {
+    pub etal: f64,
+    pub a_vqs0: f64,
+    pub theta_b: f64,
+    pub theta_f: f64,
+}
+
+impl Default for SRecipe {
+    fn default() -> Self {
+        let opts = STransformOpts::new();
+        Self {
+            etal: *opts.etal,
+            a_vqs0: *opts.a_vqs0,
+            theta_b: *opts.theta_b,
+            theta_f: *opts.theta_f,
+        }
+    }
+}
+
+/// Options shared by the ROMS stretching functions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomsRecipe {
+    pub etal: f64,
+    pub a_vqs0: f64,
+    pub theta_s: f64,
+    pub theta_b: f64,
+}
+
+impl Default for RomsRecipe {
+    fn default() -> Self {
+        let opts = RomsTransformOpts::new();
+        Self {
+            etal: *opts.etal,
+            a_vqs0: *opts.a_vqs0,
+            theta_s: *opts.theta_s,
+            theta_b: *opts.theta_b,
+        }
+    }
+}
+
+impl StretchingRecipe {
+    pub fn etal(&self) -> f64 {
+        match self {
+            StretchingRecipe::Quadratic(recipe) => recipe.etal,
+            StretchingRecipe::S(recipe) => recipe.etal,
+            StretchingRecipe::Shchepetkin2005(recipe)
+            | StretchingRecipe::Geyer(recipe)
+            | StretchingRecipe::Shchepetkin2010(recipe)
+            | StretchingRecipe::Souza(recipe) => recipe.etal,
+            StretchingRecipe::Reconstructed(opts) => opts.etal,
+        }
+    }
+
+    /// The [`StretchingFunction`] described by this recipe.
+    pub fn stretching_function(&self) -> StretchingFunction<'_> {
+        match self {
+            StretchingRecipe::Quadratic(recipe) => {
+                StretchingFunction::Quadratic(QuadraticTransformOpts {
+                    etal: &recipe.etal,
+                    a_vqs0: &recipe.a_vqs0,
+                    skew_decay_rate: &recipe.skew_decay_rate,
+                })
+            }
+            StretchingRecipe::S(recipe) => StretchingFunction::S(STransformOpts {
+                etal: &recipe.etal,
+                a_vqs0: &recipe.a_vqs0,
+                theta_b: &recipe.theta_b,
+                theta_f: &recipe.theta_f,
+            }),
+            StretchingRecipe::Shchepetkin2005(recipe) => {
+                StretchingFunction::Shchepetkin2005(recipe.opts())
+            }
+            StretchingRecipe::Geyer(recipe) => StretchingFunction::Geyer(recipe.opts()),
+            StretchingRecipe::Shchepetkin2010(recipe) => {
+                StretchingFunction::Shchepetkin2010(recipe.opts())
+            }
+            StretchingRecipe::Souza(recipe) => StretchingFunction::Souza(recipe.opts()),
+            StretchingRecipe::Reconstructed(opts) => {
+                StretchingFunction::Reconstructed(opts.clone())
+            }
+        }
+    }
+}
+
+impl RomsRecipe {
+    fn opts(&self) -> RomsTransformOpts<'_> {
+        RomsTransformOpts {
+            etal: &self.etal,
+            a_vqs0: &self.a_vqs0,
+            theta_s: &self.theta_s,
+            theta_b: &self.theta_b,
+        }
+    }
+}
+
+/// How the master grids of a [`VQSRecipe`] are obtained, tagged by `mode`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MasterGridsRecipe {
+    /// Explicit master depths and level counts ([`VQSBuilder`]).
+    Hsm {
+        depths: Vec<f64>,
+        nlevels: Vec<usize>,
+    },
+    /// Master depths picked from the hgrid bathymetry ([`VQSKMeansBuilder`]).
+    Kmeans {
+        clusters: usize,
+        #[serde(default)]
+        strategy: HsmStrategyRecipe,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seed: Option<u64>,
+        #[serde(default = "default_initial_depth")]
+        initial_depth: f64,
+        #[serde(default = "default_shallow_levels")]
+        shallow_levels: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_levels: Option<usize>,
+    },
+    /// Log-spaced master depths ([`VQSAutoBuilder`]).
+    Auto {
+        ngrids: usize,
+        #[serde(default = "default_initial_depth")]
+        initial_depth: f64,
+        #[serde(default = "default_shallow_levels")]
+        shallow_levels: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_levels: Option<usize>,
+    },
+}
+
+fn default_initial_depth() -> f64 {
+    1.
+}
+
+fn default_shallow_levels() -> usize {
+    2
+}
+
+/// The [`HsmStrategy`] used by [`MasterGridsRecipe::Kmeans`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HsmStrategyRecipe {
+    #[default]
+    Kmeans,
+    Quantiles,
+    LogSpaced,
+    Jenks,
+    MaxThickness,
+}
+
+impl HsmStrategyRecipe {
+    /// Instantiate the strategy. `dz_max` is only used by
+    /// [`HsmStrategyRecipe::MaxThickness`], which requires it.
+    pub fn strategy(
+        &self,
+        clusters: usize,
+        seed: Option<u64>,
+        initial_depth: f64,
+        dz_max: Option<f64>,
+    ) -> Result<Box<dyn HsmStrategy>, RecipeError> {
+        Ok(match self {
+            HsmStrategyRecipe::Kmeans => Box::new(KMeansStrategy {
+                nclusters: clusters,
+                seed,
+            }),
+            HsmStrategyRecipe::Quantiles => Box::new(QuantileStrategy { ngrids: clusters }),
+            HsmStrategyRecipe::LogSpaced => Box::new(LogSpacedStrategy {
+                ngrids: clusters,
+                initial_depth,
+            }),
+            HsmStrategyRecipe::Jenks => Box::new(JenksStrategy { nclasses: clusters }),
+            HsmStrategyRecipe::MaxThickness => Box::new(TargetMaxThicknessStrategy {
+                ngrids: clusters,
+                initial_depth,
+                dz_max: dz_max.ok_or(RecipeError::MissingDzMax)?,
+            }),
+        })
+    }
+}
+
+/// A part of the domain with its own master grids and/or stretching, see
+/// [`VQSRegion`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegionRecipe {
+    pub polygon: Vec<(f64, f64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depths: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nlevels: Option<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stretching: Option<StretchingRecipe>,
+}
+
+impl RegionRecipe {
+    /// Read a list of regions from a YAML (or .json) file.
+    pub fn list_from_file(path: &Path) -> Result<Vec<Self>, RecipeError> {
+        read_recipe(path)
+    }
+}
+
+/// Everything needed to regenerate a [`VQS`] on a given hgrid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VQSRecipe {
+    pub stretching: StretchingRecipe,
+    pub master_grids: MasterGridsRecipe,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dz_bottom_min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_level_jump: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dz_surface_target: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dz_max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regions: Option<Vec<RegionRecipe>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blending_width: Option<f64>,
+}
+
+impl VQSRecipe {
+    pub fn from_file(path: &Path) -> Result<Self, RecipeError> {
+        read_recipe(path)
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), RecipeError> {
+        write_recipe(self, path)
+    }
+
+    pub fn build(&self, hgrid: &Hgrid) -> Result<VQS, RecipeError> {
+        Ok(self.build_with_master_grids(hgrid)?.0)
+    }
+
+    /// The recipe with a random seed filled in for unseeded k-means
+    /// clustering, so that it builds the same grid every time and can be
+    /// saved to regenerate it. Other recipes are returned unchanged.
+    pub fn seeded(&self) -> Self {
+        let mut recipe = self.clone();
+        if let MasterGridsRecipe::Kmeans {
+            strategy: HsmStrategyRecipe::Kmeans,
+            seed: seed @ None,
+            ..
+        } = &mut recipe.master_grids
+        {
+            *seed = Some(RandomState::new().build_hasher().finish());
+        }
+        recipe
+    }
+
+    /// Build the VQS and, for [`MasterGridsRecipe::Kmeans`], also return the
+    /// master grids picked by the strategy.
+    pub fn build_with_master_grids(
+        &self,
+        hgrid: &Hgrid,
+    ) -> Result<(VQS, Option<MasterGrids>), RecipeError> {
+        let stretching = self.stretching.stretching_function();
+        let regions = self.regions.as_deref().unwrap_or_default();
+        let region_stretchings: Vec<Option<StretchingFunction>> = regions
+            .iter()
+            .map(|region| {
+                region
+                    .stretching
+                    .as_ref()
+                    .map(|stretching| stretching.stretching_function())
+            })
+            .collect();
+        let regions: Vec<VQSRegion> = regions
+            .iter()
+            .zip(&region_stretchings)
+            .map(|(region, stretching)| VQSRegion {
+                polygon: &region.polygon,
+                depths: region.depths.as_ref(),
+                nlevels: region.nlevels.as_ref(),
+                stretching: stretching.as_ref(),
+            })
+            .collect();
+        let (vqs, master_grids) = match &self.master_grids {
+            MasterGridsRecipe::Hsm { depths, nlevels } => {
+                let mut builder = VQSBuilder::default();
+                builder
+                    .hgrid(hgrid)
+                    .depths(depths)
+                    .nlevels(nlevels)
+                    .stretching(&stretching);
+                if let Some(dz_bottom_min) = &self.dz_bottom_min {
+                    builder.dz_bottom_min(dz_bottom_min);
+                }
+                if let Some(max_level_jump) = &self.max_level_jump {
+                    builder.max_level_jump(max_level_jump);
+                }
+                if !regions.is_empty() {
+                    builder.regions(&regions);
+                }
+                if let Some(blending_width) = &self.blending_width {
+                    builder.blending_width(blending_width);
+                }
+                if let Some(dz_surface_target) = &self.dz_surface_target {
+                    builder.dz_surface_target(dz_surface_target);
+                }
+                if let Some(dz_max) = &self.dz_max {
+                    builder.dz_max(dz_max);
+                }
+                (builder.build()?, None)
+            }
+            MasterGridsRecipe::Kmeans {
+                clusters,
+                strategy,
+                seed,
+                initial_depth,
+                shallow_levels,
+                max_levels,
+            } => {
+                let strategy = strategy.strategy(*clusters, *seed, *initial_depth, self.dz_max)?;
+                let etal = self.stretching.etal();
+                let mut builder = VQSKMeansBuilder::default();
+                builder
+                    .hgrid(hgrid)
+                    .stretching(&stretching)
+                    .nclusters(clusters)
+                    .strategy(strategy.as_ref())
+                    .etal(&etal)
+                    .shallow_levels(shallow_levels);
+                if let Some(max_levels) = max_levels {
+                    builder.max_levels(max_levels);
+                }
+                if let Some(dz_bottom_min) = &self.dz_bottom_min {
+                    builder.dz_bottom_min(dz_bottom_min);
+                }
+                if let Some(max_level_jump) = &self.max_level_jump {
+                    builder.max_level_jump(max_level_jump);
+                }
+                if !regions.is_empty() {
+                    builder.regions(&regions);
+                }
+                if let Some(blending_width) = &self.blending_width {
+                    builder.blending_width(blending_width);
+                }
+                if let Some(dz_surface_target) = &self.dz_surface_target {
+                    builder.dz_surface_target(dz_surface_target);
+                }
+                if let Some(dz_max) = &self.dz_max {
+                    builder.dz_max(dz_max);
+                }
+                let (vqs, master_grids) = builder.build_with_master_grids()?;
+                (vqs, Some(master_grids))
+            }
+            MasterGridsRecipe::Auto {
+                ngrids,
+                initial_depth,
+                shallow_levels,
+                max_levels,
+            } => {
+                let mut builder = VQSAutoBuilder::default();
+                builder
+                    .hgrid(hgrid)
+                    .stretching(&stretching)
+                    .ngrids(ngrids)
+                    .initial_depth(initial_depth)
+                    .shallow_levels(shallow_levels);
+                if let Some(max_levels) = max_levels {
+                    builder.max_levels(max_levels);
+                }
+                if let Some(dz_bottom_min) = &self.dz_bottom_min {
+                    builder.dz_bottom_min(dz_bottom_min);
+                }
+                if let Some(max_level_jump) = &self.max_level_jump {
+                    builder.max_level_jump(max_level_jump);
+                }
+                if !regions.is_empty() {
+                    builder.regions(&regions);
+                }
+                if let Some(blending_width) = &self.blending_width {
+                    builder.blending_width(blending_width);
+                }
+                if let Some(dz_surface_target) = &self.dz_surface_target {
+                    builder.dz_surface_target(dz_surface_target);
+                }
+                if let Some(dz_max) = &self.dz_max {
+                    builder.dz_max(dz_max);
+                }
+                (builder.build()?, None)
+            }
+        };
+        Ok((vqs, master_grids))
+    }
+}
+
+/// Everything needed to regenerate an [`SZ`] grid on a given hgrid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SZRecipe {
+    pub slevels: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zlevels: Option<Vec<f64>>,
+    pub theta_f: f64,
+    pub theta_b: f64,
+    pub critical_depth: f64,
+    pub etal: f64,
+}
+
+impl Default for SZRecipe {
+    fn default() -> Self {
+        Self {
+            slevels: 2,
+            zlevels: None,
+            theta_f: 0.1,
+            theta_b: 0.,
+            critical_depth: 5.,
+            etal: 0.,
+        }
+    }
+}
+
+impl SZRecipe {
+    /// Recipe of [`SZ::barotropic`], for 2D runs.
+    pub fn barotropic() -> Self {
+        Self {
+            slevels: 2,
+            zlevels: Some(vec![-BAROTROPIC_H_S]),
+            theta_f: 1.0e-4,
+            theta_b: 1.,
+            critical_depth: 40.,
+            etal: 0.,
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, RecipeError> {
+        read_recipe(path)
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), RecipeError> {
+        write_recipe(self, path)
+    }
+
+    pub fn build(&self, hgrid: &Hgrid) -> Result<SZ, RecipeError> {
+        let mut builder = SZBuilder::default();
+        builder
+            .hgrid(hgrid)
+            .slevels(&self.slevels)
+            .theta_f(&self.theta_f)
+            .theta_b(&self.theta_b)
+            .critical_depth(&self.critical_depth)
+            .etal(&self.etal);
+        if let Some(zlevels) = &self.zlevels {
+            builder.zlevels(zlevels);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+fn read_recipe<T: DeserializeOwned>(path: &Path) -> Result<T, RecipeError> {
+    let content = std::fs::read_to_string(path)?;
+    if is_json(path) {
+        Ok(serde_json::from_str(&content)?)
+    } else {
+        Ok(serde_saphyr::from_str(&content)?)
+    }
+}
+
+fn write_recipe<T: Serialize>(recipe: &T, path: &Path) -> Result<(), RecipeError> {
+    let content = if is_json(path) {
+        serde_json::to_string_pretty(recipe)?
+    } else {
+        serde_saphyr::to_string(recipe)?
+    };
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum RecipeError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("Error parsing JSON recipe: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Error parsing YAML recipe: {0}")]
+    YamlError(#[from] serde_saphyr::Error),
+    #[error("Error writing YAML recipe: {0}")]
+    YamlWriteError(#[from] serde_saphyr::ser::Error),
+    #[error("dz_max is required by the max_thickness strategy")]
+    MissingDzMax,
+    #[error(transparent)]
+    VQSBuilderError(#[from] VQSBuilderError),
+    #[error(transparent)]
+    VQSKMeansBuilderError(#[from] VQSKMeansBuilderError),
+    #[error(transparent)]
+    VQSAutoBuilderError(#[from] VQSAutoBuilderError),
+    #[error(transparent)]
+    SZBuilderError(#[from] SZBuilderError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        HsmStrategyRecipe, MasterGridsRecipe, RecipeError, SZRecipe, StretchingRecipe, VQSRecipe,
+    };
+    use crate::sz::SZ;
+    use crate::test_support::sloping_hgrid;
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::s::STransformOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vqs::{VQSBuilder, VQSRegion};
+    use tempfile::Builder;
+
+    const HSM_RECIPE: &str = "
+stretching:
+  kind: quadratic
+  skew_decay_rate: 0.03
+master_grids:
+  mode: hsm
+  depths: [5., 20., 60., 110.]
+  nlevels: [3, 6, 10, 14]
+dz_surface_target: 0.5
+regions:
+  - polygon: [[0., 0.], [4., 0.], [4., 4.], [0., 4.]]
+    depths: [5., 40., 110.]
+    nlevels: [5, 10, 16]
+    stretching:
+      kind: s
+      etal: 0.
+      a_vqs0: 0.
+      theta_b: 0.5
+      theta_f: 3.
+blending_width: 2.
+";
+
+    #[test]
+    fn test_vqs_recipe_matches_builder() {
+        let hgrid = sloping_hgrid(1.);
+        let recipe: VQSRecipe = serde_saphyr::from_str(HSM_RECIPE).unwrap();
+        assert_eq!(
+            recipe.stretching,
+            StretchingRecipe::Quadratic(Default::default())
+        );
+        let from_recipe = recipe.build(&hgrid).unwrap();
+
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 14];
+        let region_depths = vec![5., 40., 110.];
+        let region_nlevels = vec![5, 10, 16];
+        let region_stretching = StretchingFunction::S(STransformOpts {
+            etal: &0.,
+            a_vqs0: &0.,
+            theta_b: &0.5,
+            theta_f: &3.,
+        });
+        let regions = [VQSRegion {
+            polygon: &[(0., 0.), (4., 0.), (4., 4.), (0., 4.)],
+            depths: Some(&region_depths),
+            nlevels: Some(&region_nlevels),
+            stretching: Some(&region_stretching),
+        }];
+        let from_builder = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .dz_surface_target(&0.5)
+            .regions(&regions)
+            .blending_width(&2.)
+            .build()
+            .unwrap();
+        assert_eq!(from_recipe.sigma(), from_builder.sigma());
+        assert_eq!(
+            from_recipe.bottom_level_indices(),
+            from_builder.bottom_level_indices()
+        );
+
+        // the region changes the grid
+        let without_regions = VQSRecipe {
+            regions: None,
+            blending_width: None,
+            ..recipe.clone()
+        };
+        assert_ne!(
+            without_regions
+                .build(&hgrid)
+                .unwrap()
+                .bottom_level_indices(),
+            from_recipe.bottom_level_indices()
+        );
+
+        // recipes survive a round trip through both file formats
+        for suffix in [".json", ".yaml"] {
+            let file = Builder::new().suffix(suffix).tempfile().unwrap();
+            recipe.write_to_file(file.path()).unwrap();
+            assert_eq!(VQSRecipe::from_file(file.path()).unwrap(), recipe);
+        }
+    }
+
+    #[test]
+    fn test_seeded_kmeans_recipe_regenerates() {
+        let hgrid = sloping_hgrid(1.);
+        let recipe = VQSRecipe {
+            stretching: StretchingRecipe::Quadratic(Default::default()),
+            master_grids: MasterGridsRecipe::Kmeans {
+                clusters: 3,
+                strategy: HsmStrategyRecipe::Kmeans,
+                seed: None,
+                initial_depth: 1.,
+                shallow_levels: 2,
+                max_levels: None,
+            },
+            dz_bottom_min: None,
+            max_level_jump: None,
+            dz_surface_target: None,
+            dz_max: None,
+            regions: None,
+            blending_width: None,
+        };
+        let seeded = recipe.seeded();
+        assert!(matches!(
+            seeded.master_grids,
+            MasterGridsRecipe::Kmeans { seed: Some(_), .. }
+        ));
+        assert_eq!(seeded.seeded(), seeded);
+        let vqs = seeded.build(&hgrid).unwrap();
+
+        let file = Builder::new().suffix(".yaml").tempfile().unwrap();
+        seeded.write_to_file(file.path()).unwrap();
+        let reloaded = VQSRecipe::from_file(file.path()).unwrap();
+        assert_eq!(reloaded, seeded);
+        let rebuilt = reloaded.build(&hgrid).unwrap();
+        assert_eq!(rebuilt.sigma(), vqs.sigma());
+        assert_eq!(rebuilt.bottom_level_indices(), vqs.bottom_level_indices());
+    }
+
+    #[test]
+    fn test_recipe_errors() {
+        let typo = HSM_RECIPE.replace("dz_surface_target", "dz_surface_targett");
+        assert!(serde_saphyr::from_str::<VQSRecipe>(&typo).is_err());
+
+        let hgrid = sloping_hgrid(1.);
+        let recipe = VQSRecipe {
+            stretching: StretchingRecipe::Quadratic(Default::default()),
+            master_grids: serde_json::from_str(
+                r#"{"mode": "kmeans", "clusters": 3, "strategy": "max_thickness"}"#,
+            )
+            .unwrap(),
+            dz_bottom_min: None,
+            max_level_jump: None,
+            dz_surface_target: None,
+            dz_max: None,
+            regions: None,
+            blending_width: None,
+        };
+        assert!(matches!(
+            recipe.master_grids,
+            MasterGridsRecipe::Kmeans {
+                initial_depth,
+                shallow_levels: 2,
+                ..
+            } if initial_depth == 1.
+        ));
+        assert!(matches!(
+            recipe.build(&hgrid),
+            Err(RecipeError::MissingDzMax)
+        ));
+    }
+
+    #[test]
+    fn test_sz_recipe() {
+        let hgrid = sloping_hgrid(1.);
+        let recipe: SZRecipe =
+            serde_json::from_str(r#"{"slevels": 10, "zlevels": [-200.0, -150.0]}"#).unwrap();
+        assert_eq!(recipe.critical_depth, 5.);
+        let sz = recipe.build(&hgrid).unwrap();
+        assert_eq!(sz.kz(), 2);
+        assert_eq!(sz.nvrt(), 11);
+
+        let barotropic = SZRecipe::barotropic().build(&hgrid).unwrap();
+        assert!(barotropic.is_barotropic());
+        assert_eq!(barotropic.to_string(), SZ::barotropic().to_string());
+    }
+}
@@ -0,0 +1,441 @@
+use crate::vertical_grid::{VerticalGrid, ZcorError, DEFAULT_H0};
+use ndarray::{Array1, Array2, ArrayView1};
+use schismrs_hgrid::hgrid::Hgrid;
+use thiserror::Error;
+
+/// How values are continued above the top and below the bottom of the source
+/// column. Both grids share the bottom and the surface, so this only matters
+/// where rounding or dry nodes make the target column stick out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Extrapolation {
+    /// Hold the value at the outermost source level.
+    #[default]
+    Constant,
+    /// Continue the gradient of the outermost source layer.
+    Linear,
+}
+
+/// Remaps 3D fields from the levels of one vertical grid to those of another
+/// on the same hgrid.
+///
+/// Fields are laid out like [`VerticalGrid::zcor`], one column per node and
+/// the bottom-most level first. Entries below the bottom are ignored on input
+/// and NaN on output.
+pub struct VerticalRemap {
+    source_zcor: Array2<f64>,
+    target_zcor: Array2<f64>,
+    extrapolation: Extrapolation,
+}
+
+impl VerticalRemap {
+    pub fn source_zcor(&self) -> &Array2<f64> {
+        &self.source_zcor
+    }
+
+    pub fn target_zcor(&self) -> &Array2<f64> {
+        &self.target_zcor
+    }
+
+    /// Linear interpolation of values defined on the source levels
+    /// (source nvrt × np) to the target levels (target nvrt × np).
+    ///
+    /// Linear profiles are reproduced exactly.
+    pub fn linear(&self, values: &Array2<f64>) -> Result<Array2<f64>, RemapError> {
+        self.remap(values, 0, |zs, vs, zt| {
+            zt.iter()
+                .map(|&z| interpolate(zs, vs, z, self.extrapolation))
+                .collect()
+        })
+    }
+
+    /// Conservative remapping of layer averages. Layer `k` lies between
+    /// levels `k` and `k + 1`, so the input is (source nvrt - 1) × np and the
+    /// output (target nvrt - 1) × np.
+    ///
+    /// Each source layer is reconstructed as a line through its average, with
+    /// a monotonized central slope from the neighbouring layers (one-sided in
+    /// the bottom and surface layers), and integrated over the target layers.
+    /// The depth integral of every column is preserved, and linear profiles
+    /// are reproduced exactly wherever the source column has more than one
+    /// layer. A single layer is spread evenly over the target column.
+    pub fn conservative(&self, values: &Array2<f64>) -> Result<Array2<f64>, RemapError> {
+        self.remap(values, 1, |zs, vs, zt| {
+            let reconstruction = Reconstruction::new(zs, vs, self.extrapolation);
+            zt.windows(2)
+                .map(|pair| reconstruction.average(pair[0], pair[1]))
+                .collect()
+        })
+    }
+
+    /// Applies `column` to the wet part of every node. `layers` is 0 for
+    /// level values and 1 for layer values.
+    fn remap<F>(
+        &self,
+        values: &Array2<f64>,
+        layers: usize,
+        column: F,
+    ) -> Result<Array2<f64>, RemapError>
+    where
+        F: Fn(&[f64], &[f64], &[f64]) -> Vec<f64>,
+    {
+        let (source_nvrt, np) = self.source_zcor.dim();
+        let expected = (source_nvrt - layers, np);
+        if values.dim() != expected {
+            return Err(RemapError::ShapeMismatch(expected, values.dim()));
+        }
+        let target_nvrt = self.target_zcor.nrows();
+        let mut remapped = Array2::from_elem((target_nvrt - layers, np), f64::NAN);
+        for node in 0..np {
+            let (source_bottom, zs) = wet_levels(self.source_zcor.column(node));
+            let (target_bottom, zt) = wet_levels(self.target_zcor.column(node));
+            if zs.len() <= layers || zt.len() <= layers {
+                continue;
+            }
+            let vs: Vec<f64> = (source_bottom..source_bottom + zs.len() - layers)
+                .map(|k| values[[k, node]])
+                .collect();
+            if let Some(k) = vs.iter().position(|value| value.is_nan()) {
+                return Err(RemapError::MissingValue(node, source_bottom + k + 1));
+            }
+            for (k, value) in column(&zs, &vs, &zt).into_iter().enumerate() {
+                remapped[[target_bottom + k, node]] = value;
+            }
+        }
+        Ok(remapped)
+    }
+}
+
+/// 0-based index of the bottom level and the elevations of the levels from
+/// there up.
+fn wet_levels(zcor: ArrayView1<f64>) -> (usize, Vec<f64>) {
+    let bottom = zcor.iter().position(|z| !z.is_nan()).unwrap_or(zcor.len());
+    (bottom, zcor.iter().skip(bottom).cloned().collect())
+}
+
+fn interpolate(zs: &[f64], vs: &[f64], z: f64, extrapolation: Extrapolation) -> f64 {
+    let n = zs.len();
+    let thick: Vec<usize> = (0..n - 1).filter(|&k| zs[k + 1] > zs[k]).collect();
+    let (Some(&first), Some(&last)) = (thick.first(), thick.last()) else {
+        // a dry column has all its levels on the bottom
+        return vs[n - 1];
+    };
+    let along = |k: usize| vs[k] + (z - zs[k]) * (vs[k + 1] - vs[k]) / (zs[k + 1] - zs[k]);
+    if z <= zs[0] {
+        return match extrapolation {
+            Extrapolation::Constant => vs[0],
+            Extrapolation::Linear => along(first),
+        };
+    }
+    if z >= zs[n - 1] {
+        return match extrapolation {
+            Extrapolation::Constant => vs[n - 1],
+            Extrapolation::Linear => along(last),
+        };
+    }
+    // zs[k] <= z < zs[k + 1], so the layer has a positive thickness
+    along(zs.partition_point(|&level| level <= z) - 1)
+}
+
+/// Piecewise linear profile with the given layer averages.
+struct Reconstruction {
+    bottoms: Vec<f64>,
+    tops: Vec<f64>,
+    means: Vec<f64>,
+    slopes: Vec<f64>,
+    extrapolation: Extrapolation,
+}
+
+impl Reconstruction {
+    fn new(zs: &[f64], means: &[f64], extrapolation: Extrapolation) -> Self {
+        let thick: Vec<usize> = (0..means.len()).filter(|&k| zs[k + 1] > zs[k]).collect();
+        if thick.is_empty() {
+            // dry column: a constant profile through the surface layer
+            let z = zs[zs.len() - 1];
+            return Self {
+                bottoms: vec![z],
+                tops: vec![z],
+                means: vec![means[means.len() - 1]],
+                slopes: vec![0.],
+                extrapolation: Extrapolation::Constant,
+            };
+        }
+        let bottoms: Vec<f64> = thick.iter().map(|&k| zs[k]).collect();
+        let tops: Vec<f64> = thick.iter().map(|&k| zs[k + 1]).collect();
+        let means: Vec<f64> = thick.iter().map(|&k| means[k]).collect();
+        let centers: Vec<f64> = bottoms
+            .iter()
+            .zip(&tops)
+            .map(|(bottom, top)| 0.5 * (bottom + top))
+            .collect();
+        let gradient = |i: usize, j: usize| (means[j] - means[i]) / (centers[j] - centers[i]);
+        let n = means.len();
+        let slopes = (0..n)
+            .map(|i| match (i, n) {
+                (_, 1) => 0.,
+                (0, _) => gradient(0, 1),
+                (i, n) if i == n - 1 => gradient(n - 2, n - 1),
+                (i, _) => {
+                    let (below, above) = (gradient(i - 1, i), gradient(i, i + 1));
+                    if below * above <= 0. {
+                        return 0.;
+                    }
+                    let central = gradient(i - 1, i + 1);
+                    central.signum() * central.abs().min(2. * below.abs()).min(2. * above.abs())
+                }
+            })
+            .collect();
+        Self {
+            bottoms,
+            tops,
+            means,
+            slopes,
+            extrapolation,
+        }
+    }
+
+    fn line(&self, i: usize, z: f64) -> f64 {
+        self.means[i] + self.slopes[i] * (z - 0.5 * (self.bottoms[i] + self.tops[i]))
+    }
+
+    /// Integral of the profile between `a` and `b`, with `a <= b`.
+    fn integral(&self, a: f64, b: f64) -> f64 {
+        let n = self.means.len();
+        let (bottom, top) = (self.bottoms[0], self.tops[n - 1]);
+        let mut integral = 0.;
+        let mut beyond = |i: usize, edge: f64, lo: f64, hi: f64| {
+            if hi > lo {
+                let z = match self.extrapolation {
+                    Extrapolation::Constant => edge,
+                    Extrapolation::Linear => 0.5 * (lo + hi),
+                };
+                integral += (hi - lo) * self.line(i, z);
+            }
+        };
+        beyond(0, bottom, a, b.min(bottom));
+        beyond(n - 1, top, a.max(top), b);
+        for i in 0..n {
+            let (lo, hi) = (a.max(self.bottoms[i]), b.min(self.tops[i]));
+            if hi > lo {
+                integral += (hi - lo) * self.line(i, 0.5 * (lo + hi));
+            }
+        }
+        integral
+    }
+
+    fn value(&self, z: f64) -> f64 {
+        let n = self.means.len();
+        if z < self.bottoms[0] {
+            return match self.extrapolation {
+                Extrapolation::Constant => self.line(0, self.bottoms[0]),
+                Extrapolation::Linear => self.line(0, z),
+            };
+        }
+        if z > self.tops[n - 1] {
+            return match self.extrapolation {
+                Extrapolation::Constant => self.line(n - 1, self.tops[n - 1]),
+                Extrapolation::Linear => self.line(n - 1, z),
+            };
+        }
+        let i = self.tops.partition_point(|&top| top < z).min(n - 1);
+        self.line(i, z)
+    }
+
+    /// Average between `a` and `b`, or the value at `a` for an empty layer.
+    fn average(&self, a: f64, b: f64) -> f64 {
+        if b > a {
+            self.integral(a, b) / (b - a)
+        } else {
+            self.value(a)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct VerticalRemapBuilder<'a> {
+    hgrid: Option<&'a Hgrid>,
+    source: Option<&'a dyn VerticalGrid>,
+    target: Option<&'a dyn VerticalGrid>,
+    eta: Option<&'a Array1<f64>>,
+    h0: Option<&'a f64>,
+    extrapolation: Option<&'a Extrapolation>,
+}
+
+impl<'a> VerticalRemapBuilder<'a> {
+    pub fn build(&self) -> Result<VerticalRemap, RemapError> {
+        let hgrid = self
+            .hgrid
+            .ok_or_else(|| RemapError::UninitializedFieldError("hgrid".to_string()))?;
+        let source = self
+            .source
+            .ok_or_else(|| RemapError::UninitializedFieldError("source".to_string()))?;
+        let target = self
+            .target
+            .ok_or_else(|| RemapError::UninitializedFieldError("target".to_string()))?;
+        let h0 = self.h0.copied().unwrap_or(DEFAULT_H0);
+        Ok(VerticalRemap {
+            source_zcor: source.zcor_with_h0(hgrid, self.eta, h0)?,
+            target_zcor: target.zcor_with_h0(hgrid, self.eta, h0)?,
+            extrapolation: self.extrapolation.copied().unwrap_or_default(),
+        })
+    }
+
+    pub fn hgrid(&mut self, hgrid: &'a Hgrid) -> &mut Self {
+        self.hgrid = Some(hgrid);
+        self
+    }
+
+    pub fn source(&mut self, source: &'a dyn VerticalGrid) -> &mut Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn target(&mut self, target: &'a dyn VerticalGrid) -> &mut Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn eta(&mut self, eta: &'a Array1<f64>) -> &mut Self {
+        self.eta = Some(eta);
+        self
+    }
+
+    pub fn h0(&mut self, h0: &'a f64) -> &mut Self {
+        self.h0 = Some(h0);
+        self
+    }
+
+    pub fn extrapolation(&mut self, extrapolation: &'a Extrapolation) -> &mut Self {
+        self.extrapolation = Some(extrapolation);
+        self
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RemapError {
+    #[error("Unitialized field on VerticalRemapBuilder: {0}")]
+    UninitializedFieldError(String),
+    #[error(transparent)]
+    ZcorError(#[from] ZcorError),
+    #[error("Expected values of shape {0:?}, got {1:?}")]
+    ShapeMismatch((usize, usize), (usize, usize)),
+    #[error("Missing value at node index {0}, level {1}")]
+    MissingValue(usize, usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interpolate, Extrapolation, Reconstruction, VerticalRemapBuilder};
+    use crate::sz::SZBuilder;
+    use crate::test_support::sloping_hgrid;
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vqs::VQSBuilder;
+    use ndarray::Array2;
+
+    #[test]
+    fn test_remap_vqs_to_sz() {
+        let hgrid = sloping_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&vec![5., 20., 60., 110.])
+            .nlevels(&vec![3, 6, 10, 14])
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+        let sz = SZBuilder::default()
+            .hgrid(&hgrid)
+            .slevels(&8)
+            .zlevels(&vec![-200., -60.])
+            .theta_f(&3.)
+            .theta_b(&0.5)
+            .critical_depth(&5.)
+            .etal(&0.)
+            .build()
+            .unwrap();
+        let remap = VerticalRemapBuilder::default()
+            .hgrid(&hgrid)
+            .source(&vqs)
+            .target(&sz)
+            .build()
+            .unwrap();
+
+        // linear profiles are reproduced exactly on levels and on layers
+        let profile = |z: f64| 12. - 0.05 * z;
+        let levels = remap.source_zcor().mapv(profile);
+        let remapped = remap.linear(&levels).unwrap();
+        let target = remap.target_zcor();
+        for (value, z) in remapped.iter().zip(target.iter()) {
+            assert_eq!(value.is_nan(), z.is_nan());
+            if !z.is_nan() {
+                assert!((value - profile(*z)).abs() < 1e-9);
+            }
+        }
+        let layer_means = |zcor: &Array2<f64>| {
+            Array2::from_shape_fn((zcor.nrows() - 1, zcor.ncols()), |(k, node)| {
+                profile(0.5 * (zcor[[k, node]] + zcor[[k + 1, node]]))
+            })
+        };
+        let remapped = remap
+            .conservative(&layer_means(remap.source_zcor()))
+            .unwrap();
+        let expected = layer_means(target);
+        let source = remap.source_zcor();
+        for node in 0..source.ncols() {
+            // a single source layer only has its average to go by
+            let source_layers = source.column(node).iter().filter(|z| !z.is_nan()).count() - 1;
+            for k in 0..expected.nrows() {
+                let (value, expected) = (remapped[[k, node]], expected[[k, node]]);
+                assert_eq!(value.is_nan(), expected.is_nan());
+                if !expected.is_nan() && source_layers > 1 {
+                    assert!((value - expected).abs() < 1e-9);
+                }
+            }
+        }
+
+        // the depth integral of any profile is preserved
+        let curved = Array2::from_shape_fn((source.nrows() - 1, source.ncols()), |(k, node)| {
+            (0.3 * source[[k, node]]).sin()
+        });
+        let remapped = remap.conservative(&curved).unwrap();
+        let integral = |means: &Array2<f64>, zcor: &Array2<f64>, node: usize| {
+            (0..means.nrows())
+                .filter(|&k| !means[[k, node]].is_nan())
+                .map(|k| means[[k, node]] * (zcor[[k + 1, node]] - zcor[[k, node]]))
+                .sum::<f64>()
+        };
+        for node in 0..source.ncols() {
+            let before = integral(&curved, source, node);
+            assert!((integral(&remapped, target, node) - before).abs() < 1e-9);
+        }
+
+        assert!(remap.linear(&curved).is_err());
+    }
+
+    #[test]
+    fn test_extrapolation() {
+        let zs = [-10., -4., 0.];
+        let vs = [1., 4., 6.];
+        let constant = Extrapolation::Constant;
+        let linear = Extrapolation::Linear;
+        assert_eq!(interpolate(&zs, &vs, -7., constant), 2.5);
+        assert_eq!(interpolate(&zs, &vs, -12., constant), 1.);
+        assert_eq!(interpolate(&zs, &vs, -12., linear), 0.);
+        assert_eq!(interpolate(&zs, &vs, 2., constant), 6.);
+        assert_eq!(interpolate(&zs, &vs, 2., linear), 7.);
+        // dry column
+        assert_eq!(interpolate(&[-1., -1.], &[3., 5.], 0., linear), 5.);
+
+        let means = [1., 3.];
+        let reconstruction = Reconstruction::new(&[-4., -2., 0.], &means, constant);
+        assert_eq!(reconstruction.average(-4., 0.), 2.);
+        assert_eq!(reconstruction.average(-6., -4.), 0.);
+        let reconstruction = Reconstruction::new(&[-4., -2., 0.], &means, linear);
+        assert_eq!(reconstruction.average(-6., -4.), -1.);
+        assert_eq!(reconstruction.average(0., 0.), 4.);
+    }
+}
@@ -0,0 +1,436 @@
+use crate::vertical_grid::{
+    load_vertical_grid, VerticalGrid, VerticalGridLoadError, ZcorError, DEFAULT_H0,
+};
+use schismrs_hgrid::hgrid::Hgrid;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Upper bounds of the depth bands used by [`VgridSummary::compute`], in
+/// metres. The last band is open ended.
+pub const DEFAULT_DEPTH_BANDS: [f64; 9] = [5., 20., 50., 100., 200., 500., 1000., 2000., 5000.];
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ThicknessStats {
+    pub count: usize,
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl ThicknessStats {
+    fn from_values(values: impl Iterator<Item = f64>) -> Self {
+        let (mut count, mut min, mut max, mut sum) = (0, f64::INFINITY, f64::NEG_INFINITY, 0.);
+        for value in values {
+            count += 1;
+            min = min.min(value);
+            max = max.max(value);
+            sum += value;
+        }
+        if count == 0 {
+            return Self {
+                count,
+                min: f64::NAN,
+                mean: f64::NAN,
+                max: f64::NAN,
+            };
+        }
+        Self {
+            count,
+            min,
+            mean: sum / count as f64,
+            max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LevelCount {
+    pub levels: usize,
+    pub nodes: usize,
+}
+
+/// Layer thickness of the wet nodes with `min_depth < depth <= max_depth`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DepthBandThickness {
+    pub min_depth: f64,
+    /// `None` for the open ended deepest band.
+    pub max_depth: Option<f64>,
+    pub nodes: usize,
+    pub thickness: ThicknessStats,
+}
+
+/// Cost of a grid relative to a reference vgrid.in, see
+/// [`VgridSummary::relative_cost`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceCost {
+    pub vgrid: PathBuf,
+    pub relative_cost: f64,
+}
+
+/// Size of the 3D problem set up by a vertical grid on an hgrid, and the
+/// distribution of its layer thicknesses, at eta = 0.
+///
+/// As in SCHISM, an element has prisms from the deepest bottom level of its
+/// nodes up, and a side has levels from the deepest bottom level of its two
+/// nodes up. Nodes with a depth of at most `h0` are dry and left out of the
+/// thickness statistics.
+#[derive(Debug, Clone, Serialize)]
+pub struct VgridSummary {
+    pub ivcor: usize,
+    pub nvrt: usize,
+    pub nodes_2d: usize,
+    pub elements_2d: usize,
+    pub sides_2d: usize,
+    pub wet_nodes: usize,
+    pub nodes_3d: usize,
+    pub prisms: usize,
+    pub sides_3d: usize,
+    pub levels_histogram: Vec<LevelCount>,
+    pub thickness_by_depth: Vec<DepthBandThickness>,
+    pub surface_thickness: ThicknessStats,
+    pub bottom_thickness: ThicknessStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<ReferenceCost>,
+}
+
+impl VgridSummary {
+    pub fn compute(hgrid: &Hgrid, vgrid: &dyn VerticalGrid) -> Result<Self, VgridSummaryError> {
+        Self::compute_with_depth_bands(hgrid, vgrid, &DEFAULT_DEPTH_BANDS)
+    }
+
+    /// Like [`VgridSummary::compute`] with custom, increasing upper bounds
+    /// for the depth bands.
+    pub fn compute_with_depth_bands(
+        hgrid: &Hgrid,
+        vgrid: &dyn VerticalGrid,
+        depth_bands: &[f64],
+    ) -> Result<Self, VgridSummaryError> {
+        if !depth_bands.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(VgridSummaryError::InvalidDepthBands);
+        }
+        let zcor = vgrid.zcor(hgrid, None)?;
+        let nvrt = vgrid.nvrt();
+        // hgrid depths are stored negative below the datum
+        let depths = hgrid.depths().mapv(|depth| -depth);
+        let np = depths.len();
+
+        let bottoms: Vec<usize> = depths
+            .iter()
+            .enumerate()
+            .map(|(node, &depth)| vgrid.bottom_level_index(node, depth))
+            .collect();
+        let nodes_3d = bottoms.iter().map(|kbp| nvrt + 1 - kbp).sum();
+        let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+        for kbp in &bottoms {
+            *histogram.entry(nvrt + 1 - kbp).or_insert(0) += 1;
+        }
+
+        let index: HashMap<u32, usize> = hgrid
+            .nodes()
+            .hash_map()
+            .keys()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+        let mut prisms = 0;
+        let mut sides = BTreeSet::new();
+        for node_ids in hgrid.elements().hash_map().values() {
+            let vertices: Vec<usize> = node_ids.iter().map(|id| index[id]).collect();
+            let kbe = vertices.iter().map(|&i| bottoms[i]).max().unwrap_or(nvrt);
+            prisms += nvrt - kbe;
+            for e in 0..vertices.len() {
+                let (i, j) = (vertices[e], vertices[(e + 1) % vertices.len()]);
+                sides.insert((i.min(j), i.max(j)));
+            }
+        }
+        let sides_3d = sides
+            .iter()
+            .map(|&(i, j)| nvrt + 1 - bottoms[i].max(bottoms[j]))
+            .sum();
+
+        let mut band_thickness: Vec<Vec<f64>> = vec![Vec::new(); depth_bands.len() + 1];
+        let mut band_nodes = vec![0; depth_bands.len() + 1];
+        let mut surface = Vec::new();
+        let mut bottom = Vec::new();
+        let mut wet_nodes = 0;
+        for node in 0..np {
+            let depth = depths[node];
+            if depth <= DEFAULT_H0 {
+                continue;
+            }
+            wet_nodes += 1;
+            let band = depth_bands.partition_point(|&upper| upper < depth);
+            band_nodes[band] += 1;
+            let column = zcor.column(node);
+            let levels: Vec<f64> = column.iter().cloned().filter(|z| !z.is_nan()).collect();
+            let dz: Vec<f64> = levels.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            if let (Some(&first), Some(&last)) = (dz.first(), dz.last()) {
+                bottom.push(first);
+                surface.push(last);
+            }
+            band_thickness[band].extend(dz);
+        }
+        let thickness_by_depth = band_thickness
+            .into_iter()
+            .zip(band_nodes)
+            .enumerate()
+            .map(|(band, (thickness, nodes))| DepthBandThickness {
+                min_depth: if band == 0 { 0. } else { depth_bands[band - 1] },
+                max_depth: depth_bands.get(band).copied(),
+                nodes,
+                thickness: ThicknessStats::from_values(thickness.into_iter()),
+            })
+            .collect();
+
+        Ok(Self {
+            ivcor: vgrid.ivcor(),
+            nvrt,
+            nodes_2d: np,
+            elements_2d: hgrid.elements().hash_map().len(),
+            sides_2d: sides.len(),
+            wet_nodes,
+            nodes_3d,
+            prisms,
+            sides_3d,
+            levels_histogram: histogram
+                .into_iter()
+                .map(|(levels, nodes)| LevelCount { levels, nodes })
+                .collect(),
+            thickness_by_depth,
+            surface_thickness: ThicknessStats::from_values(surface.into_iter()),
+            bottom_thickness: ThicknessStats::from_values(bottom.into_iter()),
+            reference: None,
+        })
+    }
+
+    /// Like [`VgridSummary::compute`], also recording the cost relative to
+    /// the vgrid.in at `reference_vgrid` when given.
+    pub fn compute_with_reference(
+        hgrid: &Hgrid,
+        vgrid: &dyn VerticalGrid,
+        reference_vgrid: Option<&Path>,
+    ) -> Result<Self, VgridSummaryError> {
+        let mut summary = Self::compute(hgrid, vgrid)?;
+        if let Some(path) = reference_vgrid {
+            let reference = load_vertical_grid(hgrid, path)?;
+            let reference = Self::compute(hgrid, reference.as_ref())?;
+            summary.reference = Some(ReferenceCost {
+                vgrid: path.to_path_buf(),
+                relative_cost: summary.relative_cost(&reference),
+            });
+        }
+        Ok(summary)
+    }
+
+    /// Rough compute cost relative to `reference`. The work of a SCHISM time
+    /// step scales with the number of prisms and 3D sides, so this is the
+    /// ratio of their sums.
+    pub fn relative_cost(&self, reference: &VgridSummary) -> f64 {
+        (self.prisms + self.sides_3d) as f64 / (reference.prisms + reference.sides_3d) as f64
+    }
+
+    pub fn to_json(&self) -> Result<String, VgridSummaryError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<(), VgridSummaryError> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ThicknessStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>8} {:>10.3} {:>10.3} {:>10.3}",
+            self.count, self.min, self.mean, self.max
+        )
+    }
+}
+
+impl fmt::Display for VgridSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ivcor: {}, nvrt: {}", self.ivcor, self.nvrt)?;
+        writeln!(
+            f,
+            "2D: {} nodes ({} wet), {} elements, {} sides",
+            self.nodes_2d, self.wet_nodes, self.elements_2d, self.sides_2d
+        )?;
+        writeln!(
+            f,
+            "3D: {} nodes, {} prisms, {} sides",
+            self.nodes_3d, self.prisms, self.sides_3d
+        )?;
+        writeln!(f, "\n{:>8} {:>10}", "levels", "nodes")?;
+        for count in &self.levels_histogram {
+            writeln!(f, "{:>8} {:>10}", count.levels, count.nodes)?;
+        }
+        writeln!(
+            f,
+            "\n{:>21} {:>8} {:>8} {:>10} {:>10} {:>10}",
+            "layer thickness (m)", "nodes", "layers", "min", "mean", "max"
+        )?;
+        for band in &self.thickness_by_depth {
+            let range = match band.max_depth {
+                Some(max_depth) => format!("{} - {}", band.min_depth, max_depth),
+                None => format!("> {}", band.min_depth),
+            };
+            writeln!(f, "{:>21} {:>8} {}", range, band.nodes, band.thickness)?;
+        }
+        writeln!(
+            f,
+            "{:>21} {:>8} {}",
+            "surface layer", "", self.surface_thickness
+        )?;
+        writeln!(
+            f,
+            "{:>21} {:>8} {}",
+            "bottom layer", "", self.bottom_thickness
+        )?;
+        if let Some(reference) = &self.reference {
+            writeln!(
+                f,
+                "Relative cost vs {}: {:.3}",
+                reference.vgrid.display(),
+                reference.relative_cost
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VgridSummaryError {
+    #[error("depth bands must be strictly increasing")]
+    InvalidDepthBands,
+    #[error(transparent)]
+    ZcorError(#[from] ZcorError),
+    #[error(transparent)]
+    VerticalGridLoadError(#[from] VerticalGridLoadError),
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VgridSummary;
+    use crate::sz::SZBuilder;
+    use crate::test_support::sloping_hgrid;
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vertical_grid::VerticalGrid;
+    use crate::vqs::VQSBuilder;
+    use std::collections::{BTreeSet, HashMap};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_sigma_grid_summary() {
+        let hgrid = sloping_hgrid(1.);
+        let sz = SZBuilder::default()
+            .hgrid(&hgrid)
+            .slevels(&7)
+            .theta_f(&0.1)
+            .theta_b(&0.)
+            .critical_depth(&5.)
+            .etal(&0.)
+            .build()
+            .unwrap();
+        let summary = VgridSummary::compute(&hgrid, &sz).unwrap();
+        let (np, ne) = (hgrid.nodes().len(), hgrid.elements().hash_map().len());
+        // pure sigma: every node has every level
+        assert_eq!(summary.nvrt, 7);
+        assert_eq!(summary.nodes_3d, 7 * np);
+        assert_eq!(summary.prisms, 6 * ne);
+        assert_eq!(summary.sides_3d, 7 * summary.sides_2d);
+        assert_eq!(summary.levels_histogram.len(), 1);
+        assert_eq!(summary.levels_histogram[0].nodes, np);
+
+        // the layers of a sigma column split its depth in 6
+        let band_depths: Vec<f64> = hgrid
+            .depths()
+            .iter()
+            .map(|depth| -depth)
+            .filter(|&depth| depth > 20. && depth <= 50.)
+            .collect();
+        let band = &summary.thickness_by_depth[2];
+        assert_eq!((band.min_depth, band.max_depth), (20., Some(50.)));
+        assert_eq!(band.nodes, band_depths.len());
+        assert_eq!(band.thickness.count, 6 * band_depths.len());
+        let mean_depth = band_depths.iter().sum::<f64>() / band_depths.len() as f64;
+        assert!((band.thickness.mean - mean_depth / 6.).abs() < 1e-9);
+        assert_eq!(summary.wet_nodes, np);
+        assert_eq!(summary.bottom_thickness.count, np);
+        assert!(summary.thickness_by_depth[5].thickness.mean.is_nan());
+
+        assert!((summary.relative_cost(&summary) - 1.).abs() < 1e-12);
+        let json: serde_json::Value = serde_json::from_str(&summary.to_json().unwrap()).unwrap();
+        assert_eq!(json["prisms"], 6 * ne);
+        assert!(json["thickness_by_depth"][9]["max_depth"].is_null());
+        assert!(json.get("reference").is_none());
+    }
+
+    #[test]
+    fn test_vqs_summary() {
+        let hgrid = sloping_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&vec![5., 20., 60., 110.])
+            .nlevels(&vec![3, 6, 10, 14])
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+        let summary = VgridSummary::compute(&hgrid, &vqs).unwrap();
+        assert!(summary.levels_histogram.len() > 1);
+
+        // level counts of the nodes, from the levels above their bottom
+        let zcor = vqs.zcor(&hgrid, None).unwrap();
+        let levels: Vec<usize> = zcor
+            .columns()
+            .into_iter()
+            .map(|column| column.iter().filter(|z| !z.is_nan()).count())
+            .collect();
+        assert_eq!(summary.nodes_3d, levels.iter().sum::<usize>());
+        let index: HashMap<u32, usize> = hgrid
+            .nodes()
+            .hash_map()
+            .keys()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+        // an element has a prism for every layer all its nodes have, a side
+        // every level both its nodes have
+        let mut prisms = 0;
+        let mut sides = BTreeSet::new();
+        for node_ids in hgrid.elements().hash_map().values() {
+            let nodes: Vec<usize> = node_ids.iter().map(|id| index[id]).collect();
+            prisms += nodes.iter().map(|&i| levels[i]).min().unwrap() - 1;
+            for (e, &i) in nodes.iter().enumerate() {
+                let j = nodes[(e + 1) % nodes.len()];
+                sides.insert((i.min(j), i.max(j)));
+            }
+        }
+        let sides_3d: usize = sides.iter().map(|&(i, j)| levels[i].min(levels[j])).sum();
+        assert_eq!(summary.prisms, prisms);
+        assert_eq!(summary.sides_3d, sides_3d);
+        assert!(summary.prisms < (vqs.nvrt() - 1) * hgrid.elements().hash_map().len());
+
+        let file = NamedTempFile::new().unwrap();
+        vqs.write_to_file(&file.path().to_path_buf()).unwrap();
+        let summary =
+            VgridSummary::compute_with_reference(&hgrid, &vqs, Some(file.path())).unwrap();
+        let reference = summary.reference.as_ref().unwrap();
+        assert!((reference.relative_cost - 1.).abs() < 1e-12);
+        assert!(summary.to_string().contains("Relative cost vs"));
+    }
+}
@@ -1,19 +1,28 @@
+use crate::vertical_grid::VerticalGrid;
 use libm::sinh;
 use libm::tanh;
 use ndarray::Array;
-use ndarray::Array1;
+use ndarray::{Array1, Array2};
 use ndarray_stats::QuantileExt;
+#[cfg(feature = "plot")]
 use plotly::color::NamedColor;
+#[cfg(feature = "plot")]
 use plotly::common::{Line, Marker, Mode};
+#[cfg(feature = "plot")]
 use plotly::{Plot, Scatter};
 use schismrs_hgrid::Hgrid;
 use std::f64::NAN;
 use std::fmt;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use thiserror::Error;
 
+/// Transition depth of [`SZ::barotropic`], deeper than any ocean so that
+/// every node is in the S region.
+pub const BAROTROPIC_H_S: f64 = 1.0e6;
+
 pub struct SZ {
     sigma: Array1<f64>,
     z_array: Array1<f64>,
@@ -24,6 +33,132 @@ pub struct SZ {
 }
 
 impl SZ {
+    /// Vertical grid of a 2D depth-averaged run: a single layer from the
+    /// bottom to the surface (nvrt = 2). The stretching parameters have no
+    /// effect with only two levels and are set to the values of the SCHISM
+    /// 2D examples.
+    pub fn barotropic() -> Self {
+        SZ {
+            sigma: Array1::from_vec(vec![-1., 0.]),
+            z_array: Array1::from_vec(vec![-BAROTROPIC_H_S]),
+            theta_f: 1.0e-4,
+            theta_b: 1.,
+            hc: 40.,
+            etal: 0.,
+        }
+    }
+    /// Whether this is a single layer grid for a 2D run.
+    pub fn is_barotropic(&self) -> bool {
+        self.nvrt() == 2
+    }
+    /// Load an existing ivcor=2 vgrid.in file.
+    ///
+    /// Text after `!` on a line is treated as a comment. The free surface
+    /// elevation is not stored in the file, so `etal` is set to 0.
+    pub fn try_from_file(path: &Path) -> Result<Self, SZLoadError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    fn parse(contents: &str) -> Result<Self, SZLoadError> {
+        let mut records = Records::new(contents);
+
+        let (line, tokens) = records.next("ivcor")?;
+        let ivcor: i32 = parse_token(&tokens, 0, line, "ivcor")?;
+        if ivcor != 2 {
+            return Err(SZLoadError::UnsupportedIvcor(ivcor));
+        }
+
+        let (line, tokens) = records.next("nvrt, kz and h_s")?;
+        let nvrt: usize = parse_token(&tokens, 0, line, "nvrt")?;
+        let kz: usize = parse_token(&tokens, 1, line, "kz")?;
+        let h_s: f64 = parse_token(&tokens, 2, line, "h_s")?;
+        if kz < 1 {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                "kz must be >= 1".to_string(),
+            ));
+        }
+        if nvrt <= kz {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                format!("nvrt ({}) must be larger than kz ({})", nvrt, kz),
+            ));
+        }
+
+        records.label("Z levels")?;
+        let mut z_array: Array1<f64> = Array1::zeros(kz);
+        let mut line = line;
+        for k in 0..kz {
+            let tokens;
+            (line, tokens) = records.next("Z levels")?;
+            expect_index(&tokens, line, k + 1)?;
+            z_array[k] = parse_token(&tokens, 1, line, "Z level")?;
+        }
+        if !z_array.windows(2).into_iter().all(|w| w[0] < w[1]) {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                "Z levels must be strictly increasing".to_string(),
+            ));
+        }
+        if (z_array[kz - 1] + h_s).abs() > 1e-6 * h_s.abs().max(1.) {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                format!(
+                    "the last Z level ({}) must be equal to -h_s ({})",
+                    z_array[kz - 1],
+                    -h_s
+                ),
+            ));
+        }
+
+        records.label("S levels")?;
+        let (line, tokens) = records.next("h_c, theta_b and theta_f")?;
+        let hc: f64 = parse_token(&tokens, 0, line, "h_c")?;
+        let theta_b: f64 = parse_token(&tokens, 1, line, "theta_b")?;
+        let theta_f: f64 = parse_token(&tokens, 2, line, "theta_f")?;
+        SZBuilder::validate_critical_depth(&hc)?;
+        SZBuilder::validate_theta_b(&theta_b)?;
+        SZBuilder::validate_theta_f(&theta_f)?;
+        if hc >= h_s {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                format!("h_c ({}) must be smaller than h_s ({})", hc, h_s),
+            ));
+        }
+
+        let nsigma = nvrt - kz + 1;
+        let mut sigma: Array1<f64> = Array1::zeros(nsigma);
+        let mut line = line;
+        for k in 0..nsigma {
+            let tokens;
+            (line, tokens) = records.next("S levels")?;
+            expect_index(&tokens, line, kz + k)?;
+            sigma[k] = parse_token(&tokens, 1, line, "S level")?;
+        }
+        if sigma[0] != -1. || sigma[nsigma - 1] != 0. {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                "S levels must go from -1 at the bottom to 0 at the surface".to_string(),
+            ));
+        }
+        if !sigma.windows(2).into_iter().all(|w| w[0] < w[1]) {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                "S levels must be strictly increasing".to_string(),
+            ));
+        }
+
+        Ok(SZ {
+            sigma,
+            z_array,
+            theta_f,
+            theta_b,
+            hc,
+            etal: 0.,
+        })
+    }
+
     pub fn write_to_file(&self, filename: &PathBuf) -> std::io::Result<()> {
         let mut file = File::create(filename)?;
         write!(file, "{}", self)?;
@@ -35,18 +170,31 @@ impl SZ {
     pub fn nvrt(&self) -> usize {
         self.sigma.len() + self.z_array.len() - 1
     }
-    pub fn make_vertical_distribution_plot(&self, nbins: usize) -> Result<Plot, SZPlotError> {
+    /// Level elevations of the S region over `nbins` bottom elevations
+    /// evenly spaced from `-h_s` up to `-hc`.
+    ///
+    /// Returns the bottom elevations and a `nbins` x `nsigma` array whose rows
+    /// are the level elevations from the bottom up.
+    pub fn vertical_distribution(
+        &self,
+        nbins: usize,
+    ) -> Result<(Array1<f64>, Array2<f64>), SZPlotError> {
         if nbins < 2 {
             return Err(SZPlotError::InvalidNbinsValue(nbins));
         }
-        let mut plot = Plot::new();
         let xdepths = Array::linspace(self.z_array[self.z_array.len() - 1], -self.hc, nbins);
+        let mut zcor = Array2::from_elem((nbins, self.sigma.len()), NAN);
         for (i, xdepth) in xdepths.iter().enumerate() {
-            if i == xdepths.len() {
-                break;
-            }
-            let ydepths = self.compute_zcor(xdepth);
-            let trace = Scatter::new(vec![*xdepth; self.sigma.len()], ydepths.to_vec())
+            zcor.row_mut(i).assign(&self.compute_zcor(xdepth));
+        }
+        Ok((xdepths, zcor))
+    }
+    #[cfg(feature = "plot")]
+    pub fn make_vertical_distribution_plot(&self, nbins: usize) -> Result<Plot, SZPlotError> {
+        let (xdepths, zcor) = self.vertical_distribution(nbins)?;
+        let mut plot = Plot::new();
+        for (xdepth, ydepths) in xdepths.iter().zip(zcor.rows()) {
+            let trace = Scatter::new(vec![*xdepth; ydepths.len()], ydepths.to_vec())
                 .mode(Mode::LinesMarkers)
                 .line(Line::new().color(NamedColor::Blue))
                 .marker(Marker::new().color(NamedColor::Black));
@@ -54,19 +202,93 @@ impl SZ {
         }
         Ok(plot)
     }
+    pub fn kz(&self) -> usize {
+        self.z_array.len()
+    }
+    /// Transition depth between the S and Z regions, positive down.
+    pub fn h_s(&self) -> f64 {
+        -self.z_array[self.z_array.len() - 1]
+    }
+    pub fn z_levels(&self) -> &Array1<f64> {
+        &self.z_array
+    }
+    pub fn s_levels(&self) -> &Array1<f64> {
+        &self.sigma
+    }
+    pub fn theta_b(&self) -> f64 {
+        self.theta_b
+    }
+    pub fn theta_f(&self) -> f64 {
+        self.theta_f
+    }
+    pub fn critical_depth(&self) -> f64 {
+        self.hc
+    }
+    /// Song and Haidvogel (1994) stretching function C(sigma).
+    fn stretching(&self, sigma: f64) -> f64 {
+        (1. - self.theta_b) * sinh(self.theta_f * sigma) / sinh(self.theta_f)
+            + self.theta_b * (tanh(self.theta_f * (sigma + 0.5)) - tanh(self.theta_f * 0.5))
+                / (2. * tanh(self.theta_f * 0.5))
+    }
     fn compute_zcor(&self, bottom: &f64) -> Array1<f64> {
         let mut zcor = Array1::from_elem(self.sigma.len(), NAN);
         let hc = -self.hc;
         for (i, sigma) in self.sigma.iter().enumerate() {
-            let cs = (1. - self.theta_b) * sinh(self.theta_f * sigma) / sinh(self.theta_f)
-                + self.theta_b * (tanh(self.theta_f * (sigma + 0.5)) - tanh(self.theta_f * 0.5))
-                    / (2. * tanh(self.theta_f * 0.5));
+            let cs = self.stretching(*sigma);
             zcor[i] = -(self.etal * (1. + sigma) + hc * sigma + (bottom - hc) * cs);
         }
         zcor
     }
 }
 
+impl VerticalGrid for SZ {
+    fn ivcor(&self) -> usize {
+        SZ::ivcor(self)
+    }
+
+    fn nvrt(&self) -> usize {
+        SZ::nvrt(self)
+    }
+
+    /// Nodes shallower than h_s start at the first S level (kz), deeper
+    /// ones at the Z level just above their depth.
+    fn bottom_level_index(&self, _node: usize, depth: f64) -> usize {
+        let kz = self.kz();
+        if depth <= self.h_s() {
+            return kz;
+        }
+        (1..kz)
+            .find(|&k| -depth >= self.z_array[k - 1] && -depth < self.z_array[k])
+            .unwrap_or(1)
+    }
+
+    fn zcor_at_node(&self, node: usize, depth: f64, eta: f64) -> Array1<f64> {
+        let kz = self.kz();
+        let h_s = self.h_s();
+        let mut zcor = Array1::from_elem(self.nvrt(), f64::NAN);
+        let hmod = depth.min(h_s);
+        for (i, &sigma) in self.sigma.iter().enumerate() {
+            zcor[kz - 1 + i] = if hmod <= self.hc {
+                sigma * (hmod + eta) + eta
+            } else {
+                eta * (1. + sigma) + self.hc * sigma + (hmod - self.hc) * self.stretching(sigma)
+            };
+        }
+        if depth > h_s {
+            let kbp = self.bottom_level_index(node, depth);
+            zcor[kbp - 1] = -depth;
+            for k in kbp + 1..kz {
+                zcor[k - 1] = self.z_array[k - 1];
+            }
+        }
+        zcor
+    }
+
+    fn write_to_file(&self, filename: &Path) -> std::io::Result<()> {
+        SZ::write_to_file(self, &filename.to_path_buf())
+    }
+}
+
 impl fmt::Display for SZ {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}\n", self.ivcor())?;
@@ -87,6 +309,70 @@ impl fmt::Display for SZ {
     }
 }
 
+/// Non-empty lines of a vgrid.in file with comments removed, numbered from 1.
+struct Records<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+}
+
+impl<'a> Records<'a> {
+    fn new(contents: &'a str) -> Self {
+        Self {
+            lines: contents.lines().enumerate(),
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(usize, Vec<&'a str>), SZLoadError> {
+        for (index, line) in self.lines.by_ref() {
+            let data = line.split('!').next().unwrap_or_default();
+            let tokens: Vec<&str> = data.split_whitespace().collect();
+            if !tokens.is_empty() {
+                return Ok((index + 1, tokens));
+            }
+        }
+        Err(SZLoadError::UnexpectedEof(expected.to_string()))
+    }
+
+    fn label(&mut self, expected: &str) -> Result<(), SZLoadError> {
+        let (line, tokens) = self.next(expected)?;
+        if tokens[0].parse::<f64>().is_ok() {
+            return Err(SZLoadError::InvalidFormat(
+                line,
+                format!(
+                    "expected the '{}' label, got '{}'",
+                    expected,
+                    tokens.join(" ")
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_token<T: FromStr>(
+    tokens: &[&str],
+    index: usize,
+    line: usize,
+    name: &str,
+) -> Result<T, SZLoadError> {
+    let token = tokens
+        .get(index)
+        .ok_or_else(|| SZLoadError::ParseError(line, format!("missing {}", name)))?;
+    token
+        .parse()
+        .map_err(|_| SZLoadError::ParseError(line, format!("invalid {}: '{}'", name, token)))
+}
+
+fn expect_index(tokens: &[&str], line: usize, expected: usize) -> Result<(), SZLoadError> {
+    let index: usize = parse_token(tokens, 0, line, "level index")?;
+    if index != expected {
+        return Err(SZLoadError::InvalidFormat(
+            line,
+            format!("expected level {}, got {}", expected, index),
+        ));
+    }
+    Ok(())
+}
+
 #[derive(Default)]
 pub struct SZBuilder<'a> {
     hgrid: Option<&'a Hgrid>,
@@ -244,3 +530,106 @@ pub enum SZBuilderError {
     #[error("critical depth must be larger or equal than 5., but got {0}")]
     InvalidCriticalDepth(f64),
 }
+
+#[derive(Error, Debug)]
+pub enum SZLoadError {
+    #[error("File IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Unsupported ivcor value: {0} (only ivcor=2 is supported)")]
+    UnsupportedIvcor(i32),
+    #[error("Unexpected end of file while reading {0}")]
+    UnexpectedEof(String),
+    #[error("Parse error on line {0}: {1}")]
+    ParseError(usize, String),
+    #[error("Invalid vgrid.in on line {0}: {1}")]
+    InvalidFormat(usize, String),
+    #[error(transparent)]
+    SZBuilderError(#[from] SZBuilderError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SZLoadError, SZPlotError, SZ};
+
+    const VGRID: &str = "2 !ivcor
+6 3 100. !nvrt, kz (# of Z-levels); h_s (transition depth between S and Z)
+Z levels
+1  -5000.
+2  -2300.
+3  -100.
+S levels
+30. 0.7 5. !h_c, theta_b, theta_f
+
+3 -1.
+4 -0.6
+5 -0.25
+6 0.
+";
+
+    #[test]
+    fn test_parse_then_write_is_equivalent() {
+        let sz = SZ::parse(VGRID).unwrap();
+        assert_eq!(sz.nvrt(), 6);
+        assert_eq!(sz.kz(), 3);
+        assert_eq!(sz.h_s(), 100.);
+        assert_eq!(sz.z_levels().to_vec(), vec![-5000., -2300., -100.]);
+        assert_eq!(sz.s_levels().to_vec(), vec![-1., -0.6, -0.25, 0.]);
+        assert_eq!(
+            (sz.critical_depth(), sz.theta_b(), sz.theta_f()),
+            (30., 0.7, 5.)
+        );
+        let written = sz.to_string();
+        let reparsed = SZ::parse(&written).unwrap();
+        assert_eq!(reparsed.to_string(), written);
+        assert_eq!(reparsed.z_levels(), sz.z_levels());
+        assert_eq!(reparsed.s_levels(), sz.s_levels());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let truncated: String = VGRID.lines().take(10).collect::<Vec<_>>().join("\n");
+        assert!(matches!(
+            SZ::parse(&truncated),
+            Err(SZLoadError::UnexpectedEof(_))
+        ));
+        let bad_h_s = VGRID.replace("6 3 100.", "6 3 90.");
+        assert!(matches!(
+            SZ::parse(&bad_h_s),
+            Err(SZLoadError::InvalidFormat(6, _))
+        ));
+        let bad_index = VGRID.replace("5 -0.25", "4 -0.25");
+        assert!(matches!(
+            SZ::parse(&bad_index),
+            Err(SZLoadError::InvalidFormat(12, _))
+        ));
+        let bad_value = VGRID.replace("-2300.", "-2300,");
+        assert!(matches!(
+            SZ::parse(&bad_value),
+            Err(SZLoadError::ParseError(5, _))
+        ));
+        assert!(matches!(
+            SZ::parse(&VGRID.replacen("2", "1", 1)),
+            Err(SZLoadError::UnsupportedIvcor(1))
+        ));
+    }
+
+    #[test]
+    fn test_vertical_distribution() {
+        let sz = SZ::parse(VGRID).unwrap();
+        let (bottoms, zcor) = sz.vertical_distribution(4).unwrap();
+        assert_eq!(
+            bottoms.to_vec(),
+            vec![-100., -76.66666666666667, -53.333333333333336, -30.]
+        );
+        assert_eq!(zcor.dim(), (4, 4));
+        for (bottom, levels) in bottoms.iter().zip(zcor.rows()) {
+            assert!((levels[0] - bottom).abs() < 1e-9);
+            assert!(levels[3].abs() < 1e-12);
+            assert!(levels.windows(2).into_iter().all(|pair| pair[0] < pair[1]));
+        }
+        assert!(matches!(
+            sz.vertical_distribution(1),
+            Err(SZPlotError::InvalidNbinsValue(1))
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+//! Fixtures shared by the unit tests.
+
+use ndarray::Array2;
+use schismrs_hgrid::hgrid::Hgrid;
+use schismrs_hgrid::mesh_generator::{Bathymetry, MeshGeneratorBuilder, SizeFunction};
+use schismrs_hgrid::raster::Raster;
+
+/// Mesh of the square 0..10 with elements about `size` across, on a
+/// bathymetry deepening from 1 at the origin by 5 per unit in x and y.
+pub(crate) fn sloping_hgrid(size: f64) -> Hgrid {
+    square_hgrid(size, 1., 5.)
+}
+
+/// Like [`sloping_hgrid`], on a bathymetry 2 above the datum at the origin
+/// and deepening by 2.5 per unit in x and y, so that the nodes near the
+/// origin are dry at eta = 0.
+pub(crate) fn intertidal_hgrid(size: f64) -> Hgrid {
+    square_hgrid(size, -2., 2.5)
+}
+
+fn square_hgrid(size: f64, origin_depth: f64, slope: f64) -> Hgrid {
+    let values = Array2::from_shape_fn((11, 11), |(i, j)| origin_depth + slope * (i + j) as f64);
+    MeshGeneratorBuilder::default()
+        .outer(vec![(0., 0.), (10., 0.), (10., 10.), (0., 10.)])
+        .size_function(SizeFunction::Constant(size))
+        .bathymetry(Some(Bathymetry::Raster(
+            Raster::new(0., 0., 1., 1., values).unwrap(),
+        )))
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+}
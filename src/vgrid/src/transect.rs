@@ -0,0 +1,415 @@
+// schismrs-vgrid/src/transect.rs
+
+//! Layer structure of a vertical grid along a polyline through the mesh.
+
+use crate::vertical_grid::{VerticalGrid, ZcorError, DEFAULT_H0};
+use ndarray::{s, Array1, Array2};
+#[cfg(feature = "plot")]
+use plotly::color::NamedColor;
+#[cfg(feature = "plot")]
+use plotly::common::{Fill, Line, Mode, Title};
+#[cfg(feature = "plot")]
+use plotly::layout::{Axis, Layout};
+#[cfg(feature = "plot")]
+use plotly::{Plot, Scatter};
+use schismrs_hgrid::hgrid::Hgrid;
+use schismrs_hgrid::transect::TransectPoint;
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// Samples along a polyline through the mesh with the elevations of every
+/// level of a vertical grid, interpolated within the element under each
+/// sample. Samples outside the mesh are gaps.
+pub struct Transect {
+    points: Vec<TransectPoint>,
+    eta: Vec<f64>,
+    zcor: Array2<f64>,
+}
+
+impl Transect {
+    /// The hgrid samples, with their element and interpolated depth.
+    pub fn points(&self) -> &[TransectPoint] {
+        &self.points
+    }
+
+    /// Distance of each sample along the polyline, in the units of the hgrid
+    /// coordinates.
+    pub fn distance(&self) -> Vec<f64> {
+        self.points.iter().map(|point| point.distance).collect()
+    }
+
+    /// Surface elevation at each sample, NaN at gaps.
+    pub fn eta(&self) -> &[f64] {
+        &self.eta
+    }
+
+    /// Elevations of the levels (nvrt × samples), bottom-most level first.
+    /// Levels below the bottom at every node of the element, and all levels
+    /// at gaps, are NaN.
+    pub fn zcor(&self) -> &Array2<f64> {
+        &self.zcor
+    }
+
+    /// Layer interfaces drawn over the bathymetry. Lines break at gaps and
+    /// where levels end at the bottom.
+    #[cfg(feature = "plot")]
+    pub fn make_plot(&self) -> Plot {
+        let mut plot = Plot::new();
+        let distance = self.distance();
+        let bottom: Vec<Option<f64>> = self
+            .points
+            .iter()
+            .map(|point| point.depth.map(|depth| -depth))
+            .collect();
+        let floor = bottom
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f64::INFINITY, f64::min);
+        let floor = floor - 0.05 * floor.abs().max(1.);
+        let under_mesh: Vec<Option<f64>> = bottom.iter().map(|z| z.map(|_| floor)).collect();
+        plot.add_trace(
+            Scatter::new(distance.clone(), under_mesh)
+                .mode(Mode::Lines)
+                .line(Line::new().color(NamedColor::SandyBrown).width(0.))
+                .show_legend(false),
+        );
+        plot.add_trace(
+            Scatter::new(distance.clone(), bottom)
+                .mode(Mode::Lines)
+                .line(Line::new().color(NamedColor::SaddleBrown).width(2.))
+                .fill(Fill::ToNextY)
+                .name("bathymetry"),
+        );
+        for (k, level) in self.zcor.outer_iter().enumerate() {
+            let level: Vec<Option<f64>> =
+                level.iter().map(|z| (!z.is_nan()).then_some(*z)).collect();
+            plot.add_trace(
+                Scatter::new(distance.clone(), level)
+                    .mode(Mode::Lines)
+                    .line(Line::new().color(NamedColor::Blue).width(1.))
+                    .name(format!("level {}", k + 1))
+                    .show_legend(false),
+            );
+        }
+        let eta: Vec<Option<f64>> = self
+            .eta
+            .iter()
+            .map(|z| (!z.is_nan()).then_some(*z))
+            .collect();
+        plot.add_trace(
+            Scatter::new(distance, eta)
+                .mode(Mode::Lines)
+                .line(Line::new().color(NamedColor::DarkBlue).width(2.))
+                .name("eta"),
+        );
+        plot.set_layout(
+            Layout::new()
+                .title(Title::new("Vertical grid transect"))
+                .x_axis(Axis::new().title(Title::new("distance along transect")))
+                .y_axis(Axis::new().title(Title::new("z (m)"))),
+        );
+        plot
+    }
+
+    /// One row per level of every sample in the mesh, bottom-most level first.
+    pub fn write_csv(&self, path: &Path) -> Result<(), TransectError> {
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(["distance", "x", "y", "element_id", "depth", "level", "z"])?;
+        for (point, column) in self.points.iter().zip(self.zcor.columns()) {
+            let (Some(element_id), Some(depth)) = (point.element_id, point.depth) else {
+                continue;
+            };
+            for (k, z) in column.iter().enumerate() {
+                if z.is_nan() {
+                    continue;
+                }
+                wtr.write_record(&[
+                    point.distance.to_string(),
+                    point.x.to_string(),
+                    point.y.to_string(),
+                    element_id.to_string(),
+                    depth.to_string(),
+                    (k + 1).to_string(),
+                    z.to_string(),
+                ])?;
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct TransectBuilder<'a> {
+    hgrid: Option<&'a Hgrid>,
+    vgrid: Option<&'a dyn VerticalGrid>,
+    polyline: Option<&'a [(f64, f64)]>,
+    spacing: Option<&'a f64>,
+    eta: Option<&'a Array1<f64>>,
+    h0: Option<&'a f64>,
+}
+
+impl<'a> TransectBuilder<'a> {
+    pub fn build(&self) -> Result<Transect, TransectError> {
+        let hgrid = self
+            .hgrid
+            .ok_or_else(|| TransectError::UninitializedFieldError("hgrid".to_string()))?;
+        let vgrid = self
+            .vgrid
+            .ok_or_else(|| TransectError::UninitializedFieldError("vgrid".to_string()))?;
+        let polyline = self
+            .polyline
+            .ok_or_else(|| TransectError::UninitializedFieldError("polyline".to_string()))?;
+        let spacing = self
+            .spacing
+            .ok_or_else(|| TransectError::UninitializedFieldError("spacing".to_string()))?;
+        let h0 = self.h0.copied().unwrap_or(DEFAULT_H0);
+
+        let np = hgrid.nodes().len();
+        if let Some(node_count) = vgrid.node_count() {
+            if node_count != np {
+                return Err(ZcorError::NodeCountMismatch(np, node_count).into());
+            }
+        }
+        if let Some(eta) = self.eta {
+            if eta.len() != np {
+                return Err(ZcorError::EtaLengthMismatch(np, eta.len()).into());
+            }
+        }
+
+        let points = hgrid.transect(polyline, *spacing)?.points;
+        if points.iter().all(|point| point.is_gap()) {
+            return Err(TransectError::OutsideMesh);
+        }
+        let index: HashMap<u32, usize> = hgrid
+            .nodes()
+            .hash_map()
+            .keys()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+        // hgrid depths are stored negative below the datum
+        let depths = hgrid.depths();
+        let nvrt = vgrid.nvrt();
+        let mut eta = vec![f64::NAN; points.len()];
+        let mut zcor = Array2::from_elem((nvrt, points.len()), f64::NAN);
+        for (i, point) in points.iter().enumerate() {
+            if point.is_gap() {
+                continue;
+            }
+            let mut column = Array1::zeros(nvrt);
+            let mut lowest_level = nvrt;
+            eta[i] = 0.;
+            for (node_id, weight) in point.weights.iter() {
+                let node = index[node_id];
+                let node_eta = self.eta.map_or(0., |eta| eta[node]);
+                let mut node_column = vgrid.zcor_at_node_with_h0(node, -depths[node], node_eta, h0);
+                let bottom = match node_column.iter().position(|z| !z.is_nan()) {
+                    Some(bottom) => {
+                        // levels below the bottom of a node sit on its bottom
+                        let z_bottom = node_column[bottom];
+                        node_column.slice_mut(s![..bottom]).fill(z_bottom);
+                        bottom
+                    }
+                    None => {
+                        // a node that was dry when the grid was built has no
+                        // levels; when eta floods it, its column collapses to
+                        // the surface level over the bottom
+                        node_column.fill(depths[node]);
+                        node_column[nvrt - 1] = node_eta;
+                        nvrt - 1
+                    }
+                };
+                lowest_level = lowest_level.min(bottom);
+                column.scaled_add(*weight, &node_column);
+                eta[i] += weight * node_eta;
+            }
+            column.slice_mut(s![..lowest_level]).fill(f64::NAN);
+            zcor.column_mut(i).assign(&column);
+        }
+        Ok(Transect { points, eta, zcor })
+    }
+
+    pub fn hgrid(&mut self, hgrid: &'a Hgrid) -> &mut Self {
+        self.hgrid = Some(hgrid);
+        self
+    }
+
+    pub fn vgrid(&mut self, vgrid: &'a dyn VerticalGrid) -> &mut Self {
+        self.vgrid = Some(vgrid);
+        self
+    }
+
+    pub fn polyline(&mut self, polyline: &'a [(f64, f64)]) -> &mut Self {
+        self.polyline = Some(polyline);
+        self
+    }
+
+    /// Largest distance between consecutive samples, see [`Hgrid::transect`].
+    pub fn spacing(&mut self, spacing: &'a f64) -> &mut Self {
+        self.spacing = Some(spacing);
+        self
+    }
+
+    pub fn eta(&mut self, eta: &'a Array1<f64>) -> &mut Self {
+        self.eta = Some(eta);
+        self
+    }
+
+    pub fn h0(&mut self, h0: &'a f64) -> &mut Self {
+        self.h0 = Some(h0);
+        self
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TransectError {
+    #[error("Unitialized field on TransectBuilder: {0}")]
+    UninitializedFieldError(String),
+    #[error("The polyline does not cross the hgrid")]
+    OutsideMesh,
+    #[error(transparent)]
+    HgridTransectError(#[from] schismrs_hgrid::transect::TransectError),
+    #[error(transparent)]
+    ZcorError(#[from] ZcorError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TransectBuilder, TransectError};
+    use crate::sz::SZBuilder;
+    use crate::test_support::{intertidal_hgrid, sloping_hgrid};
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vqs::VQSBuilder;
+    use ndarray::Array1;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_transect_along_slope() {
+        let hgrid = sloping_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&vec![5., 20., 60., 110.])
+            .nlevels(&vec![3, 6, 10, 14])
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+        // leaves the mesh at x = 10
+        let polyline = [(0., 5.), (10., 5.), (12., 5.)];
+        let transect = TransectBuilder::default()
+            .hgrid(&hgrid)
+            .vgrid(&vqs)
+            .polyline(&polyline)
+            .spacing(&0.5)
+            .build()
+            .unwrap();
+        assert_eq!(transect.points().len(), 25);
+        for (point, column) in transect.points().iter().zip(transect.zcor().columns()) {
+            if point.x > 10. {
+                assert!(point.is_gap());
+                assert!(column.iter().all(|z| z.is_nan()));
+                continue;
+            }
+            let wet: Vec<f64> = column.iter().cloned().filter(|z| !z.is_nan()).collect();
+            // the bottom level follows the bathymetry interpolated in the element
+            assert!((wet[0] + point.depth.unwrap()).abs() < 1e-9);
+            assert!(wet[wet.len() - 1].abs() < 1e-9);
+            assert!(wet.windows(2).all(|pair| pair[0] <= pair[1]));
+        }
+        assert!(transect.eta()[0].abs() < 1e-12);
+        assert!(transect.eta()[24].is_nan());
+
+        let file = NamedTempFile::new().unwrap();
+        transect.write_csv(file.path()).unwrap();
+        let rows = std::fs::read_to_string(file.path())
+            .unwrap()
+            .lines()
+            .count();
+        let wet_levels = transect.zcor().iter().filter(|z| !z.is_nan()).count();
+        assert_eq!(rows, wet_levels + 1);
+
+        let sz = SZBuilder::default()
+            .hgrid(&hgrid)
+            .slevels(&5)
+            .theta_f(&0.1)
+            .theta_b(&0.)
+            .critical_depth(&5.)
+            .etal(&0.)
+            .build()
+            .unwrap();
+        let transect = TransectBuilder::default()
+            .hgrid(&hgrid)
+            .vgrid(&sz)
+            .polyline(&polyline)
+            .spacing(&0.5)
+            .build()
+            .unwrap();
+        assert_eq!(transect.zcor().nrows(), 5);
+
+        assert!(matches!(
+            TransectBuilder::default()
+                .hgrid(&hgrid)
+                .vgrid(&sz)
+                .polyline(&[(20., 20.), (30., 30.)])
+                .spacing(&0.5)
+                .build(),
+            Err(TransectError::OutsideMesh)
+        ));
+        assert!(matches!(
+            TransectBuilder::default()
+                .hgrid(&hgrid)
+                .vgrid(&sz)
+                .polyline(&polyline)
+                .spacing(&0.)
+                .build(),
+            Err(TransectError::HgridTransectError(_))
+        ));
+    }
+
+    #[test]
+    fn test_transect_over_flooded_dry_nodes() {
+        let hgrid = intertidal_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&vec![5., 20., 50.])
+            .nlevels(&vec![3, 6, 10])
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+        // nodes above the datum have no levels but are wet under eta = 3
+        let eta = Array1::from_elem(hgrid.nodes().len(), 3.);
+        let polyline = [(0., 0.), (10., 10.)];
+        let transect = TransectBuilder::default()
+            .hgrid(&hgrid)
+            .vgrid(&vqs)
+            .polyline(&polyline)
+            .spacing(&0.5)
+            .eta(&eta)
+            .build()
+            .unwrap();
+        assert!(transect.points()[0].depth.unwrap() < 0.);
+        for (point, column) in transect.points().iter().zip(transect.zcor().columns()) {
+            let wet: Vec<f64> = column.iter().cloned().filter(|z| !z.is_nan()).collect();
+            assert!((wet[0] + point.depth.unwrap()).abs() < 1e-9);
+            assert!((wet[wet.len() - 1] - 3.).abs() < 1e-9);
+            assert!(wet.windows(2).all(|pair| pair[0] <= pair[1]));
+        }
+    }
+}
@@ -2,10 +2,12 @@
 
 pub use quadratic::QuadraticTransform;
 pub use reconstructed::ReconstructedTransform;
+pub use roms::{RomsTransform, RomsVstretching};
 pub use transforms::StretchingFunction;
 
 pub mod quadratic;
 pub mod reconstructed;
+pub mod roms;
 pub mod s;
 pub mod traits;
 pub mod transforms;
\ No newline at end of file
@@ -0,0 +1,497 @@
+use super::traits::Transform;
+use libm::{cosh, exp, log, sinh, tanh};
+use ndarray::Array2;
+use schismrs_hgrid::Hgrid;
+use thiserror::Error;
+
+/// Vertical stretching functions of ROMS (`Vstretching` in ROMS' set_scoord.F).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RomsVstretching {
+    /// Vstretching = 2 (Shchepetkin, 2005).
+    Shchepetkin2005,
+    /// Vstretching = 3 (Geyer), for bottom boundary layer resolution.
+    Geyer,
+    /// Vstretching = 4 (Shchepetkin, 2010).
+    Shchepetkin2010,
+    /// Vstretching = 5 (Souza), quadratic in the level index.
+    Souza,
+}
+
+/// Scale of the Geyer stretching, fixed in ROMS.
+const GEYER_HSCALE: f64 = 3.;
+
+impl RomsVstretching {
+    pub fn vstretching(&self) -> u8 {
+        match self {
+            RomsVstretching::Shchepetkin2005 => 2,
+            RomsVstretching::Geyer => 3,
+            RomsVstretching::Shchepetkin2010 => 4,
+            RomsVstretching::Souza => 5,
+        }
+    }
+
+    /// Sigma coordinate and stretching value C(sigma) of w-level `k` out of
+    /// `nlayers`, numbered as in ROMS from 0 (bottom) to `nlayers` (surface).
+    ///
+    /// For the Geyer stretching `theta_s` and `theta_b` are the surface and
+    /// bottom exponents.
+    pub fn sigma_and_cs(&self, k: usize, nlayers: usize, theta_s: f64, theta_b: f64) -> (f64, f64) {
+        if k == 0 {
+            return (-1., -1.);
+        }
+        if k >= nlayers {
+            return (0., 0.);
+        }
+        let n = nlayers as f64;
+        let lev = k as f64;
+        let sigma = match self {
+            RomsVstretching::Souza => {
+                -(lev * lev - 2. * lev * n + lev + n * n - n) / (n * n - n)
+                    - 0.01 * (lev * lev - lev * n) / (1. - n)
+            }
+            _ => (lev - n) / n,
+        };
+        let cs = match self {
+            RomsVstretching::Shchepetkin2005 => Self::shchepetkin2005(sigma, theta_s, theta_b),
+            RomsVstretching::Geyer => Self::geyer(sigma, theta_s, theta_b),
+            RomsVstretching::Shchepetkin2010 | RomsVstretching::Souza => {
+                Self::shchepetkin2010(sigma, theta_s, theta_b)
+            }
+        };
+        (sigma, cs)
+    }
+
+    fn shchepetkin2005(sigma: f64, theta_s: f64, theta_b: f64) -> f64 {
+        if theta_s <= 0. {
+            return sigma;
+        }
+        let csur = (1. - cosh(theta_s * sigma)) / (cosh(theta_s) - 1.);
+        if theta_b <= 0. {
+            return csur;
+        }
+        let cbot = sinh(theta_b * (sigma + 1.)) / sinh(theta_b) - 1.;
+        // ROMS' Aweight = Bweight = 1
+        let cweight = (sigma + 1.) * (1. + (1. - (sigma + 1.)));
+        cweight * csur + (1. - cweight) * cbot
+    }
+
+    fn shchepetkin2010(sigma: f64, theta_s: f64, theta_b: f64) -> f64 {
+        let csur = if theta_s > 0. {
+            (1. - cosh(theta_s * sigma)) / (cosh(theta_s) - 1.)
+        } else {
+            -sigma * sigma
+        };
+        if theta_b > 0. {
+            (exp(theta_b * csur) - 1.) / (1. - exp(-theta_b))
+        } else {
+            csur
+        }
+    }
+
+    fn geyer(sigma: f64, exp_s: f64, exp_b: f64) -> f64 {
+        let norm = log(cosh(GEYER_HSCALE));
+        let cbot = log(cosh(GEYER_HSCALE * (sigma + 1.).powf(exp_b))) / norm - 1.;
+        let csur = -log(cosh(GEYER_HSCALE * sigma.abs().powf(exp_s))) / norm;
+        let cweight = 0.5 * (1. - tanh(GEYER_HSCALE * (sigma + 0.5)));
+        cweight * cbot + (1. - cweight) * csur
+    }
+}
+
+pub struct RomsTransform {
+    zmas: Array2<f64>,
+    etal: f64,
+    a_vqs0: f64,
+    vstretching: RomsVstretching,
+}
+
+impl RomsTransform {
+    pub fn vstretching(&self) -> &RomsVstretching {
+        &self.vstretching
+    }
+}
+
+impl Transform for RomsTransform {
+    fn zmas(&self) -> &Array2<f64> {
+        &self.zmas
+    }
+    fn etal(&self) -> &f64 {
+        &self.etal
+    }
+    fn a_vqs0(&self) -> &f64 {
+        &self.a_vqs0
+    }
+}
+
+#[derive(Default)]
+pub struct RomsTransformBuilder<'a> {
+    hgrid: Option<&'a Hgrid>,
+    etal: Option<&'a f64>,
+    depths: Option<&'a Vec<f64>>,
+    nlevels: Option<&'a Vec<usize>>,
+    a_vqs0: Option<&'a f64>,
+    theta_s: Option<&'a f64>,
+    theta_b: Option<&'a f64>,
+    vstretching: Option<&'a RomsVstretching>,
+}
+
+impl<'a> RomsTransformBuilder<'a> {
+    pub fn build(&self) -> Result<RomsTransform, RomsTransformBuilderError> {
+        let hgrid = self.hgrid.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("hgrid".to_string())
+        })?;
+        let depths = self.depths.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("depths".to_string())
+        })?;
+        Self::validate_depths(hgrid, depths)?;
+        let nlevels = self.nlevels.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("nlevels".to_string())
+        })?;
+        Self::validate_nlevels(nlevels)?;
+        Self::validate_depths_and_nlevels(depths, nlevels)?;
+        let etal = self.etal.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("etal".to_string())
+        })?;
+        Self::validate_etal(etal, &depths[0])?;
+        let a_vqs0 = self.a_vqs0.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("a_vqs0".to_string())
+        })?;
+        Self::validate_a_vqs0(a_vqs0)?;
+        let vstretching = self.vstretching.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("vstretching".to_string())
+        })?;
+        let theta_s = self.theta_s.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("theta_s".to_string())
+        })?;
+        let theta_b = self.theta_b.ok_or_else(|| {
+            RomsTransformBuilderError::UninitializedFieldError("theta_b".to_string())
+        })?;
+        Self::validate_thetas(vstretching, theta_s, theta_b)?;
+        let zmas = Self::build_zmas(depths, nlevels, etal, vstretching, theta_s, theta_b);
+        Ok(RomsTransform {
+            zmas,
+            etal: *etal,
+            a_vqs0: *a_vqs0,
+            vstretching: *vstretching,
+        })
+    }
+
+    /// Master grids in the same form as the S transform, with depths[0] in the
+    /// role of the critical depth:
+    /// z = etal (1 + sigma) + depths[0] sigma + (depth - depths[0]) C(sigma).
+    pub fn build_zmas(
+        depths: &[f64],
+        nlevels: &[usize],
+        etal: &f64,
+        vstretching: &RomsVstretching,
+        theta_s: &f64,
+        theta_b: &f64,
+    ) -> Array2<f64> {
+        let num_grids = depths.len();
+        let max_levels = nlevels.iter().max().unwrap();
+        let mut z_mas = Array2::from_elem((*max_levels, num_grids), f64::NAN);
+        for (m, &depth) in depths.iter().enumerate() {
+            let nlayers = nlevels[m] - 1;
+            // z_mas rows go from the surface down, ROMS levels from the bottom up
+            for k in 0..nlevels[m] {
+                let (sigma, cs) =
+                    vstretching.sigma_and_cs(nlayers - k, nlayers, *theta_s, *theta_b);
+                z_mas[[k, m]] = *etal * (1. + sigma) + depths[0] * sigma + (depth - depths[0]) * cs;
+            }
+        }
+        z_mas
+    }
+
+    fn validate_depths_and_nlevels(
+        depths: &[f64],
+        nlevels: &[usize],
+    ) -> Result<(), RomsTransformBuilderError> {
+        let depth_len = depths.len();
+        let nlevels_len = nlevels.len();
+        if depth_len != nlevels_len {
+            return Err(RomsTransformBuilderError::DepthsAndLevelsSizeMismatch(
+                depth_len,
+                nlevels_len,
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_a_vqs0(a_vqs0: &f64) -> Result<(), RomsTransformBuilderError> {
+        if *a_vqs0 < -1.0 || *a_vqs0 > 1.0 {
+            return Err(RomsTransformBuilderError::InvalidAVqs0(*a_vqs0));
+        }
+        Ok(())
+    }
+
+    fn validate_etal(etal: &f64, depths0: &f64) -> Result<(), RomsTransformBuilderError> {
+        if *etal >= *depths0 {
+            return Err(RomsTransformBuilderError::InvalidEtalValue(*depths0, *etal));
+        }
+        Ok(())
+    }
+
+    fn validate_depths(hgrid: &Hgrid, depths: &[f64]) -> Result<(), RomsTransformBuilderError> {
+        let mut prev_depth = depths[0];
+        for &depth in &depths[1..] {
+            if depth <= prev_depth {
+                return Err(RomsTransformBuilderError::InvalidDepths);
+            }
+            prev_depth = depth;
+        }
+        let min_hgrid_depth = hgrid.depths().iter().cloned().fold(f64::MAX, f64::min);
+        let last_depth = depths[depths.len() - 1];
+        if last_depth < -min_hgrid_depth {
+            return Err(RomsTransformBuilderError::InvalidLastDepth(
+                last_depth,
+                -min_hgrid_depth,
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_nlevels(nlevels: &[usize]) -> Result<(), RomsTransformBuilderError> {
+        let mut prev_nlevel = nlevels[0];
+        if prev_nlevel < 2 {
+            return Err(RomsTransformBuilderError::InvalidFirstLevel);
+        }
+        for &nlevel in &nlevels[1..] {
+            if nlevel < prev_nlevel {
+                return Err(RomsTransformBuilderError::InvalidNLevels);
+            }
+            prev_nlevel = nlevel;
+        }
+        Ok(())
+    }
+
+    /// Ranges recommended for ROMS. The Geyer exponents only need to be positive.
+    pub fn validate_thetas(
+        vstretching: &RomsVstretching,
+        theta_s: &f64,
+        theta_b: &f64,
+    ) -> Result<(), RomsTransformBuilderError> {
+        match vstretching {
+            RomsVstretching::Geyer => {
+                if theta_s.is_nan() || *theta_s <= 0. {
+                    return Err(RomsTransformBuilderError::InvalidGeyerExponent(
+                        "theta_s".to_string(),
+                        *theta_s,
+                    ));
+                }
+                if theta_b.is_nan() || *theta_b <= 0. {
+                    return Err(RomsTransformBuilderError::InvalidGeyerExponent(
+                        "theta_b".to_string(),
+                        *theta_b,
+                    ));
+                }
+            }
+            _ => {
+                if !(0.0..=10.0).contains(theta_s) {
+                    return Err(RomsTransformBuilderError::InvalidThetaS(*theta_s));
+                }
+                if !(0.0..=4.0).contains(theta_b) {
+                    return Err(RomsTransformBuilderError::InvalidThetaB(*theta_b));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn hgrid(&mut self, hgrid: &'a Hgrid) -> &mut Self {
+        self.hgrid = Some(hgrid);
+        self
+    }
+    pub fn depths(&mut self, depths: &'a Vec<f64>) -> &mut Self {
+        self.depths = Some(depths);
+        self
+    }
+    pub fn nlevels(&mut self, nlevels: &'a Vec<usize>) -> &mut Self {
+        self.nlevels = Some(nlevels);
+        self
+    }
+    pub fn etal(&mut self, etal: &'a f64) -> &mut Self {
+        self.etal = Some(etal);
+        self
+    }
+    pub fn a_vqs0(&mut self, a_vqs0: &'a f64) -> &mut Self {
+        self.a_vqs0 = Some(a_vqs0);
+        self
+    }
+    pub fn theta_s(&mut self, theta_s: &'a f64) -> &mut Self {
+        self.theta_s = Some(theta_s);
+        self
+    }
+    pub fn theta_b(&mut self, theta_b: &'a f64) -> &mut Self {
+        self.theta_b = Some(theta_b);
+        self
+    }
+    pub fn vstretching(&mut self, vstretching: &'a RomsVstretching) -> &mut Self {
+        self.vstretching = Some(vstretching);
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RomsTransformOpts<'a> {
+    pub etal: &'a f64,
+    pub a_vqs0: &'a f64,
+    pub theta_s: &'a f64,
+    pub theta_b: &'a f64,
+}
+
+impl<'a> RomsTransformOpts<'a> {
+    pub fn new() -> Self {
+        Self {
+            etal: &0.,
+            a_vqs0: &0.,
+            theta_s: &5.,
+            theta_b: &0.4,
+        }
+    }
+    pub fn etal(&mut self, etal: &'a f64) -> &mut Self {
+        self.etal = etal;
+        self
+    }
+    pub fn a_vqs0(&mut self, a_vqs0: &'a f64) -> &mut Self {
+        self.a_vqs0 = a_vqs0;
+        self
+    }
+    pub fn theta_s(&mut self, theta_s: &'a f64) -> &mut Self {
+        self.theta_s = theta_s;
+        self
+    }
+    pub fn theta_b(&mut self, theta_b: &'a f64) -> &mut Self {
+        self.theta_b = theta_b;
+        self
+    }
+}
+
+impl<'a> Default for RomsTransformOpts<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RomsTransformBuilderError {
+    #[error("Unitialized field on RomsTransformBuilder: {0}")]
+    UninitializedFieldError(String),
+    #[error(
+        "depths and nlevels array must be of the same length. Got lengths {0} and {1} respectively"
+    )]
+    DepthsAndLevelsSizeMismatch(usize, usize),
+    #[error("depths vector must be strictly increasing")]
+    InvalidDepths,
+    #[error("First level in nlevels must be >= 2")]
+    InvalidFirstLevel,
+    #[error("nlevels vector must be increasing")]
+    InvalidNLevels,
+    #[error("Last depth provided was {0} but it must be greater or equal than {1} which is the deepest point in hgrid.")]
+    InvalidLastDepth(f64, f64),
+    #[error("a_vqs0 must be in [-1., 1.], but got {0}")]
+    InvalidAVqs0(f64),
+    #[error("theta_s must be in [0., 10.], but got {0}")]
+    InvalidThetaS(f64),
+    #[error("theta_b must be in [0., 4.], but got {0}")]
+    InvalidThetaB(f64),
+    #[error("The Geyer stretching exponent {0} must be positive, but got {1}")]
+    InvalidGeyerExponent(String, f64),
+    #[error("etal must be smaller than the first depth, (which is {0}) but got {1}")]
+    InvalidEtalValue(f64, f64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RomsTransformBuilder, RomsTransformBuilderError, RomsVstretching};
+    use schismrs_hgrid::mesh_generator::{Bathymetry, MeshGeneratorBuilder, SizeFunction};
+
+    // Regression values of C(sigma) at w-levels 2, 5 and 8 of 10 layers with
+    // theta_s = 5, theta_b = 0.4 (Geyer: 3, 3). They were computed with the
+    // formulas implemented here, so they pin the current output rather than
+    // check it against ROMS; the end points and monotonicity are checked
+    // independently.
+    #[test]
+    fn test_profile_regression_values() {
+        let cases = [
+            (
+                RomsVstretching::Shchepetkin2005,
+                (5., 0.4),
+                [-0.644584843055, -0.180036787703, -0.015424503191],
+            ),
+            (
+                RomsVstretching::Shchepetkin2010,
+                (5., 0.4),
+                [-0.406115736567, -0.083875204222, -0.008987056976],
+            ),
+            (
+                RomsVstretching::Souza,
+                (5., 0.4),
+                [-0.181401215717, -0.014688043146, -0.000332545215],
+            ),
+            (
+                RomsVstretching::Geyer,
+                (3., 3.),
+                [-0.912596145785, -0.5, -0.087403854215],
+            ),
+        ];
+        for (vstretching, (theta_s, theta_b), expected) in cases {
+            for (k, cs) in [2, 5, 8].into_iter().zip(expected) {
+                let (_, value) = vstretching.sigma_and_cs(k, 10, theta_s, theta_b);
+                assert!((value - cs).abs() < 1e-10, "{:?} k={}", vstretching, k);
+            }
+            assert_eq!(
+                vstretching.sigma_and_cs(0, 10, theta_s, theta_b),
+                (-1., -1.)
+            );
+            assert_eq!(vstretching.sigma_and_cs(10, 10, theta_s, theta_b), (0., 0.));
+            let profile: Vec<f64> = (0..=10)
+                .map(|k| vstretching.sigma_and_cs(k, 10, theta_s, theta_b).1)
+                .collect();
+            assert!(profile.windows(2).all(|w| w[0] < w[1]));
+        }
+        let (sigma, _) = RomsVstretching::Souza.sigma_and_cs(2, 10, 5., 0.4);
+        assert!((sigma + 0.64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_build_and_validate() {
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(vec![(0., 0.), (4., 0.), (4., 4.), (0., 4.)])
+            .size_function(SizeFunction::Constant(1.))
+            .bathymetry(Some(Bathymetry::Constant(100.)))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let depths = vec![10., 100.];
+        let nlevels = vec![5, 11];
+        let mut builder = RomsTransformBuilder::default();
+        builder
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .etal(&0.)
+            .a_vqs0(&0.)
+            .theta_s(&5.)
+            .theta_b(&0.4)
+            .vstretching(&RomsVstretching::Shchepetkin2010);
+        let transform = builder.build().unwrap();
+        let zmas = transform.zmas.column(1);
+        assert_eq!(zmas[0], 0.);
+        assert!((zmas[10] + 100.).abs() < 1e-12);
+        // 10 sigma + 90 C(sigma) at sigma = -0.2
+        assert!((zmas[2] - (-2. - 90. * 0.008987056976)).abs() < 1e-8);
+        assert!(transform.zmas[[5, 0]].is_nan());
+
+        builder.theta_s(&12.);
+        assert!(matches!(
+            builder.build(),
+            Err(RomsTransformBuilderError::InvalidThetaS(_))
+        ));
+        builder.vstretching(&RomsVstretching::Geyer);
+        builder.theta_b(&0.);
+        assert!(matches!(
+            builder.build(),
+            Err(RomsTransformBuilderError::InvalidGeyerExponent(_, _))
+        ));
+    }
+}
@@ -1,26 +1,41 @@
 use ndarray::Axis;
 use ndarray::{Array1, Array2};
 use ndarray_stats::errors::MinMaxError;
+#[cfg(feature = "plot")]
 use ndarray_stats::QuantileExt;
+#[cfg(feature = "plot")]
 use plotly::color::NamedColor;
+#[cfg(feature = "plot")]
 use plotly::common::{Line, Marker, Mode};
+#[cfg(feature = "plot")]
 use plotly::{Plot, Scatter};
 use thiserror::Error;
 
-pub trait Transform {
+/// Master grids of a VQS. Transforms are shared between threads while building.
+pub trait Transform: Send + Sync {
     fn zmas(&self) -> &Array2<f64>;
     fn etal(&self) -> &f64;
     fn a_vqs0(&self) -> &f64;
 
+    /// Level elevations of each master grid, i.e. the columns of
+    /// [`Transform::zmas`] without their unused (NaN) levels.
+    fn master_grid_profiles(&self) -> Vec<Array1<f64>> {
+        self.zmas()
+            .axis_iter(Axis(1))
+            .map(|master_grid| {
+                master_grid
+                    .iter()
+                    .filter(|&&x| !x.is_nan())
+                    .cloned()
+                    .collect::<Array1<f64>>()
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "plot")]
     fn make_zmas_plot(&self) -> Result<Plot, TransformPlotterError> {
-        let z_mas = self.zmas();
         let mut plot = Plot::new();
-        for master_grid in z_mas.axis_iter(Axis(1)) {
-            let master_grid = master_grid
-                .iter()
-                .filter(|&&x| !x.is_nan())
-                .cloned()
-                .collect::<Array1<f64>>();
+        for master_grid in self.master_grid_profiles() {
             let min_value = *master_grid.min()?;
             let trace = Scatter::new(vec![min_value; master_grid.len()], master_grid.to_vec())
                 .mode(Mode::LinesMarkers)
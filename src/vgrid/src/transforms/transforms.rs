@@ -4,23 +4,37 @@ use super::quadratic::QuadraticTransformBuilder;
 use super::quadratic::QuadraticTransformBuilderError;
 use super::quadratic::QuadraticTransformOpts;
 use super::reconstructed::ReconstructedTransform;
+use super::roms::RomsTransformBuilder;
+use super::roms::RomsTransformBuilderError;
+use super::roms::RomsTransformOpts;
+use super::roms::RomsVstretching;
 use super::s::STransformBuilder;
 use super::s::STransformBuilderError;
 use super::s::STransformOpts;
 use super::traits::Transform;
 use schismrs_hgrid::Hgrid;
-use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Clone, Debug)]
 pub enum StretchingFunction<'a> {
     Quadratic(QuadraticTransformOpts<'a>),
     S(STransformOpts<'a>),
+    /// ROMS Vstretching = 2
+    Shchepetkin2005(RomsTransformOpts<'a>),
+    /// ROMS Vstretching = 3
+    Geyer(RomsTransformOpts<'a>),
+    /// ROMS Vstretching = 4
+    Shchepetkin2010(RomsTransformOpts<'a>),
+    /// ROMS Vstretching = 5
+    Souza(RomsTransformOpts<'a>),
     Reconstructed(ReconstructedOpts),
 }
 
 /// Options for a reconstructed transform from a loaded VQS file
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ReconstructedOpts {
     pub master_depths: Vec<f64>,
     pub master_levels: Vec<usize>,
@@ -33,18 +47,22 @@ impl<'a> StretchingFunction<'a> {
         match self {
             StretchingFunction::Quadratic(opts) => opts.etal,
             StretchingFunction::S(opts) => opts.etal,
+            StretchingFunction::Shchepetkin2005(opts)
+            | StretchingFunction::Geyer(opts)
+            | StretchingFunction::Shchepetkin2010(opts)
+            | StretchingFunction::Souza(opts) => opts.etal,
             StretchingFunction::Reconstructed(opts) => &opts.etal,
         }
     }
-    
+
     pub fn transform(
         &self,
         hgrid: &Hgrid,
         depths: &Vec<f64>,
         nlevels: &Vec<usize>,
-    ) -> Result<Rc<dyn Transform>, StretchingFunctionError> {
+    ) -> Result<Arc<dyn Transform>, StretchingFunctionError> {
         match self {
-            StretchingFunction::Quadratic(opts) => Ok(Rc::new(
+            StretchingFunction::Quadratic(opts) => Ok(Arc::new(
                 QuadraticTransformBuilder::default()
                     .hgrid(hgrid)
                     .depths(depths)
@@ -54,7 +72,7 @@ impl<'a> StretchingFunction<'a> {
                     .a_vqs0(opts.a_vqs0)
                     .build()?,
             )),
-            StretchingFunction::S(opts) => Ok(Rc::new(
+            StretchingFunction::S(opts) => Ok(Arc::new(
                 STransformBuilder::default()
                     .hgrid(hgrid)
                     .depths(depths)
@@ -65,16 +83,55 @@ impl<'a> StretchingFunction<'a> {
                     .theta_b(opts.theta_b)
                     .build()?,
             )),
-            StretchingFunction::Reconstructed(opts) => Ok(Rc::new(
-                ReconstructedTransform::new(
-                    opts.master_depths.clone(),
-                    opts.master_levels.clone(),
-                    opts.etal,
-                    opts.a_vqs0,
-                )
-            )),
+            StretchingFunction::Shchepetkin2005(opts) => Self::roms_transform(
+                hgrid,
+                depths,
+                nlevels,
+                opts,
+                &RomsVstretching::Shchepetkin2005,
+            ),
+            StretchingFunction::Geyer(opts) => {
+                Self::roms_transform(hgrid, depths, nlevels, opts, &RomsVstretching::Geyer)
+            }
+            StretchingFunction::Shchepetkin2010(opts) => Self::roms_transform(
+                hgrid,
+                depths,
+                nlevels,
+                opts,
+                &RomsVstretching::Shchepetkin2010,
+            ),
+            StretchingFunction::Souza(opts) => {
+                Self::roms_transform(hgrid, depths, nlevels, opts, &RomsVstretching::Souza)
+            }
+            StretchingFunction::Reconstructed(opts) => Ok(Arc::new(ReconstructedTransform::new(
+                opts.master_depths.clone(),
+                opts.master_levels.clone(),
+                opts.etal,
+                opts.a_vqs0,
+            ))),
         }
     }
+
+    fn roms_transform(
+        hgrid: &Hgrid,
+        depths: &Vec<f64>,
+        nlevels: &Vec<usize>,
+        opts: &RomsTransformOpts,
+        vstretching: &RomsVstretching,
+    ) -> Result<Arc<dyn Transform>, StretchingFunctionError> {
+        Ok(Arc::new(
+            RomsTransformBuilder::default()
+                .hgrid(hgrid)
+                .depths(depths)
+                .nlevels(nlevels)
+                .etal(opts.etal)
+                .a_vqs0(opts.a_vqs0)
+                .theta_s(opts.theta_s)
+                .theta_b(opts.theta_b)
+                .vstretching(vstretching)
+                .build()?,
+        ))
+    }
 }
 
 #[derive(Error, Debug)]
@@ -83,4 +140,6 @@ pub enum StretchingFunctionError {
     STransformBuilderError(#[from] STransformBuilderError),
     #[error(transparent)]
     QuadraticTransformBuilderError(#[from] QuadraticTransformBuilderError),
-}
\ No newline at end of file
+    #[error(transparent)]
+    RomsTransformBuilderError(#[from] RomsTransformBuilderError),
+}
@@ -0,0 +1,756 @@
+// schismrs-vgrid/src/tuning.rs
+
+//! Side-by-side comparison of candidate VQS grids.
+//!
+//! A [`ParameterSweep`] expands a base [`VQSRecipe`] into one candidate per
+//! combination of the listed parameter values. [`TuningReport::compute`]
+//! builds the candidates in parallel, scores them and ranks them, best first.
+
+use crate::diagnostics::{PressureGradientDiagnostics, PressureGradientDiagnosticsError};
+use crate::recipe::{MasterGridsRecipe, RecipeError, StretchingRecipe, VQSRecipe};
+use crate::summary::{VgridSummary, VgridSummaryError};
+use rayon::prelude::*;
+use schismrs_hgrid::elements::{ElementsBuilder, ElementsBuilderError};
+use schismrs_hgrid::hgrid::{Hgrid, HgridBuilder, HgridBuilderError};
+use schismrs_hgrid::nodes::{NodesBuilder, NodesBuilderError};
+use std::fmt;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Values to try for each tunable option. An empty list keeps the value of
+/// the base recipe.
+///
+/// `ngrids` is the number of master grids of the auto mode and the number of
+/// clusters of the kmeans mode. `theta_f` only applies to the S transform and
+/// `theta_b` to the S and ROMS transforms.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParameterSweep {
+    pub ngrids: Vec<usize>,
+    pub shallow_levels: Vec<usize>,
+    pub max_levels: Vec<usize>,
+    pub theta_f: Vec<f64>,
+    pub theta_b: Vec<f64>,
+    pub a_vqs0: Vec<f64>,
+}
+
+impl ParameterSweep {
+    /// One recipe per combination of values, varying `a_vqs0` fastest.
+    ///
+    /// An unseeded KMeans base is seeded once, so that every candidate
+    /// clusters with the same seed and differs only by the swept values.
+    pub fn candidates(&self, base: &VQSRecipe) -> Result<Vec<VQSRecipe>, TuningError> {
+        let recipes = vec![base.seeded()];
+        let recipes = expand(recipes, &self.ngrids, set_ngrids)?;
+        let recipes = expand(recipes, &self.shallow_levels, set_shallow_levels)?;
+        let recipes = expand(recipes, &self.max_levels, set_max_levels)?;
+        let recipes = expand(recipes, &self.theta_f, set_theta_f)?;
+        let recipes = expand(recipes, &self.theta_b, set_theta_b)?;
+        expand(recipes, &self.a_vqs0, set_a_vqs0)
+    }
+}
+
+fn expand<T: Copy>(
+    recipes: Vec<VQSRecipe>,
+    values: &[T],
+    set: fn(&mut VQSRecipe, T) -> Result<(), TuningError>,
+) -> Result<Vec<VQSRecipe>, TuningError> {
+    if values.is_empty() {
+        return Ok(recipes);
+    }
+    let mut expanded = Vec::with_capacity(recipes.len() * values.len());
+    for recipe in recipes {
+        for &value in values {
+            let mut candidate = recipe.clone();
+            set(&mut candidate, value)?;
+            expanded.push(candidate);
+        }
+    }
+    Ok(expanded)
+}
+
+fn stretching_kind(stretching: &StretchingRecipe) -> &'static str {
+    match stretching {
+        StretchingRecipe::Quadratic(_) => "quadratic",
+        StretchingRecipe::S(_) => "s",
+        StretchingRecipe::Shchepetkin2005(_) => "shchepetkin2005",
+        StretchingRecipe::Geyer(_) => "geyer",
+        StretchingRecipe::Shchepetkin2010(_) => "shchepetkin2010",
+        StretchingRecipe::Souza(_) => "souza",
+        StretchingRecipe::Reconstructed(_) => "reconstructed",
+    }
+}
+
+fn set_ngrids(recipe: &mut VQSRecipe, value: usize) -> Result<(), TuningError> {
+    match &mut recipe.master_grids {
+        MasterGridsRecipe::Auto { ngrids, .. } => *ngrids = value,
+        MasterGridsRecipe::Kmeans { clusters, .. } => *clusters = value,
+        MasterGridsRecipe::Hsm { .. } => return Err(TuningError::NotApplicable("ngrids", "hsm")),
+    }
+    Ok(())
+}
+
+fn set_shallow_levels(recipe: &mut VQSRecipe, value: usize) -> Result<(), TuningError> {
+    match &mut recipe.master_grids {
+        MasterGridsRecipe::Auto { shallow_levels, .. }
+        | MasterGridsRecipe::Kmeans { shallow_levels, .. } => *shallow_levels = value,
+        MasterGridsRecipe::Hsm { .. } => {
+            return Err(TuningError::NotApplicable("shallow_levels", "hsm"))
+        }
+    }
+    Ok(())
+}
+
+fn set_max_levels(recipe: &mut VQSRecipe, value: usize) -> Result<(), TuningError> {
+    match &mut recipe.master_grids {
+        MasterGridsRecipe::Auto { max_levels, .. }
+        | MasterGridsRecipe::Kmeans { max_levels, .. } => *max_levels = Some(value),
+        MasterGridsRecipe::Hsm { .. } => {
+            return Err(TuningError::NotApplicable("max_levels", "hsm"))
+        }
+    }
+    Ok(())
+}
+
+fn set_theta_f(recipe: &mut VQSRecipe, value: f64) -> Result<(), TuningError> {
+    match &mut recipe.stretching {
+        StretchingRecipe::S(opts) => opts.theta_f = value,
+        other => {
+            return Err(TuningError::NotApplicable(
+                "theta_f",
+                stretching_kind(other),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn set_theta_b(recipe: &mut VQSRecipe, value: f64) -> Result<(), TuningError> {
+    match &mut recipe.stretching {
+        StretchingRecipe::S(opts) => opts.theta_b = value,
+        StretchingRecipe::Shchepetkin2005(opts)
+        | StretchingRecipe::Geyer(opts)
+        | StretchingRecipe::Shchepetkin2010(opts)
+        | StretchingRecipe::Souza(opts) => opts.theta_b = value,
+        other => {
+            return Err(TuningError::NotApplicable(
+                "theta_b",
+                stretching_kind(other),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn set_a_vqs0(recipe: &mut VQSRecipe, value: f64) -> Result<(), TuningError> {
+    match &mut recipe.stretching {
+        StretchingRecipe::Quadratic(opts) => opts.a_vqs0 = value,
+        StretchingRecipe::S(opts) => opts.a_vqs0 = value,
+        StretchingRecipe::Shchepetkin2005(opts)
+        | StretchingRecipe::Geyer(opts)
+        | StretchingRecipe::Shchepetkin2010(opts)
+        | StretchingRecipe::Souza(opts) => opts.a_vqs0 = value,
+        StretchingRecipe::Reconstructed(opts) => opts.a_vqs0 = value,
+    }
+    Ok(())
+}
+
+/// The tunable options of a candidate recipe, `None` where they do not
+/// apply.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CandidateParameters {
+    pub ngrids: Option<usize>,
+    pub shallow_levels: Option<usize>,
+    pub max_levels: Option<usize>,
+    pub theta_f: Option<f64>,
+    pub theta_b: Option<f64>,
+    pub a_vqs0: Option<f64>,
+}
+
+impl CandidateParameters {
+    pub fn from_recipe(recipe: &VQSRecipe) -> Self {
+        let mut parameters = Self::default();
+        match &recipe.master_grids {
+            MasterGridsRecipe::Auto {
+                ngrids,
+                shallow_levels,
+                max_levels,
+                ..
+            } => {
+                parameters.ngrids = Some(*ngrids);
+                parameters.shallow_levels = Some(*shallow_levels);
+                parameters.max_levels = *max_levels;
+            }
+            MasterGridsRecipe::Kmeans {
+                clusters,
+                shallow_levels,
+                max_levels,
+                ..
+            } => {
+                parameters.ngrids = Some(*clusters);
+                parameters.shallow_levels = Some(*shallow_levels);
+                parameters.max_levels = *max_levels;
+            }
+            MasterGridsRecipe::Hsm { .. } => {}
+        }
+        match &recipe.stretching {
+            StretchingRecipe::Quadratic(opts) => parameters.a_vqs0 = Some(opts.a_vqs0),
+            StretchingRecipe::S(opts) => {
+                parameters.theta_f = Some(opts.theta_f);
+                parameters.theta_b = Some(opts.theta_b);
+                parameters.a_vqs0 = Some(opts.a_vqs0);
+            }
+            StretchingRecipe::Shchepetkin2005(opts)
+            | StretchingRecipe::Geyer(opts)
+            | StretchingRecipe::Shchepetkin2010(opts)
+            | StretchingRecipe::Souza(opts) => {
+                parameters.theta_b = Some(opts.theta_b);
+                parameters.a_vqs0 = Some(opts.a_vqs0);
+            }
+            StretchingRecipe::Reconstructed(opts) => parameters.a_vqs0 = Some(opts.a_vqs0),
+        }
+        parameters
+    }
+
+    fn columns(&self) -> [String; 6] {
+        fn column<T: ToString>(value: Option<T>) -> String {
+            value.map(|value| value.to_string()).unwrap_or_default()
+        }
+        [
+            column(self.ngrids),
+            column(self.shallow_levels),
+            column(self.max_levels),
+            column(self.theta_f),
+            column(self.theta_b),
+            column(self.a_vqs0),
+        ]
+    }
+}
+
+/// Figures of merit of a built candidate, at eta = 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CandidateMetrics {
+    pub nvrt: usize,
+    pub nodes_3d: usize,
+    /// Thinnest bottom layer over the wet nodes.
+    pub min_bottom_thickness: f64,
+    /// Largest Haney number, 0 when it is undefined everywhere.
+    pub rx1_max: f64,
+    /// Mean surface layer thickness over the wet nodes.
+    pub surface_thickness: f64,
+}
+
+impl CandidateMetrics {
+    pub fn compute(hgrid: &Hgrid, recipe: &VQSRecipe) -> Result<Self, TuningError> {
+        let vqs = recipe.build(hgrid)?;
+        let summary = VgridSummary::compute(hgrid, &vqs)?;
+        let diagnostics = PressureGradientDiagnostics::compute(hgrid, &vqs)?;
+        Ok(Self {
+            nvrt: summary.nvrt,
+            nodes_3d: summary.nodes_3d,
+            min_bottom_thickness: summary.bottom_thickness.min,
+            rx1_max: diagnostics.rx1_max.map_or(0., |maximum| maximum.value),
+            surface_thickness: summary.surface_thickness.mean,
+        })
+    }
+
+    /// The metrics oriented so that lower is better.
+    fn costs(&self) -> [f64; 4] {
+        [
+            self.nodes_3d as f64,
+            -self.min_bottom_thickness,
+            self.rx1_max,
+            self.surface_thickness,
+        ]
+    }
+}
+
+/// Relative importance of the metrics in the [`Candidate::score`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreWeights {
+    pub nodes_3d: f64,
+    pub bottom_thickness: f64,
+    pub rx1: f64,
+    pub surface_thickness: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            nodes_3d: 1.,
+            bottom_thickness: 1.,
+            rx1: 1.,
+            surface_thickness: 1.,
+        }
+    }
+}
+
+impl ScoreWeights {
+    fn as_array(&self) -> [f64; 4] {
+        [
+            self.nodes_3d,
+            self.bottom_thickness,
+            self.rx1,
+            self.surface_thickness,
+        ]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub recipe: VQSRecipe,
+    pub parameters: CandidateParameters,
+    /// The metrics, or why the candidate could not be built.
+    pub metrics: Result<CandidateMetrics, String>,
+    /// Weighted mean of the metrics, each scaled to 0 for the best and 1 for
+    /// the worst candidate. Lower is better, `None` for failed candidates.
+    pub score: Option<f64>,
+}
+
+/// Candidates of a [`ParameterSweep`], best first and failed ones last.
+#[derive(Clone, Debug)]
+pub struct TuningReport {
+    pub candidates: Vec<Candidate>,
+}
+
+impl TuningReport {
+    /// Build and rank the candidates of `sweep` on `hgrid`.
+    ///
+    /// An [`Hgrid`] cannot be shared between threads, so every worker rebuilds
+    /// its own copy from the node and element tables. The copies have no CRS
+    /// or boundaries, which the candidate metrics do not use.
+    pub fn compute(
+        hgrid: &Hgrid,
+        base: &VQSRecipe,
+        sweep: &ParameterSweep,
+        weights: &ScoreWeights,
+    ) -> Result<Self, TuningError> {
+        let recipes = sweep.candidates(base)?;
+        let nodes = hgrid.nodes().hash_map().clone();
+        let elements = hgrid.elements().hash_map().clone();
+        let worker_hgrid = || -> Result<Hgrid, TuningError> {
+            let nodes = NodesBuilder::default()
+                .hash_map(nodes.clone())
+                .crs(None)
+                .build()
+                .map(Arc::new)?;
+            let elements = ElementsBuilder::default()
+                .hash_map(elements.clone())
+                .nodes(nodes.clone())
+                .build()?;
+            Ok(HgridBuilder::default()
+                .nodes(nodes)
+                .elements(elements)
+                .boundaries(None)
+                .description(None)
+                .build()?)
+        };
+        let mut candidates: Vec<Candidate> = recipes
+            .into_par_iter()
+            .map_init(worker_hgrid, |hgrid, recipe| {
+                let metrics = match hgrid {
+                    Ok(hgrid) => {
+                        CandidateMetrics::compute(hgrid, &recipe).map_err(|e| e.to_string())
+                    }
+                    Err(e) => Err(e.to_string()),
+                };
+                Candidate {
+                    parameters: CandidateParameters::from_recipe(&recipe),
+                    metrics,
+                    recipe,
+                    score: None,
+                }
+            })
+            .collect();
+
+        let costs: Vec<[f64; 4]> = candidates
+            .iter()
+            .filter_map(|candidate| candidate.metrics.as_ref().ok())
+            .map(|metrics| metrics.costs())
+            .collect();
+        let weights = weights.as_array();
+        let total_weight: f64 = weights.iter().sum();
+        for candidate in &mut candidates {
+            let Ok(metrics) = &candidate.metrics else {
+                continue;
+            };
+            let mut score = 0.;
+            for (m, cost) in metrics.costs().into_iter().enumerate() {
+                let (best, worst) = costs
+                    .iter()
+                    .map(|costs| costs[m])
+                    .filter(|cost| !cost.is_nan())
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), cost| {
+                        (lo.min(cost), hi.max(cost))
+                    });
+                // undefined metrics, e.g. without wet nodes, count as the worst
+                let scaled = if cost.is_nan() {
+                    1.
+                } else if worst > best {
+                    (cost - best) / (worst - best)
+                } else {
+                    0.
+                };
+                score += weights[m] * scaled;
+            }
+            candidate.score = Some(if total_weight > 0. {
+                score / total_weight
+            } else {
+                0.
+            });
+        }
+        // stable, so ties keep the sweep order
+        candidates.sort_by(|a, b| match (a.score, b.score) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        Ok(Self { candidates })
+    }
+
+    /// The best ranked candidate that could be built.
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates
+            .first()
+            .filter(|candidate| candidate.score.is_some())
+    }
+
+    fn header() -> [&'static str; 13] {
+        [
+            "rank",
+            "score",
+            "ngrids",
+            "shallow_levels",
+            "max_levels",
+            "theta_f",
+            "theta_b",
+            "a_vqs0",
+            "nvrt",
+            "nodes_3d",
+            "min_bottom_thickness",
+            "rx1_max",
+            "surface_thickness",
+        ]
+    }
+
+    /// Rank, score, parameters and metrics of every candidate as text.
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                let mut row = vec![
+                    candidate
+                        .score
+                        .map(|_| (i + 1).to_string())
+                        .unwrap_or_default(),
+                    candidate
+                        .score
+                        .map(|score| format!("{:.4}", score))
+                        .unwrap_or_default(),
+                ];
+                row.extend(candidate.parameters.columns());
+                match &candidate.metrics {
+                    Ok(metrics) => row.extend([
+                        metrics.nvrt.to_string(),
+                        metrics.nodes_3d.to_string(),
+                        format!("{:.4}", metrics.min_bottom_thickness),
+                        format!("{:.4}", metrics.rx1_max),
+                        format!("{:.4}", metrics.surface_thickness),
+                    ]),
+                    Err(_) => row.extend(vec![String::new(); 5]),
+                }
+                row
+            })
+            .collect()
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), TuningError> {
+        let mut wtr = csv::Writer::from_path(path)?;
+        let mut header = Self::header().to_vec();
+        header.push("error");
+        wtr.write_record(&header)?;
+        for (mut row, candidate) in self.rows().into_iter().zip(&self.candidates) {
+            row.push(candidate.metrics.clone().err().unwrap_or_default());
+            wtr.write_record(&row)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// A standalone HTML page with the ranking table. Clicking a column
+    /// header sorts the table by that column.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(HTML_HEAD);
+        html.push_str("<table>\n<thead><tr>");
+        for column in Self::header() {
+            let _ = write!(html, "<th>{}</th>", column);
+        }
+        html.push_str("<th>error</th></tr></thead>\n<tbody>\n");
+        for (i, (row, candidate)) in self.rows().iter().zip(&self.candidates).enumerate() {
+            let class = if i == 0 && candidate.score.is_some() {
+                " class=\"best\""
+            } else {
+                ""
+            };
+            let _ = write!(html, "<tr{}>", class);
+            for value in row {
+                let _ = write!(html, "<td>{}</td>", value);
+            }
+            let error = candidate
+                .metrics
+                .as_ref()
+                .err()
+                .cloned()
+                .unwrap_or_default();
+            let _ = writeln!(html, "<td>{}</td></tr>", escape_html(&error));
+        }
+        html.push_str("</tbody>\n</table>\n");
+        html.push_str(HTML_TAIL);
+        html
+    }
+
+    pub fn write_html(&self, path: &Path) -> Result<(), TuningError> {
+        std::fs::write(path, self.to_html())?;
+        Ok(())
+    }
+
+    /// Write CSV for a `.csv` extension and HTML otherwise.
+    pub fn write_to_file(&self, path: &Path) -> Result<(), TuningError> {
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+        {
+            self.write_csv(path)
+        } else {
+            self.write_html(path)
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>VQS candidate grids</title>
+<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; }
+th { background: #eee; cursor: pointer; }
+tr.best { background: #dfd; }
+</style>
+</head>
+<body>
+<h1>VQS candidate grids</h1>
+<p>Lower scores are better. Each metric is scaled from 0 for the best to 1 for
+the worst candidate: 3D node count, thinnest bottom layer, largest rx1 and
+mean surface layer thickness.</p>
+"#;
+
+const HTML_TAIL: &str = r#"<script>
+document.querySelectorAll("th").forEach((th, column) => {
+  th.addEventListener("click", () => {
+    const tbody = th.closest("table").querySelector("tbody");
+    const ascending = th.dataset.order !== "asc";
+    th.dataset.order = ascending ? "asc" : "desc";
+    const key = (row) => {
+      const text = row.children[column].textContent;
+      const value = parseFloat(text);
+      return isNaN(value) ? Infinity : value;
+    };
+    Array.from(tbody.rows)
+      .sort((a, b) => (ascending ? 1 : -1) * (key(a) - key(b)))
+      .forEach((row) => tbody.appendChild(row));
+  });
+});
+</script>
+</body>
+</html>
+"#;
+
+impl fmt::Display for TuningReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = Self::header();
+        let rows = self.rows();
+        let widths: Vec<usize> = (0..header.len())
+            .map(|c| {
+                rows.iter()
+                    .map(|row| row[c].len())
+                    .chain([header[c].len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |values: &mut dyn Iterator<Item = &str>| {
+            values
+                .zip(&widths)
+                .map(|(value, width)| format!("{:>width$}", value, width = width))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        writeln!(f, "{}", line(&mut header.into_iter()))?;
+        for (row, candidate) in rows.iter().zip(&self.candidates) {
+            let mut line = line(&mut row.iter().map(String::as_str));
+            if let Err(error) = &candidate.metrics {
+                line = format!("{} failed: {}", line, error);
+            }
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TuningError {
+    #[error("{0} cannot be tuned with {1}")]
+    NotApplicable(&'static str, &'static str),
+    #[error(transparent)]
+    NodesBuilderError(#[from] NodesBuilderError),
+    #[error(transparent)]
+    ElementsBuilderError(#[from] ElementsBuilderError),
+    #[error(transparent)]
+    HgridBuilderError(#[from] HgridBuilderError),
+    #[error(transparent)]
+    RecipeError(#[from] RecipeError),
+    #[error(transparent)]
+    VgridSummaryError(#[from] VgridSummaryError),
+    #[error(transparent)]
+    PressureGradientDiagnosticsError(#[from] PressureGradientDiagnosticsError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParameterSweep, ScoreWeights, TuningError, TuningReport};
+    use crate::recipe::{HsmStrategyRecipe, MasterGridsRecipe, StretchingRecipe, VQSRecipe};
+    use crate::test_support::sloping_hgrid;
+    use schismrs_hgrid::hgrid::Hgrid;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_tuning_report() {
+        let gr3 = Builder::new().suffix(".gr3").tempfile().unwrap();
+        sloping_hgrid(1.).write(gr3.path()).unwrap();
+        let hgrid = Hgrid::try_from(&gr3.path().to_path_buf()).unwrap();
+        let base = VQSRecipe {
+            stretching: StretchingRecipe::S(Default::default()),
+            master_grids: MasterGridsRecipe::Auto {
+                ngrids: 3,
+                initial_depth: 1.,
+                shallow_levels: 2,
+                max_levels: None,
+            },
+            dz_bottom_min: None,
+            max_level_jump: None,
+            dz_surface_target: None,
+            dz_max: None,
+            regions: None,
+            blending_width: None,
+        };
+        let sweep = ParameterSweep {
+            ngrids: vec![3, 5],
+            shallow_levels: vec![2, 4],
+            theta_b: vec![0., 1.],
+            ..Default::default()
+        };
+        let report =
+            TuningReport::compute(&hgrid, &base, &sweep, &ScoreWeights::default()).unwrap();
+        assert_eq!(report.candidates.len(), 8);
+        let scores: Vec<f64> = report
+            .candidates
+            .iter()
+            .map(|candidate| candidate.score.unwrap())
+            .collect();
+        assert!(scores.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(scores.iter().all(|score| (0. ..=1.).contains(score)));
+
+        // the coarsest candidate wins when only the node count matters
+        let weights = ScoreWeights {
+            nodes_3d: 1.,
+            bottom_thickness: 0.,
+            rx1: 0.,
+            surface_thickness: 0.,
+        };
+        let report = TuningReport::compute(&hgrid, &base, &sweep, &weights).unwrap();
+        let best = report.best().unwrap();
+        let fewest = report
+            .candidates
+            .iter()
+            .map(|candidate| candidate.metrics.as_ref().unwrap().nodes_3d)
+            .min()
+            .unwrap();
+        assert_eq!(best.metrics.as_ref().unwrap().nodes_3d, fewest);
+        assert_eq!(best.score, Some(0.));
+        assert_eq!(best.parameters.shallow_levels, Some(2));
+        let rebuilt = best.recipe.build(&hgrid).unwrap();
+        assert_eq!(rebuilt.nvrt(), best.metrics.as_ref().unwrap().nvrt);
+
+        let csv = Builder::new().suffix(".csv").tempfile().unwrap();
+        report.write_to_file(csv.path()).unwrap();
+        let lines = std::fs::read_to_string(csv.path()).unwrap();
+        assert_eq!(lines.lines().count(), 9);
+        assert!(lines.starts_with("rank,score,ngrids"));
+        assert!(report.to_html().contains("<tr class=\"best\">"));
+
+        let theta_f = ParameterSweep {
+            theta_f: vec![0.1],
+            ..Default::default()
+        };
+        let quadratic = VQSRecipe {
+            stretching: StretchingRecipe::Quadratic(Default::default()),
+            ..base
+        };
+        assert!(matches!(
+            theta_f.candidates(&quadratic),
+            Err(TuningError::NotApplicable("theta_f", "quadratic"))
+        ));
+    }
+
+    #[test]
+    fn test_unseeded_kmeans_candidates_share_a_seed() {
+        let base = VQSRecipe {
+            stretching: StretchingRecipe::S(Default::default()),
+            master_grids: MasterGridsRecipe::Kmeans {
+                clusters: 3,
+                strategy: HsmStrategyRecipe::Kmeans,
+                seed: None,
+                initial_depth: 1.,
+                shallow_levels: 2,
+                max_levels: None,
+            },
+            dz_bottom_min: None,
+            max_level_jump: None,
+            dz_surface_target: None,
+            dz_max: None,
+            regions: None,
+            blending_width: None,
+        };
+        let sweep = ParameterSweep {
+            shallow_levels: vec![2, 4],
+            theta_b: vec![0., 1.],
+            ..Default::default()
+        };
+        let seeds: Vec<Option<u64>> = sweep
+            .candidates(&base)
+            .unwrap()
+            .iter()
+            .map(|candidate| match candidate.master_grids {
+                MasterGridsRecipe::Kmeans { seed, .. } => seed,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(seeds.len(), 4);
+        assert!(seeds[0].is_some());
+        assert!(seeds.iter().all(|seed| *seed == seeds[0]));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::sz::{SZLoadError, SZ};
+use crate::vqs::{VQSLoadError, VQS};
+use ndarray::{s, Array1, Array2, Axis};
+use schismrs_hgrid::hgrid::Hgrid;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use thiserror::Error;
+
+/// Common interface of the SCHISM vertical grid types.
+///
+/// Levels are numbered from 1 (bottom-most) to `nvrt` (surface) as in SCHISM.
+/// Depths are still water depths, positive down, and `eta` is the surface
+/// elevation, positive up.
+pub trait VerticalGrid {
+    fn ivcor(&self) -> usize;
+    fn nvrt(&self) -> usize;
+
+    /// Index of the bottom level (SCHISM's `kbp`) at `node`, a 0-based index
+    /// into the hgrid nodes.
+    fn bottom_level_index(&self, node: usize, depth: f64) -> usize;
+
+    /// Elevations of the `nvrt` levels at `node`. Levels below the bottom are NaN.
+    fn zcor_at_node(&self, node: usize, depth: f64, eta: f64) -> Array1<f64>;
+
+    fn write_to_file(&self, filename: &Path) -> std::io::Result<()>;
+
+    /// Number of hgrid nodes the grid was built for, or `None` when it
+    /// applies to any hgrid.
+    fn node_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Elevations of every level at every node (nvrt × np) with SCHISM's
+    /// default minimum depth `h0`. `eta` defaults to 0 everywhere.
+    fn zcor(&self, hgrid: &Hgrid, eta: Option<&Array1<f64>>) -> Result<Array2<f64>, ZcorError> {
+        self.zcor_with_h0(hgrid, eta, DEFAULT_H0)
+    }
+
+    /// Like [`VerticalGrid::zcor`], treating nodes whose total water depth
+    /// `eta + depth` is at most `h0` as dry. As in SCHISM, the levels of a dry
+    /// node all sit on the bottom. Levels below the bottom are NaN.
+    fn zcor_with_h0(
+        &self,
+        hgrid: &Hgrid,
+        eta: Option<&Array1<f64>>,
+        h0: f64,
+    ) -> Result<Array2<f64>, ZcorError> {
+        // hgrid depths are stored negative below the datum
+        let depths = hgrid.depths().mapv(|depth| -depth);
+        let np = depths.len();
+        if let Some(node_count) = self.node_count() {
+            if node_count != np {
+                return Err(ZcorError::NodeCountMismatch(np, node_count));
+            }
+        }
+        if let Some(eta) = eta {
+            if eta.len() != np {
+                return Err(ZcorError::EtaLengthMismatch(np, eta.len()));
+            }
+        }
+        let mut zcor = Array2::from_elem((self.nvrt(), np), f64::NAN);
+        for (node, mut column) in zcor.axis_iter_mut(Axis(1)).enumerate() {
+            let eta = eta.map_or(0., |eta| eta[node]);
+            column.assign(&self.zcor_at_node_with_h0(node, depths[node], eta, h0));
+        }
+        Ok(zcor)
+    }
+
+    /// Like [`VerticalGrid::zcor_at_node`], with the levels of a node whose
+    /// total water depth is at most `h0` all on the bottom.
+    fn zcor_at_node_with_h0(&self, node: usize, depth: f64, eta: f64, h0: f64) -> Array1<f64> {
+        if eta + depth > h0 {
+            return self.zcor_at_node(node, depth, eta);
+        }
+        let mut column = Array1::from_elem(self.nvrt(), f64::NAN);
+        let kbp = self.bottom_level_index(node, depth);
+        column.slice_mut(s![kbp - 1..]).fill(-depth);
+        column
+    }
+}
+
+/// SCHISM's default minimum water depth (`h0` in param.nml).
+pub const DEFAULT_H0: f64 = 0.01;
+
+/// Load a vgrid.in of either kind, dispatching on its ivcor value.
+pub fn load_vertical_grid(
+    hgrid: &Hgrid,
+    path: &Path,
+) -> Result<Box<dyn VerticalGrid>, VerticalGridLoadError> {
+    let mut line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut line)?;
+    let ivcor = line
+        .split('!')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .parse::<i32>()
+        .map_err(|_| VerticalGridLoadError::InvalidIvcor(line.trim().to_string()))?;
+    match ivcor {
+        1 => Ok(Box::new(VQS::try_from_file(hgrid, path)?)),
+        2 => Ok(Box::new(SZ::try_from_file(path)?)),
+        _ => Err(VerticalGridLoadError::UnsupportedIvcor(ivcor)),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VerticalGridLoadError {
+    #[error("File IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Failed to parse ivcor from '{0}'")]
+    InvalidIvcor(String),
+    #[error("Unsupported ivcor value: {0} (expected 1 or 2)")]
+    UnsupportedIvcor(i32),
+    #[error(transparent)]
+    VQSLoadError(#[from] VQSLoadError),
+    #[error(transparent)]
+    SZLoadError(#[from] SZLoadError),
+}
+
+#[derive(Error, Debug)]
+pub enum ZcorError {
+    #[error("Node count mismatch: hgrid has {0} nodes but vgrid has {1}")]
+    NodeCountMismatch(usize, usize),
+    #[error("eta must have one value per node ({0}) but has {1}")]
+    EtaLengthMismatch(usize, usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_vertical_grid, VerticalGrid, VerticalGridLoadError, ZcorError};
+    use crate::test_support::sloping_hgrid;
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vqs::VQSBuilder;
+    use schismrs_hgrid::mesh_generator::{Bathymetry, MeshGeneratorBuilder, SizeFunction};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_load_sz_through_trait() {
+        let hgrid = MeshGeneratorBuilder::default()
+            .outer(vec![(0., 0.), (4., 0.), (4., 4.), (0., 4.)])
+            .size_function(SizeFunction::Constant(1.))
+            .bathymetry(Some(Bathymetry::Constant(500.)))
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap();
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "2\n5 3 100.\nZ levels\n1 -1000.\n2 -300.\n3 -100.\n\
+             S levels\n10. 0. 1e-6\n3 -1.\n4 -0.5\n5 0.\n"
+        )
+        .unwrap();
+        let vgrid = load_vertical_grid(&hgrid, file.path()).unwrap();
+        assert_eq!((vgrid.ivcor(), vgrid.nvrt()), (2, 5));
+
+        // deeper than h_s: the bottom falls between the first two Z levels
+        assert_eq!(vgrid.bottom_level_index(0, 500.), 1);
+        let zcor = vgrid.zcor_at_node(0, 500., 1.);
+        assert_eq!(zcor[0], -500.);
+        assert_eq!(zcor[1], -300.);
+        assert!((zcor[2] + 100.).abs() < 1e-9);
+        assert!((zcor[4] - 1.).abs() < 1e-9);
+
+        // shallower than h_s: only the S levels are used
+        assert_eq!(vgrid.bottom_level_index(0, 50.), 3);
+        let zcor = vgrid.zcor_at_node(0, 50., 0.);
+        assert!(zcor[0].is_nan() && zcor[1].is_nan());
+        assert!((zcor[2] + 50.).abs() < 1e-9);
+
+        let zcor = vgrid.zcor(&hgrid, None).unwrap();
+        assert_eq!(zcor.dim(), (5, hgrid.nodes().len()));
+        assert_eq!(
+            zcor.column(0).to_vec(),
+            vgrid.zcor_at_node(0, 500., 0.).to_vec()
+        );
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "3").unwrap();
+        assert!(matches!(
+            load_vertical_grid(&hgrid, file.path()),
+            Err(VerticalGridLoadError::UnsupportedIvcor(3))
+        ));
+    }
+
+    #[test]
+    fn test_vqs_zcor_with_dry_node() {
+        let hgrid = sloping_hgrid(2.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 14];
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+
+        let depths = hgrid.depths().mapv(|depth| -depth);
+        let mut eta = depths.mapv(|_| 0.5);
+        eta[0] = -depths[0];
+        let zcor = vqs.zcor(&hgrid, Some(&eta)).unwrap();
+        let nvrt = vqs.nvrt();
+        for (node, column) in zcor.columns().into_iter().enumerate() {
+            let kbp = vqs.bottom_level_index(node, depths[node]);
+            assert!(column.iter().take(kbp - 1).all(|z| z.is_nan()));
+            assert!((column[kbp - 1] + depths[node]).abs() < 1e-4);
+            if node == 0 {
+                assert!(column.iter().skip(kbp - 1).all(|&z| z == -depths[0]));
+            } else {
+                assert!((column[nvrt - 1] - 0.5).abs() < 1e-12);
+                assert!(column
+                    .iter()
+                    .skip(kbp - 1)
+                    .zip(column.iter().skip(kbp))
+                    .all(|(a, b)| a < b));
+            }
+        }
+        assert!(matches!(
+            vqs.zcor(&hgrid, Some(&eta.slice(ndarray::s![1..]).to_owned())),
+            Err(ZcorError::EtaLengthMismatch(_, _))
+        ));
+    }
+}
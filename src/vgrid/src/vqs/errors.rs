@@ -3,8 +3,10 @@
 use crate::transforms::quadratic::QuadraticTransformBuilderError;
 use crate::transforms::s::STransformBuilderError;
 use crate::transforms::transforms::StretchingFunctionError;
+use crate::hsm::HsmStrategyError;
 use crate::KMeansHSMCreateError;
 use ndarray::Array1;
+use schismrs_hgrid::gr3::Gr3ParserOutputBuilderError;
 use ndarray_stats::errors::MinMaxError;
 use thiserror::Error;
 
@@ -28,6 +30,64 @@ pub enum VQSBuilderError {
     StretchingFunctionError(#[from] StretchingFunctionError),
     #[error("{0}")]
     InvalidBottomIndices(String),
+    #[error(transparent)]
+    LevelSmoothingError(#[from] LevelSmoothingError),
+    #[error(transparent)]
+    LayerThicknessError(#[from] LayerThicknessError),
+    #[error(transparent)]
+    RegionError(#[from] RegionError),
+}
+
+#[derive(Error, Debug)]
+pub enum RegionError {
+    #[error("Region {0} has {1} vertices, a polygon needs at least 3")]
+    InvalidPolygon(usize, usize),
+    #[error("Region {0} must set both depths and nlevels, or neither")]
+    IncompleteMasterGrids(usize),
+    #[error("blending_width must be >= 0, but got {0}")]
+    InvalidBlendingWidth(f64),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    Gr3ParserOutputBuilderError(#[from] Gr3ParserOutputBuilderError),
+}
+
+#[derive(Error, Debug)]
+pub enum LayerThicknessError {
+    #[error("dz_surface_target must be > 0, but got {0}")]
+    InvalidDzSurfaceTarget(f64),
+    #[error("dz_max must be > 0, but got {0}")]
+    InvalidDzMax(f64),
+    #[error("dz_surface_target ({0}) must not be larger than dz_max ({1})")]
+    SurfaceTargetAboveDzMax(f64, f64),
+    #[error("Node id {0} with depth {1} has {2} levels, too few to keep every layer under dz_max={3}. Use more levels in the deep master grids or a larger dz_max")]
+    TooFewLevels(usize, f64, usize, f64),
+    #[error("Node id {0} with depth {1} cannot keep its bottom layer at least dz_bottom_min={2} thick under the other layer thickness constraints")]
+    BottomLayerTooThin(usize, f64, f64),
+    #[error("Node id {0} with depth {1} has no layers left to take up the rest of the water column under dz_surface_target={2}")]
+    SurfaceTargetTooThick(usize, f64, f64),
+    #[error("Node count mismatch: hgrid has {0} nodes but vgrid has {1}")]
+    NodeCountMismatch(usize, usize),
+}
+
+#[derive(Error, Debug)]
+pub enum RoundTripError {
+    #[error("Node count mismatch: original vgrid has {0} nodes but regenerated vgrid has {1}")]
+    NodeCountMismatch(usize, usize),
+    #[error(transparent)]
+    VQSBuilderError(#[from] VQSBuilderError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum LevelSmoothingError {
+    #[error("max_jump must be >= 1")]
+    InvalidMaxJump,
+    #[error("Node count mismatch: hgrid has {0} nodes but vgrid has {1}")]
+    NodeCountMismatch(usize, usize),
 }
 
 #[derive(Error, Debug)]
@@ -38,6 +98,8 @@ pub enum VQSKMeansBuilderError {
     VQSBuilderError(#[from] VQSBuilderError),
     #[error(transparent)]
     KMeansHSMCreateError(#[from] KMeansHSMCreateError),
+    #[error(transparent)]
+    HsmStrategyError(#[from] HsmStrategyError),
     #[error("shallow_levels must be >= 2")]
     InvalidShallowLevels,
     #[error("max_levels must be > shallow_levels but got max_levels={1}, shallow_levels={0}")]
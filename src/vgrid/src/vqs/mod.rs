@@ -4,14 +4,22 @@ mod vqs;
 mod vqs_builder;
 mod vqs_kmeans_builder;
 mod vqs_auto_builder;
+mod verification;
+mod regions;
 mod errors;
 
-pub use vqs::{VQS, IterLevelValues};
+pub use vqs::{VQS, IterLevelValues, VgridFormat};
 pub use vqs_builder::VQSBuilder;
 pub use vqs_kmeans_builder::VQSKMeansBuilder;
 pub use vqs_auto_builder::VQSAutoBuilder;
+pub use regions::{RegionAssignment, VQSRegion};
+pub use verification::{ColumnDifference, RoundTripReport, DEFAULT_SIGMA_TOLERANCE};
 pub use errors::{
     VQSBuilderError, 
+    LevelSmoothingError,
+    LayerThicknessError,
+    RegionError,
+    RoundTripError,
     VQSKMeansBuilderError, 
     VQSAutoBuilderError,
     ReconstructionError,
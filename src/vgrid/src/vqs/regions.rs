@@ -0,0 +1,350 @@
+// schismrs-vgrid/src/vqs/regions.rs
+
+use super::errors::RegionError;
+use crate::transforms::StretchingFunction;
+use ndarray::Array2;
+use schismrs_hgrid::gr3::{write_to_path, Gr3ParserOutputBuilder};
+use schismrs_hgrid::hgrid::Hgrid;
+use std::path::Path;
+
+/// A part of the domain, e.g. an estuary, with its own master grids and/or
+/// stretching function. Whatever is left unset is taken from the
+/// [`VQSBuilder`](super::VQSBuilder) the region is passed to.
+#[derive(Clone, Copy, Debug)]
+pub struct VQSRegion<'a> {
+    /// Vertices in hgrid coordinates. The polygon is closed implicitly.
+    pub polygon: &'a [(f64, f64)],
+    pub depths: Option<&'a Vec<f64>>,
+    pub nlevels: Option<&'a Vec<usize>>,
+    pub stretching: Option<&'a StretchingFunction<'a>>,
+}
+
+impl<'a> VQSRegion<'a> {
+    pub fn new(polygon: &'a [(f64, f64)]) -> Self {
+        Self {
+            polygon,
+            depths: None,
+            nlevels: None,
+            stretching: None,
+        }
+    }
+
+    pub(crate) fn validate(&self, region: usize) -> Result<(), RegionError> {
+        if self.polygon.len() < 3 {
+            return Err(RegionError::InvalidPolygon(region, self.polygon.len()));
+        }
+        if self.depths.is_some() != self.nlevels.is_some() {
+            return Err(RegionError::IncompleteMasterGrids(region));
+        }
+        Ok(())
+    }
+
+    /// Distance from (x, y) to the polygon outline, positive inside.
+    fn signed_distance(&self, x: f64, y: f64) -> f64 {
+        let n = self.polygon.len();
+        let mut inside = false;
+        let mut distance = f64::INFINITY;
+        for i in 0..n {
+            let (x0, y0) = self.polygon[i];
+            let (x1, y1) = self.polygon[(i + 1) % n];
+            if (y0 > y) != (y1 > y) && x < x0 + (y - y0) * (x1 - x0) / (y1 - y0) {
+                inside = !inside;
+            }
+            let (dx, dy) = (x1 - x0, y1 - y0);
+            let length_squared = dx * dx + dy * dy;
+            let t = if length_squared > 0. {
+                (((x - x0) * dx + (y - y0) * dy) / length_squared).clamp(0., 1.)
+            } else {
+                0.
+            };
+            distance = distance.min((x - x0 - t * dx).hypot(y - y0 - t * dy));
+        }
+        if inside || distance == 0. {
+            distance
+        } else {
+            -distance
+        }
+    }
+}
+
+/// Which region each node of an hgrid falls in, and how much of that
+/// region's grid it takes.
+///
+/// Region indices count from 1 in the order the regions were given, 0 is
+/// the rest of the domain. Where regions overlap the first one wins. The
+/// blend runs across a band `blending_width` wide centred on each region
+/// outline: the weight of a region is 0.5 on its outline and reaches 1 and 0
+/// half the width inside and outside of it. A node only blends with the
+/// region of largest weight.
+#[derive(Clone, Debug)]
+pub struct RegionAssignment {
+    region: Vec<usize>,
+    blend_region: Vec<usize>,
+    weight: Vec<f64>,
+}
+
+impl RegionAssignment {
+    pub fn new(
+        hgrid: &Hgrid,
+        regions: &[VQSRegion],
+        blending_width: f64,
+    ) -> Result<Self, RegionError> {
+        if blending_width.is_nan() || blending_width < 0. {
+            return Err(RegionError::InvalidBlendingWidth(blending_width));
+        }
+        for (i, region) in regions.iter().enumerate() {
+            region.validate(i + 1)?;
+        }
+        let (xs, ys) = (hgrid.x(), hgrid.y());
+        let np = xs.len();
+        let mut assignment = Self {
+            region: vec![0; np],
+            blend_region: vec![0; np],
+            weight: vec![0.; np],
+        };
+        for node in 0..np {
+            for (i, region) in regions.iter().enumerate() {
+                let distance = region.signed_distance(xs[node], ys[node]);
+                if distance >= 0. && assignment.region[node] == 0 {
+                    assignment.region[node] = i + 1;
+                }
+                let weight = if blending_width > 0. {
+                    (0.5 + distance / blending_width).clamp(0., 1.)
+                } else if distance >= 0. {
+                    1.
+                } else {
+                    0.
+                };
+                if weight > assignment.weight[node] {
+                    assignment.blend_region[node] = i + 1;
+                    assignment.weight[node] = weight;
+                }
+            }
+        }
+        Ok(assignment)
+    }
+
+    /// Region of each node, 0 outside of all regions.
+    pub fn region(&self) -> &[usize] {
+        &self.region
+    }
+
+    /// Region each node blends with, 0 for none.
+    pub fn blend_region(&self) -> &[usize] {
+        &self.blend_region
+    }
+
+    /// Weight of [`RegionAssignment::blend_region`] at each node, from 0
+    /// (rest of the domain only) to 1 (region only).
+    pub fn weight(&self) -> &[f64] {
+        &self.weight
+    }
+
+    /// Write the region index of every node as a gr3.
+    pub fn write_gr3(&self, hgrid: &Hgrid, path: &Path) -> Result<(), RegionError> {
+        let mut nodes = hgrid.nodes().hash_map().clone();
+        for ((_, (_, values)), region) in nodes.iter_mut().zip(&self.region) {
+            *values = Some(vec![*region as f64]);
+        }
+        let gr3 = Gr3ParserOutputBuilder::default()
+            .description(Some("vqs regions".to_string()))
+            .crs(hgrid.crs())
+            .nodes(nodes)
+            .elements(Some(hgrid.elements().hash_map().clone()))
+            .open_boundaries(Vec::new())
+            .land_boundaries(Vec::new())
+            .interior_boundaries(Vec::new())
+            .build()?;
+        write_to_path(path, &gr3)?;
+        Ok(())
+    }
+}
+
+/// Bottom-up sigma profile of `node` in an array laid out like
+/// [`VQS::sigma`](super::VQS::sigma), with `levels` levels.
+fn profile(sigma: &Array2<f64>, node: usize, levels: usize) -> Vec<f64> {
+    sigma
+        .column(node)
+        .iter()
+        .skip(sigma.nrows() - levels)
+        .cloned()
+        .collect()
+}
+
+/// `profile` resampled onto `levels` levels by its fractional index.
+fn resample(profile: &[f64], levels: usize) -> Vec<f64> {
+    let old = profile.len();
+    (0..levels)
+        .map(|j| {
+            let position = (j * (old - 1)) as f64 / (levels - 1) as f64;
+            let lower = (position.floor() as usize).min(old - 2);
+            let weight = position - lower as f64;
+            profile[lower] + weight * (profile[lower + 1] - profile[lower])
+        })
+        .collect()
+}
+
+/// Combine the columns of the rest of the domain (`base`) with those of each
+/// region following `assignment`. Both level counts and sigma values are
+/// blended, so the number of levels changes gradually across the blending
+/// band. Returns the sigma array, z coordinates (surface first) and level
+/// counts in the layout used by [`VQS::new`](super::VQS::new).
+pub(crate) fn blend_columns(
+    hgrid: &Hgrid,
+    assignment: &RegionAssignment,
+    base: (&Array2<f64>, &[usize]),
+    regions: &[(Array2<f64>, Vec<usize>)],
+    etal: f64,
+) -> (Array2<f64>, Array2<f64>, Vec<usize>) {
+    let np = base.1.len();
+    let profiles: Vec<Vec<f64>> = (0..np)
+        .map(|node| {
+            let (region, weight) = (assignment.blend_region[node], assignment.weight[node]);
+            let base_levels = base.1[node];
+            if region == 0 || weight <= 0. {
+                return profile(base.0, node, base_levels);
+            }
+            let (sigma, kbp) = &regions[region - 1];
+            let region_levels = kbp[node];
+            if weight >= 1. || base_levels < 2 {
+                return profile(sigma, node, region_levels);
+            }
+            if region_levels < 2 {
+                return profile(base.0, node, base_levels);
+            }
+            let levels = ((1. - weight) * base_levels as f64 + weight * region_levels as f64)
+                .round()
+                .max(2.) as usize;
+            let from_base = resample(&profile(base.0, node, base_levels), levels);
+            let from_region = resample(&profile(sigma, node, region_levels), levels);
+            from_base
+                .iter()
+                .zip(&from_region)
+                .map(|(b, r)| (1. - weight) * b + weight * r)
+                .collect()
+        })
+        .collect();
+
+    let nvrt = profiles
+        .iter()
+        .map(|profile| profile.len())
+        .max()
+        .unwrap_or(0);
+    let depths = hgrid.depths();
+    let mut sigma = Array2::from_elem((nvrt, np), -9.);
+    let mut znd = Array2::from_elem((nvrt, np), f64::NAN);
+    let mut kbp = vec![0; np];
+    for (node, profile) in profiles.iter().enumerate() {
+        let levels = profile.len();
+        kbp[node] = levels;
+        for (j, &value) in profile.iter().enumerate() {
+            sigma[[nvrt - levels + j, node]] = value;
+            // znd runs from the surface down
+            znd[[levels - 1 - j, node]] = etal + (etal - depths[node]) * value;
+        }
+    }
+    (sigma, znd, kbp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VQSRegion;
+    use crate::test_support::sloping_hgrid;
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vqs::{RegionError, VQSBuilder, VQSBuilderError};
+    use crate::VerticalGrid;
+    use schismrs_hgrid::hgrid::Hgrid;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_regions_with_blending() {
+        let hgrid = sloping_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 14];
+        let fine_nlevels = vec![6, 12, 20, 28];
+        let polygon = [(-100., -100.), (4., -100.), (4., 100.), (-100., 100.)];
+        let regions = [VQSRegion {
+            nlevels: Some(&fine_nlevels),
+            depths: Some(&depths),
+            ..VQSRegion::new(&polygon)
+        }];
+        let build = |nlevels: &Vec<usize>, regions: Option<&[VQSRegion]>| {
+            let mut builder = VQSBuilder::default();
+            builder
+                .hgrid(&hgrid)
+                .depths(&depths)
+                .nlevels(nlevels)
+                .stretching(&stretching)
+                .dz_bottom_min(&0.1)
+                .blending_width(&4.);
+            if let Some(regions) = regions {
+                builder.regions(regions);
+            }
+            (
+                builder.build().unwrap(),
+                builder.region_assignment().unwrap(),
+            )
+        };
+        let (coarse, _) = build(&nlevels, None);
+        let (fine, _) = build(&fine_nlevels, None);
+        let (blended, assignment) = build(&nlevels, Some(&regions));
+        assert!(coarse.nvrt() < blended.nvrt() && blended.nvrt() <= fine.nvrt());
+
+        let levels =
+            |vqs: &crate::vqs::VQS, node: usize| vqs.nvrt() + 1 - vqs.bottom_level_index(node, 0.);
+        let (xs, bathymetry) = (hgrid.x(), hgrid.depths());
+        let mut blended_nodes = 0;
+        for node in 0..xs.len() {
+            let weight = assignment.weight()[node];
+            if (xs[node] - 4.).abs() > 1e-6 {
+                assert_eq!(assignment.region()[node] == 1, xs[node] < 4.);
+            }
+            assert!((weight - (0.5 + (4. - xs[node]) / 4.).clamp(0., 1.)).abs() < 1e-9);
+            let (n, coarse, fine) = (
+                levels(&blended, node),
+                levels(&coarse, node),
+                levels(&fine, node),
+            );
+            if weight == 0. {
+                assert_eq!(n, coarse);
+            } else if weight == 1. {
+                assert_eq!(n, fine);
+            } else {
+                blended_nodes += 1;
+                assert!(coarse.min(fine) <= n && n <= coarse.max(fine));
+            }
+            let zcor = blended.zcor_at_node(node, -bathymetry[node], 0.);
+            let z: Vec<f64> = zcor.iter().cloned().filter(|z| !z.is_nan()).collect();
+            assert!((z[0] + bathymetry[node].abs()).abs() < 1e-9);
+            assert!(z.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        assert!(blended_nodes > 0);
+
+        let gr3 = NamedTempFile::new().unwrap();
+        assignment.write_gr3(&hgrid, gr3.path()).unwrap();
+        let reloaded = Hgrid::try_from(&gr3.path().to_path_buf()).unwrap();
+        let exported: Vec<usize> = reloaded.depths().iter().map(|v| v.abs() as usize).collect();
+        assert_eq!(exported, assignment.region());
+
+        let line = [(0., 0.), (1., 1.)];
+        let result = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .regions(&[VQSRegion::new(&line)])
+            .build();
+        assert!(matches!(
+            result,
+            Err(VQSBuilderError::RegionError(RegionError::InvalidPolygon(
+                1, 2
+            )))
+        ));
+    }
+}
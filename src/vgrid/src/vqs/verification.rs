@@ -0,0 +1,334 @@
+// schismrs-vgrid/src/vqs/verification.rs
+
+use super::errors::RoundTripError;
+use super::vqs::VQS;
+use super::vqs_builder::VQSBuilder;
+use crate::transforms::StretchingFunction;
+use schismrs_hgrid::hgrid::Hgrid;
+use std::fmt;
+use std::path::Path;
+
+/// vgrid.in stores sigma with 6 decimals, so a column read back from a file
+/// differs from the generated one by up to 5e-7.
+pub const DEFAULT_SIGMA_TOLERANCE: f64 = 1e-6;
+
+/// A node whose column differs between two VQS grids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDifference {
+    /// 0-based index into the hgrid nodes.
+    pub node: usize,
+    pub original_levels: usize,
+    pub regenerated_levels: usize,
+    /// Largest sigma difference, only defined when both columns have the same
+    /// number of levels.
+    pub max_sigma_error: Option<f64>,
+}
+
+/// Node-by-node comparison of an original VQS and a regenerated one.
+#[derive(Debug, Clone)]
+pub struct RoundTripReport {
+    pub node_count: usize,
+    pub identical_columns: usize,
+    /// Largest sigma difference over the columns with matching level counts.
+    pub max_sigma_error: f64,
+    pub tolerance: f64,
+    pub differences: Vec<ColumnDifference>,
+}
+
+impl RoundTripReport {
+    pub fn identical_fraction(&self) -> f64 {
+        if self.node_count == 0 {
+            return 1.;
+        }
+        self.identical_columns as f64 / self.node_count as f64
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), RoundTripError> {
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record([
+            "node",
+            "original_levels",
+            "regenerated_levels",
+            "max_sigma_error",
+        ])?;
+        for difference in &self.differences {
+            wtr.write_record(&[
+                (difference.node + 1).to_string(),
+                difference.original_levels.to_string(),
+                difference.regenerated_levels.to_string(),
+                difference
+                    .max_sigma_error
+                    .map(|error| format!("{:e}", error))
+                    .unwrap_or_default(),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for RoundTripReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level_mismatches = self
+            .differences
+            .iter()
+            .filter(|difference| difference.max_sigma_error.is_none())
+            .count();
+        writeln!(
+            f,
+            "Identical columns: {} / {} ({:.2}%)",
+            self.identical_columns,
+            self.node_count,
+            100. * self.identical_fraction()
+        )?;
+        writeln!(f, "Max sigma error: {:.3e}", self.max_sigma_error)?;
+        writeln!(f, "Level count mismatches: {}", level_mismatches)?;
+        writeln!(
+            f,
+            "Sigma mismatches (> {:.1e}): {}",
+            self.tolerance,
+            self.differences.len() - level_mismatches
+        )?;
+        if !self.differences.is_empty() {
+            writeln!(
+                f,
+                "{:>10} {:>10} {:>12} {:>12}",
+                "node", "levels", "regenerated", "sigma error"
+            )?;
+            for difference in &self.differences {
+                let error = difference
+                    .max_sigma_error
+                    .map(|error| format!("{:.3e}", error))
+                    .unwrap_or_else(|| "-".to_string());
+                writeln!(
+                    f,
+                    "{:>10} {:>10} {:>12} {:>12}",
+                    difference.node + 1,
+                    difference.original_levels,
+                    difference.regenerated_levels,
+                    error
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VQS {
+    /// Compare the columns of two grids as they would be written to vgrid.in.
+    ///
+    /// Columns are aligned at the surface, so grids with a different `nvrt`
+    /// can still match. A column is identical when both grids have the same
+    /// number of levels at the node and no sigma value differs by more than
+    /// `tolerance`.
+    pub fn compare(&self, other: &VQS, tolerance: f64) -> Result<RoundTripReport, RoundTripError> {
+        let original_bottoms = self.bottom_level_indices();
+        let regenerated_bottoms = other.bottom_level_indices();
+        if original_bottoms.len() != regenerated_bottoms.len() {
+            return Err(RoundTripError::NodeCountMismatch(
+                original_bottoms.len(),
+                regenerated_bottoms.len(),
+            ));
+        }
+        let (original_nvrt, regenerated_nvrt) = (self.nvrt(), other.nvrt());
+        let mut differences = Vec::new();
+        let mut max_sigma_error = 0f64;
+        for (node, (original_bottom, regenerated_bottom)) in original_bottoms
+            .into_iter()
+            .zip(regenerated_bottoms)
+            .enumerate()
+        {
+            let original_levels = original_nvrt + 1 - original_bottom;
+            let regenerated_levels = regenerated_nvrt + 1 - regenerated_bottom;
+            if original_levels != regenerated_levels {
+                differences.push(ColumnDifference {
+                    node,
+                    original_levels,
+                    regenerated_levels,
+                    max_sigma_error: None,
+                });
+                continue;
+            }
+            let node_error = (1..=original_levels)
+                .map(|k| {
+                    let original = self.sigma()[[original_nvrt - k, node]];
+                    let regenerated = other.sigma()[[regenerated_nvrt - k, node]];
+                    (original - regenerated).abs()
+                })
+                .fold(0f64, f64::max);
+            max_sigma_error = max_sigma_error.max(node_error);
+            if node_error > tolerance {
+                differences.push(ColumnDifference {
+                    node,
+                    original_levels,
+                    regenerated_levels,
+                    max_sigma_error: Some(node_error),
+                });
+            }
+        }
+        let node_count = self.sigma().ncols();
+        Ok(RoundTripReport {
+            node_count,
+            identical_columns: node_count - differences.len(),
+            max_sigma_error,
+            tolerance,
+            differences,
+        })
+    }
+
+    /// Regenerate the grid from master depth/level pairs, e.g. those returned
+    /// by [`VQS::extract_master_grids`], and compare it with this one.
+    pub fn verify_master_grids(
+        &self,
+        hgrid: &Hgrid,
+        master_depths: &Vec<f64>,
+        master_levels: &Vec<usize>,
+        stretching: &StretchingFunction,
+        tolerance: f64,
+    ) -> Result<RoundTripReport, RoundTripError> {
+        let regenerated = VQSBuilder::default()
+            .hgrid(hgrid)
+            .depths(master_depths)
+            .nlevels(master_levels)
+            .stretching(stretching)
+            .build()?;
+        self.compare(&regenerated, tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DEFAULT_SIGMA_TOLERANCE;
+    use crate::test_support::sloping_hgrid;
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::transforms::ReconstructedOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vqs::{VQSBuilder, VQS};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_verify_master_grids() {
+        let hgrid = sloping_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 14];
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+        let file = NamedTempFile::new().unwrap();
+        vqs.write_to_file(&file.path().to_path_buf()).unwrap();
+        let original = VQS::try_from_file(&hgrid, file.path()).unwrap();
+
+        // the masters the file was generated from reproduce it
+        let report = original
+            .verify_master_grids(
+                &hgrid,
+                &depths,
+                &nlevels,
+                &stretching,
+                DEFAULT_SIGMA_TOLERANCE,
+            )
+            .unwrap();
+        assert!(report.is_identical());
+        assert_eq!(report.identical_fraction(), 1.);
+        assert!(report.max_sigma_error <= 5e-7);
+
+        // one more level in the deepest master changes the deep columns only
+        let report = original
+            .verify_master_grids(
+                &hgrid,
+                &depths,
+                &vec![3, 6, 10, 15],
+                &stretching,
+                DEFAULT_SIGMA_TOLERANCE,
+            )
+            .unwrap();
+        assert!(!report.is_identical());
+        assert!(report.identical_fraction() > 0.);
+        assert!(report
+            .differences
+            .iter()
+            .all(|difference| hgrid.depths()[difference.node] < -60.));
+        assert!(report
+            .differences
+            .iter()
+            .any(|difference| difference.max_sigma_error.is_none()));
+    }
+
+    #[test]
+    fn test_extracted_master_grids_round_trip() {
+        let hgrid = sloping_hgrid(1.);
+        let reconstructed = |depths: &Vec<f64>, nlevels: &Vec<usize>| {
+            StretchingFunction::Reconstructed(ReconstructedOpts {
+                master_depths: depths.clone(),
+                master_levels: nlevels.clone(),
+                etal: 0.5,
+                a_vqs0: 0.,
+            })
+        };
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 14];
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&reconstructed(&depths, &nlevels))
+            .build()
+            .unwrap();
+
+        // the deepest extracted master reaches the deepest node, so the grid
+        // can be regenerated from the masters
+        let (master_depths, master_levels) = vqs.extract_master_grids(&hgrid).unwrap();
+        let deepest = -hgrid.depths().iter().cloned().fold(f64::INFINITY, f64::min);
+        assert_eq!(*master_depths.last().unwrap(), deepest);
+        let regenerated = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&master_depths)
+            .nlevels(&master_levels)
+            .stretching(&reconstructed(&master_depths, &master_levels))
+            .build()
+            .unwrap();
+        let report = vqs.compare(&regenerated, DEFAULT_SIGMA_TOLERANCE).unwrap();
+        let np = hgrid.nodes().len();
+        assert_eq!(report.node_count, np);
+        assert_eq!(report.identical_columns + report.differences.len(), np);
+
+        // the reported level counts are those of the two grids
+        let levels = |vqs: &VQS, node: usize| vqs.nvrt() + 1 - vqs.bottom_level_indices()[node];
+        for difference in &report.differences {
+            assert_eq!(difference.original_levels, levels(&vqs, difference.node));
+            assert_eq!(
+                difference.regenerated_levels,
+                levels(&regenerated, difference.node)
+            );
+            assert_eq!(
+                difference.max_sigma_error.is_none(),
+                difference.original_levels != difference.regenerated_levels
+            );
+        }
+        let same_levels = (0..np)
+            .filter(|&node| levels(&vqs, node) == levels(&regenerated, node))
+            .count();
+        assert_eq!(
+            report
+                .differences
+                .iter()
+                .filter(|difference| difference.max_sigma_error.is_some())
+                .count()
+                + report.identical_columns,
+            same_levels
+        );
+    }
+}
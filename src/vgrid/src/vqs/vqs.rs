@@ -1,24 +1,53 @@
 // schismrs-vgrid/src/vqs/vqs.rs
 
-use super::errors::{ReconstructionError, VQSLoadError};
-use crate::transforms::traits::{Transform, TransformPlotterError};
+use super::errors::{LayerThicknessError, LevelSmoothingError, ReconstructionError, VQSLoadError};
+use crate::transforms::traits::Transform;
+#[cfg(feature = "plot")]
+use crate::transforms::traits::TransformPlotterError;
 use crate::transforms::StretchingFunction;
+use crate::vertical_grid::VerticalGrid;
 use log::{debug, info, trace, warn};
-use ndarray::Array2;
+use ndarray::{Array1, Array2, ShapeBuilder};
+#[cfg(feature = "plot")]
 use plotly::Plot;
 use schismrs_hgrid::hgrid::Hgrid;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Instant;
 
+/// Layout of an ivcor=1 vgrid.in file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VgridFormat {
+    /// One line per node with its id, bottom level index and the sigma values
+    /// from the bottom up to the surface (SCHISM < 5.10).
+    Legacy,
+    /// The bottom level indices of all nodes on one line, then one row of
+    /// sigma values per level (SCHISM >= 5.10).
+    #[default]
+    ColumnMajor,
+}
+
+impl VgridFormat {
+    /// Guess the layout from the third line of the file. Bottom indices are
+    /// integers, while a legacy record always carries sigma values.
+    fn detect(line: &str) -> Self {
+        let is_real = |token: &str| token.contains(['.', 'e', 'E']);
+        if line.split_whitespace().skip(2).any(is_real) {
+            VgridFormat::Legacy
+        } else {
+            VgridFormat::ColumnMajor
+        }
+    }
+}
+
 pub struct VQS {
     sigma_vqs: Array2<f64>,
     _znd: Array2<f64>,
-    transform: Rc<dyn Transform>,
+    transform: Arc<dyn Transform>,
     kbp: Vec<usize>, // Bottom level indices for each node
 }
 
@@ -26,7 +55,7 @@ impl VQS {
     pub fn new(
         sigma_vqs: Array2<f64>,
         _znd: Array2<f64>,
-        transform: Rc<dyn Transform>,
+        transform: Arc<dyn Transform>,
         kbp: Vec<usize>,
     ) -> Self {
         let shape = sigma_vqs.shape();
@@ -73,15 +102,16 @@ impl VQS {
             .parse()
             .map_err(|_| VQSLoadError::ParseError("Failed to parse nvrt".to_string()))?;
 
-        // Read bottom level indices
+        // The third line tells the layouts apart: the bottom level indices
+        // of every node (>= 5.10) or the first node's record (legacy)
         line.clear();
         reader.read_line(&mut line)?;
-        let bottom_indices: Vec<usize> = line
-            .trim()
-            .split_whitespace()
-            .map(|s| s.parse::<usize>())
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_| VQSLoadError::ParseError("Failed to parse bottom indices".to_string()))?;
+        let format = VgridFormat::detect(&line);
+        debug!("Detected vgrid.in layout: {:?}", format);
+        let (sigma_vqs, bottom_indices) = match format {
+            VgridFormat::ColumnMajor => Self::read_column_major(&line, &mut reader, nvrt)?,
+            VgridFormat::Legacy => Self::read_legacy(&line, &mut reader, nvrt)?,
+        };
 
         let node_count = bottom_indices.len();
         if node_count != hgrid.nodes().len() {
@@ -94,14 +124,70 @@ impl VQS {
         // Convert bottom indices to kbp (actual number of levels per node)
         let kbp: Vec<usize> = bottom_indices.iter().map(|&idx| nvrt + 1 - idx).collect();
 
+        // Extract master grids from the loaded data for the reconstructed transform
+        // We'll use a simplified extraction for the initial load
+        let (extracted_depths, extracted_levels) =
+            Self::quick_extract_master_grids(&sigma_vqs, &kbp, hgrid).map_err(|e| {
+                VQSLoadError::ParseError(format!("Failed to extract master grids: {}", e))
+            })?;
+
+        // Create a reconstructed transform with the extracted master grids
+        let reconstructed_opts = crate::transforms::transforms::ReconstructedOpts {
+            master_depths: extracted_depths.clone(),
+            master_levels: extracted_levels.clone(),
+            etal: 0.0,    // Default etal for loaded files
+            a_vqs0: -1.0, // Default a_vqs0 for loaded files
+        };
+        let stretching = StretchingFunction::Reconstructed(reconstructed_opts);
+
+        // The transform method still expects depths and levels, but for Reconstructed type they're ignored
+        let transform = stretching
+            .transform(hgrid, &extracted_depths, &extracted_levels)
+            .map_err(|e| VQSLoadError::ParseError(format!("Failed to create transform: {}", e)))?;
+
+        // Create empty znd array (not needed for reconstruction)
+        let znd = Array2::<f64>::zeros((nvrt, node_count));
+
+        let elapsed = start.elapsed();
+        info!("VQS loaded from file in {:?}", elapsed);
+
+        Ok(Self::new(sigma_vqs, znd, transform, kbp))
+    }
+
+    fn parse_bottom_index(token: &str, nvrt: usize) -> Result<usize, VQSLoadError> {
+        let index: usize = token.parse().map_err(|_| {
+            VQSLoadError::ParseError(format!("Failed to parse bottom index '{}'", token))
+        })?;
+        if index < 1 || index > nvrt {
+            return Err(VQSLoadError::InvalidFormat(format!(
+                "Bottom index {} is outside [1, {}]",
+                index, nvrt
+            )));
+        }
+        Ok(index)
+    }
+
+    /// Bottom indices on one line, followed by one row of sigma values per level.
+    fn read_column_major(
+        first_line: &str,
+        reader: &mut impl BufRead,
+        nvrt: usize,
+    ) -> Result<(Array2<f64>, Vec<usize>), VQSLoadError> {
+        let bottom_indices: Vec<usize> = first_line
+            .split_whitespace()
+            .map(|s| Self::parse_bottom_index(s, nvrt))
+            .collect::<Result<Vec<_>, _>>()?;
+        let node_count = bottom_indices.len();
+
         // Initialize sigma array
         let mut sigma_vqs = Array2::<f64>::from_elem((nvrt, node_count), -9.0);
+        let mut line = String::new();
 
         // Read sigma values level by level
         for level in 1..=nvrt {
             line.clear();
             reader.read_line(&mut line)?;
-            let parts: Vec<&str> = line.trim().split_whitespace().collect();
+            let parts: Vec<&str> = line.split_whitespace().collect();
 
             if parts.is_empty() {
                 return Err(VQSLoadError::InvalidFormat(format!(
@@ -141,34 +227,65 @@ impl VQS {
             }
         }
 
-        // Extract master grids from the loaded data for the reconstructed transform
-        // We'll use a simplified extraction for the initial load
-        let (extracted_depths, extracted_levels) =
-            Self::quick_extract_master_grids(&sigma_vqs, &kbp, hgrid).map_err(|e| {
-                VQSLoadError::ParseError(format!("Failed to extract master grids: {}", e))
-            })?;
-
-        // Create a reconstructed transform with the extracted master grids
-        let reconstructed_opts = crate::transforms::transforms::ReconstructedOpts {
-            master_depths: extracted_depths.clone(),
-            master_levels: extracted_levels.clone(),
-            etal: 0.0,    // Default etal for loaded files
-            a_vqs0: -1.0, // Default a_vqs0 for loaded files
-        };
-        let stretching = StretchingFunction::Reconstructed(reconstructed_opts);
-
-        // The transform method still expects depths and levels, but for Reconstructed type they're ignored
-        let transform = stretching
-            .transform(hgrid, &extracted_depths, &extracted_levels)
-            .map_err(|e| VQSLoadError::ParseError(format!("Failed to create transform: {}", e)))?;
-
-        // Create empty znd array (not needed for reconstruction)
-        let znd = Array2::<f64>::zeros((nvrt, node_count));
-
-        let elapsed = start.elapsed();
-        info!("VQS loaded from file in {:?}", elapsed);
+        Ok((sigma_vqs, bottom_indices))
+    }
 
-        Ok(Self::new(sigma_vqs, znd, transform, kbp))
+    /// One record per node: node id, bottom index and the sigma values from
+    /// the bottom level up to the surface.
+    fn read_legacy(
+        first_line: &str,
+        reader: &mut impl BufRead,
+        nvrt: usize,
+    ) -> Result<(Array2<f64>, Vec<usize>), VQSLoadError> {
+        let mut bottom_indices = Vec::new();
+        let mut columns: Vec<f64> = Vec::new();
+        let mut line = first_line.to_string();
+        loop {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if !parts.is_empty() {
+                let node = bottom_indices.len() + 1;
+                let node_id: usize = parts[0].parse().map_err(|_| {
+                    VQSLoadError::ParseError(format!("Failed to parse node id of record {}", node))
+                })?;
+                if node_id != node {
+                    return Err(VQSLoadError::InvalidFormat(format!(
+                        "Expected node {}, got {}",
+                        node, node_id
+                    )));
+                }
+                let bottom_index = Self::parse_bottom_index(parts.get(1).unwrap_or(&""), nvrt)?;
+                let levels = nvrt + 1 - bottom_index;
+                if parts.len() - 2 != levels {
+                    return Err(VQSLoadError::InvalidFormat(format!(
+                        "Node {} has bottom index {} and expects {} sigma values, found {}",
+                        node,
+                        bottom_index,
+                        levels,
+                        parts.len() - 2
+                    )));
+                }
+                let mut column = vec![-9.0; nvrt];
+                for (k, sigma_str) in parts[2..].iter().enumerate() {
+                    column[bottom_index - 1 + k] = sigma_str.parse().map_err(|_| {
+                        VQSLoadError::ParseError(format!(
+                            "Failed to parse sigma at level {}, node {}",
+                            bottom_index + k,
+                            node
+                        ))
+                    })?;
+                }
+                columns.extend(column);
+                bottom_indices.push(bottom_index);
+            }
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+        }
+        let node_count = bottom_indices.len();
+        let sigma_vqs = Array2::from_shape_vec((nvrt, node_count).f(), columns)
+            .map_err(|e| VQSLoadError::InvalidFormat(e.to_string()))?;
+        Ok((sigma_vqs, bottom_indices))
     }
 
     /// Extract master grids from the VQS data using depth-level relationships
@@ -215,7 +332,20 @@ impl VQS {
         depth_level_pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
 
         // Identify transitions and extract master grids
-        let (master_depths, master_levels) = self.identify_master_grids(&depth_level_pairs)?;
+        let (mut master_depths, mut master_levels) =
+            self.identify_master_grids(&depth_level_pairs)?;
+        // the deepest master must reach the deepest wet node for the grid to
+        // be regenerated from the masters
+        if let (Some(last_depth), Some(last_levels), Some(&(deepest, levels))) = (
+            master_depths.last_mut(),
+            master_levels.last_mut(),
+            depth_level_pairs.last(),
+        ) {
+            if *last_depth < deepest {
+                *last_depth = deepest;
+                *last_levels = levels;
+            }
+        }
 
         // Validate the extracted master grids
         let success_rate =
@@ -595,12 +725,25 @@ impl VQS {
         Ok(())
     }
 
+    /// Write using the SCHISM >= 5.10 layout, same as the `Display` output.
     pub fn write_to_file(&self, filename: &PathBuf) -> std::io::Result<()> {
-        info!("Writing VQS to file: {:?}", filename);
+        self.write_to_file_with_format(filename, VgridFormat::ColumnMajor)
+    }
+
+    pub fn write_to_file_with_format(
+        &self,
+        filename: &PathBuf,
+        format: VgridFormat,
+    ) -> std::io::Result<()> {
+        info!("Writing VQS to file: {:?} ({:?} layout)", filename, format);
         let start = Instant::now();
 
-        let mut file = File::create(filename)?;
-        let result = write!(file, "{}", self);
+        let mut file = BufWriter::new(File::create(filename)?);
+        let result = match format {
+            VgridFormat::ColumnMajor => write!(file, "{}", self),
+            VgridFormat::Legacy => self.write_legacy(&mut file),
+        }
+        .and_then(|_| file.flush());
 
         let elapsed = start.elapsed();
         info!("VQS file write completed in {:?}", elapsed);
@@ -612,6 +755,19 @@ impl VQS {
         result
     }
 
+    fn write_legacy(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(writer, "{:>12}", self.ivcor())?;
+        writeln!(writer, "{:>12}", self.nvrt())?;
+        for (node_idx, bottom_idx) in self.bottom_level_indices().into_iter().enumerate() {
+            let mut line = format!("{:>10}{:>10}", node_idx + 1, bottom_idx);
+            for value in self.sigma_vqs.column(node_idx).iter().skip(bottom_idx - 1) {
+                line.push_str(&format!("{:14.6}", value));
+            }
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
     pub fn ivcor(&self) -> usize {
         1
     }
@@ -624,7 +780,7 @@ impl VQS {
         &self.sigma_vqs
     }
 
-    pub fn transform(&self) -> Rc<dyn Transform> {
+    pub fn transform(&self) -> Arc<dyn Transform> {
         self.transform.clone()
     }
     pub fn bottom_level_indices(&self) -> Vec<usize> {
@@ -656,6 +812,305 @@ impl VQS {
             .collect()
     }
 
+    /// Limit the difference in level counts between neighbouring wet nodes
+    /// to `max_jump`, adding levels to the nodes that have too few.
+    ///
+    /// Large jumps in `kbp` between neighbours make LSC2 grids behave like
+    /// z-level walls. A refined node keeps the shape of its sigma profile,
+    /// resampled onto the new number of levels. Returns the number of nodes
+    /// whose level count changed.
+    pub fn smooth_level_counts(
+        &mut self,
+        hgrid: &Hgrid,
+        max_jump: usize,
+    ) -> Result<usize, LevelSmoothingError> {
+        if max_jump < 1 {
+            return Err(LevelSmoothingError::InvalidMaxJump);
+        }
+        let np = self.sigma_vqs.ncols();
+        if hgrid.nodes().len() != np {
+            return Err(LevelSmoothingError::NodeCountMismatch(
+                hgrid.nodes().len(),
+                np,
+            ));
+        }
+        let index: HashMap<u32, usize> = hgrid
+            .nodes()
+            .hash_map()
+            .keys()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+        let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); np];
+        for node_ids in hgrid.elements().hash_map().values() {
+            let n = node_ids.len();
+            for i in 0..n {
+                let a = index[&node_ids[i]];
+                let b = index[&node_ids[(i + 1) % n]];
+                if !neighbors[a].contains(&b) {
+                    neighbors[a].push(b);
+                    neighbors[b].push(a);
+                }
+            }
+        }
+
+        // levels only ever increase, so the worklist runs out
+        let original = self.kbp.clone();
+        let mut queue: VecDeque<usize> = (0..np).filter(|&i| self.kbp[i] > 0).collect();
+        let mut queued: Vec<bool> = self.kbp.iter().map(|&kbp| kbp > 0).collect();
+        while let Some(node) = queue.pop_front() {
+            queued[node] = false;
+            let target = neighbors[node]
+                .iter()
+                .map(|&neighbor| self.kbp[neighbor].saturating_sub(max_jump))
+                .max()
+                .unwrap_or(0);
+            if target > self.kbp[node] {
+                self.kbp[node] = target;
+                for &neighbor in neighbors[node].iter() {
+                    if self.kbp[neighbor] > 0 && !queued[neighbor] {
+                        queued[neighbor] = true;
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        let nvrt = self.nvrt();
+        let etal = *self.transform.etal();
+        let depths = hgrid.depths();
+        let mut changed = 0;
+        for node in 0..np {
+            let (old, new) = (original[node], self.kbp[node]);
+            if old == new {
+                continue;
+            }
+            changed += 1;
+            // bottom-up profile of the node before refinement
+            let profile: Vec<f64> = if old >= 2 {
+                self.sigma_vqs
+                    .column(node)
+                    .iter()
+                    .skip(nvrt - old)
+                    .cloned()
+                    .collect()
+            } else {
+                vec![-1., 0.]
+            };
+            let old = profile.len();
+            let mut column = self.sigma_vqs.column_mut(node);
+            column.fill(-9.);
+            let mut znd = self._znd.column_mut(node);
+            znd.fill(f64::NAN);
+            for j in 0..new {
+                let position = (j * (old - 1)) as f64 / (new - 1) as f64;
+                let lower = (position.floor() as usize).min(old - 2);
+                let weight = position - lower as f64;
+                let sigma = if j == 0 {
+                    -1.
+                } else if j == new - 1 {
+                    0.
+                } else {
+                    profile[lower] + weight * (profile[lower + 1] - profile[lower])
+                };
+                column[nvrt - new + j] = sigma;
+                // znd runs from the surface down
+                znd[new - 1 - j] = etal + (etal - depths[node]) * sigma;
+            }
+        }
+        info!(
+            "Level count smoothing with max_jump={} changed {} nodes",
+            max_jump, changed
+        );
+        Ok(changed)
+    }
+
+    /// Give every wet node a surface layer `dz_surface_target` thick and no
+    /// layer thicker than `dz_max`, for the total depth at `etal`.
+    ///
+    /// The surface target only applies where it is thinner than the mean
+    /// layer of the node, shallower nodes keep their profile. The other layers
+    /// are rescaled to fill the rest of the column, and layers above `dz_max`
+    /// are capped with the excess spread over the thinner ones. The bottom
+    /// layer is kept at least `dz_bottom_min` thick, or as thick as it was
+    /// where the builder already made it thinner. Nodes without enough levels
+    /// to stay under `dz_max` or above `dz_bottom_min` are an error. Returns
+    /// the number of nodes whose sigma profile changed.
+    pub fn constrain_layer_thickness(
+        &mut self,
+        hgrid: &Hgrid,
+        dz_surface_target: Option<f64>,
+        dz_max: Option<f64>,
+        dz_bottom_min: f64,
+    ) -> Result<usize, LayerThicknessError> {
+        Self::validate_layer_thickness_constraints(dz_surface_target, dz_max)?;
+        let np = self.sigma_vqs.ncols();
+        if hgrid.nodes().len() != np {
+            return Err(LayerThicknessError::NodeCountMismatch(
+                hgrid.nodes().len(),
+                np,
+            ));
+        }
+        let nvrt = self.nvrt();
+        let etal = *self.transform.etal();
+        let depths = hgrid.depths();
+        let mut changed = 0;
+        for node in 0..np {
+            let kbp = self.kbp[node];
+            let total_depth = etal - depths[node];
+            if kbp < 2 || total_depth <= 0. {
+                continue;
+            }
+            // layer thicknesses from the bottom up
+            let column = self.sigma_vqs.column(node);
+            let mut dz: Vec<f64> = (nvrt - kbp..nvrt - 1)
+                .map(|k| (column[k + 1] - column[k]) * total_depth)
+                .collect();
+            let nlayers = dz.len();
+            let mut fixed = vec![false; nlayers];
+            let bottom_min = dz_bottom_min.min(dz[0]);
+            let bottom_too_thin =
+                || LayerThicknessError::BottomLayerTooThin(node + 1, -depths[node], dz_bottom_min);
+            if let Some(target) = dz_surface_target {
+                if target * (nlayers as f64) < total_depth && nlayers > 1 {
+                    dz[nlayers - 1] = target;
+                    fixed[nlayers - 1] = true;
+                    if !Self::rescale_free_layers(&mut dz, &fixed, total_depth) {
+                        return Err(LayerThicknessError::SurfaceTargetTooThick(
+                            node + 1,
+                            -depths[node],
+                            target,
+                        ));
+                    }
+                    if !Self::fix_bottom_layer(&mut dz, &mut fixed, bottom_min, total_depth) {
+                        return Err(bottom_too_thin());
+                    }
+                }
+            }
+            if let Some(dz_max) = dz_max {
+                while let Some(k) = (0..nlayers).find(|&k| !fixed[k] && dz[k] > dz_max) {
+                    dz[k] = dz_max;
+                    fixed[k] = true;
+                    if fixed.iter().all(|&f| f)
+                        || !Self::rescale_free_layers(&mut dz, &fixed, total_depth)
+                    {
+                        return Err(LayerThicknessError::TooFewLevels(
+                            node + 1,
+                            -depths[node],
+                            kbp,
+                            dz_max,
+                        ));
+                    }
+                }
+                if dz.iter().any(|&thickness| thickness > dz_max * (1. + 1e-9)) {
+                    return Err(LayerThicknessError::TooFewLevels(
+                        node + 1,
+                        -depths[node],
+                        kbp,
+                        dz_max,
+                    ));
+                }
+            }
+            if dz[0] < bottom_min * (1. - 1e-9) {
+                return Err(bottom_too_thin());
+            }
+            let mut sigma = -1.;
+            let mut profile = Vec::with_capacity(kbp);
+            profile.push(sigma);
+            for thickness in dz.iter().take(nlayers - 1) {
+                sigma += thickness / total_depth;
+                profile.push(sigma);
+            }
+            profile.push(0.);
+            let before = self.sigma_vqs.column(node);
+            if before
+                .iter()
+                .skip(nvrt - kbp)
+                .zip(profile.iter())
+                .all(|(a, b)| (a - b).abs() < 1e-12)
+            {
+                continue;
+            }
+            changed += 1;
+            let mut column = self.sigma_vqs.column_mut(node);
+            let mut znd = self._znd.column_mut(node);
+            for (j, &sigma) in profile.iter().enumerate() {
+                column[nvrt - kbp + j] = sigma;
+                // znd runs from the surface down
+                znd[kbp - 1 - j] = etal + total_depth * sigma;
+            }
+        }
+        info!(
+            "Layer thickness constraints (dz_surface_target={:?}, dz_max={:?}) changed {} nodes",
+            dz_surface_target, dz_max, changed
+        );
+        Ok(changed)
+    }
+
+    pub fn validate_layer_thickness_constraints(
+        dz_surface_target: Option<f64>,
+        dz_max: Option<f64>,
+    ) -> Result<(), LayerThicknessError> {
+        if let Some(target) = dz_surface_target {
+            if target.is_nan() || target <= 0. {
+                return Err(LayerThicknessError::InvalidDzSurfaceTarget(target));
+            }
+        }
+        if let Some(dz_max) = dz_max {
+            if dz_max.is_nan() || dz_max <= 0. {
+                return Err(LayerThicknessError::InvalidDzMax(dz_max));
+            }
+            if let Some(target) = dz_surface_target {
+                if target > dz_max {
+                    return Err(LayerThicknessError::SurfaceTargetAboveDzMax(target, dz_max));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Scale the layers that are not `fixed` so that all layers add up to
+    /// `total_depth`. Returns false when the fixed layers alone are too thick.
+    fn rescale_free_layers(dz: &mut [f64], fixed: &[bool], total_depth: f64) -> bool {
+        let fixed_sum: f64 = dz
+            .iter()
+            .zip(fixed)
+            .filter(|(_, &f)| f)
+            .map(|(d, _)| d)
+            .sum();
+        let free_sum: f64 = dz
+            .iter()
+            .zip(fixed)
+            .filter(|(_, &f)| !f)
+            .map(|(d, _)| d)
+            .sum();
+        if fixed_sum >= total_depth || free_sum <= 0. {
+            return false;
+        }
+        let scale = (total_depth - fixed_sum) / free_sum;
+        for (thickness, _) in dz.iter_mut().zip(fixed).filter(|(_, &f)| !f) {
+            *thickness *= scale;
+        }
+        true
+    }
+
+    /// Fix the bottom layer at `bottom_min` where rescaling made it thinner
+    /// and rescale the remaining free layers. Returns false when no free
+    /// layers can take up the difference.
+    fn fix_bottom_layer(
+        dz: &mut [f64],
+        fixed: &mut [bool],
+        bottom_min: f64,
+        total_depth: f64,
+    ) -> bool {
+        if fixed[0] || dz[0] >= bottom_min {
+            return true;
+        }
+        dz[0] = bottom_min;
+        fixed[0] = true;
+        Self::rescale_free_layers(dz, fixed, total_depth)
+    }
+
     fn iter_level_values(&self) -> IterLevelValues<'_> {
         trace!("Creating level values iterator");
         IterLevelValues {
@@ -669,6 +1124,13 @@ impl VQS {
         self.sigma_vqs.row(level - 1).to_vec()
     }
 
+    /// Level elevations of each master grid, see
+    /// [`Transform::master_grid_profiles`].
+    pub fn master_grid_profiles(&self) -> Vec<Array1<f64>> {
+        self.transform.master_grid_profiles()
+    }
+
+    #[cfg(feature = "plot")]
     pub fn make_z_mas_plot(&self) -> Result<Plot, TransformPlotterError> {
         info!("Generating z_mas plot");
         Ok(self.transform.make_zmas_plot()?)
@@ -731,6 +1193,47 @@ impl VQS {
     }
 }
 
+impl VerticalGrid for VQS {
+    fn ivcor(&self) -> usize {
+        VQS::ivcor(self)
+    }
+
+    fn nvrt(&self) -> usize {
+        VQS::nvrt(self)
+    }
+
+    fn bottom_level_index(&self, node: usize, _depth: f64) -> usize {
+        (self.nvrt() + 1)
+            .saturating_sub(self.kbp[node])
+            .clamp(1, self.nvrt())
+    }
+
+    fn zcor_at_node(&self, node: usize, depth: f64, eta: f64) -> Array1<f64> {
+        let kbp = self.bottom_level_index(node, depth);
+        self.sigma_vqs
+            .column(node)
+            .iter()
+            .enumerate()
+            .map(|(k, &sigma)| {
+                // nodes that were dry when the grid was built have no levels
+                if k + 1 < kbp || sigma == -9.0 {
+                    f64::NAN
+                } else {
+                    eta + (eta + depth) * sigma
+                }
+            })
+            .collect()
+    }
+
+    fn write_to_file(&self, filename: &Path) -> std::io::Result<()> {
+        VQS::write_to_file(self, &filename.to_path_buf())
+    }
+
+    fn node_count(&self) -> Option<usize> {
+        Some(self.sigma_vqs.ncols())
+    }
+}
+
 pub struct IterLevelValues<'a> {
     vqs: &'a VQS,
     level: usize,
@@ -846,3 +1349,238 @@ impl fmt::Display for VQS {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{VgridFormat, VQS};
+    use crate::test_support::sloping_hgrid;
+    use crate::transforms::quadratic::QuadraticTransformOpts;
+    use crate::transforms::StretchingFunction;
+    use crate::vqs::VQSBuilder;
+    use crate::vqs::{LayerThicknessError, VQSBuilderError};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_round_trip_both_layouts() {
+        let hgrid = sloping_hgrid(1.);
+        let opts = QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        };
+        let stretching = StretchingFunction::Quadratic(opts);
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 14];
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+
+        let mut reloaded = Vec::new();
+        for format in [VgridFormat::ColumnMajor, VgridFormat::Legacy] {
+            let file = NamedTempFile::new().unwrap();
+            let path = file.path().to_path_buf();
+            vqs.write_to_file_with_format(&path, format).unwrap();
+            let header = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(VgridFormat::detect(header.lines().nth(2).unwrap()), format);
+            reloaded.push(VQS::try_from_file(&hgrid, &path).unwrap());
+        }
+        for other in reloaded.iter() {
+            assert_eq!(other.bottom_level_indices(), vqs.bottom_level_indices());
+            assert_eq!(other.nvrt(), vqs.nvrt());
+            for (a, b) in other.sigma().iter().zip(vqs.sigma().iter()) {
+                assert!((a - b).abs() <= 5e-7);
+            }
+        }
+        assert_eq!(reloaded[0].sigma(), reloaded[1].sigma());
+    }
+
+    #[test]
+    fn test_smooth_level_counts() {
+        let hgrid = sloping_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 40];
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .build()
+            .unwrap();
+        let mut smoothed = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .max_level_jump(&2)
+            .build()
+            .unwrap();
+
+        let max_jump = |vqs: &VQS| {
+            let index: std::collections::HashMap<u32, usize> = hgrid
+                .nodes()
+                .hash_map()
+                .keys()
+                .enumerate()
+                .map(|(i, &id)| (id, i))
+                .collect();
+            hgrid
+                .elements()
+                .hash_map()
+                .values()
+                .flat_map(|ids| {
+                    let n = ids.len();
+                    (0..n)
+                        .map(|i| {
+                            let a = vqs.kbp[index[&ids[i]]];
+                            let b = vqs.kbp[index[&ids[(i + 1) % n]]];
+                            a.abs_diff(b)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .max()
+                .unwrap()
+        };
+        assert!(max_jump(&vqs) > 2);
+        assert!(max_jump(&smoothed) <= 2);
+        assert_eq!(smoothed.nvrt(), vqs.nvrt());
+        let changed = vqs
+            .kbp
+            .iter()
+            .zip(smoothed.kbp.iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert!(changed > 0);
+        assert!(vqs.kbp.iter().zip(smoothed.kbp.iter()).all(|(a, b)| a <= b));
+
+        let nvrt = smoothed.nvrt();
+        for (node, &kbp) in smoothed.kbp.iter().enumerate() {
+            let column = smoothed.sigma().column(node);
+            assert!(column.iter().take(nvrt - kbp).all(|&sigma| sigma == -9.));
+            let profile: Vec<f64> = column.iter().skip(nvrt - kbp).cloned().collect();
+            assert_eq!(profile[0], -1.);
+            assert!((profile[kbp - 1]).abs() < 1e-12);
+            assert!(profile.windows(2).all(|w| w[0] < w[1]));
+        }
+        // already smooth
+        assert_eq!(smoothed.smooth_level_counts(&hgrid, 2).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_layer_thickness_constraints() {
+        let hgrid = sloping_hgrid(1.);
+        let stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &0.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let depths = vec![5., 20., 60., 110.];
+        let nlevels = vec![3, 6, 10, 20];
+        let vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .dz_surface_target(&0.5)
+            .dz_max(&10.)
+            .build()
+            .unwrap();
+        let node_depths = hgrid.depths().mapv(|depth| -depth);
+        let nvrt = vqs.nvrt();
+        let mut constrained_surface = 0;
+        for (node, &kbp) in vqs.kbp.iter().enumerate() {
+            let depth = node_depths[node];
+            let column = vqs.sigma().column(node);
+            let dz: Vec<f64> = (nvrt - kbp..nvrt - 1)
+                .map(|k| (column[k + 1] - column[k]) * depth)
+                .collect();
+            assert!(dz
+                .iter()
+                .all(|&thickness| thickness > 0. && thickness <= 10. + 1e-9));
+            assert!((dz.iter().sum::<f64>() - depth).abs() < 1e-9);
+            if 0.5 * (dz.len() as f64) < depth && dz.len() > 1 {
+                assert!((dz[dz.len() - 1] - 0.5).abs() < 1e-9);
+                constrained_surface += 1;
+            }
+        }
+        assert!(constrained_surface > 0);
+
+        // a surface target thicker than the surface intensified layers
+        // squeezes the layers below it
+        let surface_stretching = StretchingFunction::Quadratic(QuadraticTransformOpts {
+            a_vqs0: &-1.,
+            etal: &0.,
+            skew_decay_rate: &0.03,
+        });
+        let mut vqs = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&surface_stretching)
+            .build()
+            .unwrap();
+        let bottom_layers = |vqs: &VQS| -> Vec<f64> {
+            vqs.kbp
+                .iter()
+                .enumerate()
+                .map(|(node, &kbp)| {
+                    let column = vqs.sigma().column(node);
+                    let bottom = vqs.nvrt() - kbp;
+                    (column[bottom + 1] - column[bottom]) * node_depths[node]
+                })
+                .collect()
+        };
+        let before = bottom_layers(&vqs);
+        let changed = vqs
+            .constrain_layer_thickness(&hgrid, Some(3.), None, 3.)
+            .unwrap();
+        assert!(changed > 0);
+        // bottom layers the builder made thinner than dz_bottom_min keep their thickness
+        for (after, before) in bottom_layers(&vqs).iter().zip(before.iter()) {
+            assert!(*after >= before.min(3.) - 1e-9);
+        }
+
+        let result = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .dz_max(&2.)
+            .build();
+        assert!(matches!(
+            result,
+            Err(VQSBuilderError::LayerThicknessError(
+                LayerThicknessError::TooFewLevels(..)
+            ))
+        ));
+        let result = VQSBuilder::default()
+            .hgrid(&hgrid)
+            .depths(&depths)
+            .nlevels(&nlevels)
+            .stretching(&stretching)
+            .dz_surface_target(&3.)
+            .dz_max(&2.)
+            .build();
+        assert!(matches!(
+            result,
+            Err(VQSBuilderError::LayerThicknessError(
+                LayerThicknessError::SurfaceTargetAboveDzMax(..)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_vqs_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+        assert_send_sync::<VQS>();
+        assert_send_sync::<dyn crate::transforms::traits::Transform>();
+    }
+}
@@ -1,6 +1,7 @@
 // schishmrs-vgrid/src/vqs/vqs_auto_builder.rs
 
 use super::errors::VQSAutoBuilderError;
+use super::regions::VQSRegion;
 use super::vqs::VQS;
 use super::vqs_builder::VQSBuilder;
 use crate::transforms::StretchingFunction;
@@ -13,9 +14,14 @@ pub struct VQSAutoBuilder<'a> {
     ngrids: Option<&'a usize>,
     stretching: Option<&'a StretchingFunction<'a>>,
     dz_bottom_min: Option<&'a f64>,
+    max_level_jump: Option<&'a usize>,
+    regions: Option<&'a [VQSRegion<'a>]>,
+    blending_width: Option<&'a f64>,
     initial_depth: Option<&'a f64>,
     shallow_levels: Option<&'a usize>,
     max_levels: Option<&'a usize>,
+    dz_surface_target: Option<&'a f64>,
+    dz_max: Option<&'a f64>,
 }
 
 impl<'a> VQSAutoBuilder<'a> {
@@ -63,13 +69,29 @@ impl<'a> VQSAutoBuilder<'a> {
 
         let (hsm, nlevels) =
             Self::build_hsm_and_nlevels(hgrid, ngrids, initial_depth, shallow_levels, &max_levels)?;
-        Ok(VQSBuilder::default()
-            .hgrid(&hgrid)
+        let mut builder = VQSBuilder::default();
+        builder
+            .hgrid(hgrid)
             .depths(&hsm)
             .nlevels(&nlevels)
-            .stretching(&stretching)
-            .dz_bottom_min(&dz_bottom_min)
-            .build()?)
+            .stretching(stretching)
+            .dz_bottom_min(&dz_bottom_min);
+        if let Some(max_level_jump) = self.max_level_jump {
+            builder.max_level_jump(max_level_jump);
+        }
+        if let Some(regions) = self.regions {
+            builder.regions(regions);
+        }
+        if let Some(blending_width) = self.blending_width {
+            builder.blending_width(blending_width);
+        }
+        if let Some(dz_surface_target) = self.dz_surface_target {
+            builder.dz_surface_target(dz_surface_target);
+        }
+        if let Some(dz_max) = self.dz_max {
+            builder.dz_max(dz_max);
+        }
+        Ok(builder.build()?)
     }
 
     fn validate_ngrids(ngrids: &usize) -> Result<(), VQSAutoBuilderError> {
@@ -85,7 +107,7 @@ impl<'a> VQSAutoBuilder<'a> {
         }
         Ok(())
     }
-    
+
     fn validate_max_levels(
         shallow_levels: &usize,
         max_levels: &usize,
@@ -162,34 +184,64 @@ impl<'a> VQSAutoBuilder<'a> {
         self.hgrid = Some(hgrid);
         self
     }
-    
+
     pub fn ngrids(&mut self, ngrids: &'a usize) -> &mut Self {
         self.ngrids = Some(ngrids);
         self
     }
-    
+
     pub fn stretching(&mut self, stretching: &'a StretchingFunction) -> &mut Self {
         self.stretching = Some(stretching);
         self
     }
-    
+
     pub fn dz_bottom_min(&mut self, dz_bottom_min: &'a f64) -> &mut Self {
         self.dz_bottom_min = Some(dz_bottom_min);
         self
     }
-    
+
+    /// Limit the level count difference between neighbouring nodes, see
+    /// [`VQS::smooth_level_counts`].
+    pub fn max_level_jump(&mut self, max_level_jump: &'a usize) -> &mut Self {
+        self.max_level_jump = Some(max_level_jump);
+        self
+    }
+
+    /// Parts of the domain with their own master grids or stretching, see
+    /// [`VQSBuilder::regions`].
+    pub fn regions(&mut self, regions: &'a [VQSRegion<'a>]) -> &mut Self {
+        self.regions = Some(regions);
+        self
+    }
+
+    /// See [`VQSBuilder::blending_width`].
+    pub fn blending_width(&mut self, blending_width: &'a f64) -> &mut Self {
+        self.blending_width = Some(blending_width);
+        self
+    }
+
     pub fn initial_depth(&mut self, initial_depth: &'a f64) -> &mut Self {
         self.initial_depth = Some(initial_depth);
         self
     }
-    
+
     pub fn shallow_levels(&mut self, shallow_levels: &'a usize) -> &mut Self {
         self.shallow_levels = Some(shallow_levels);
         self
     }
-    
+
     pub fn max_levels(&mut self, max_levels: &'a usize) -> &mut Self {
         self.max_levels = Some(max_levels);
         self
     }
-}
\ No newline at end of file
+
+    pub fn dz_surface_target(&mut self, dz_surface_target: &'a f64) -> &mut Self {
+        self.dz_surface_target = Some(dz_surface_target);
+        self
+    }
+
+    pub fn dz_max(&mut self, dz_max: &'a f64) -> &mut Self {
+        self.dz_max = Some(dz_max);
+        self
+    }
+}
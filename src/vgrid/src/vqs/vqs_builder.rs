@@ -1,14 +1,24 @@
 // schismrs-vgrid/src/vqs/vqs_builder.rs
 
 use super::errors::VQSBuilderError;
+use super::regions::{blend_columns, RegionAssignment, VQSRegion};
 use super::vqs::VQS;
 use crate::transforms::StretchingFunction;
 use log::{debug, error, info, trace, warn};
-use ndarray::Array2;
 use ndarray::Array1;
+use ndarray::Array2;
+use ndarray::{ArrayViewMut1, Axis};
+use rayon::prelude::*;
 use schismrs_hgrid::hgrid::Hgrid;
 use std::time::Instant;
 
+/// How a node was handled by [`VQSBuilder::build_sigma_vqs`].
+enum NodeKind {
+    Dry,
+    Shallow,
+    Deep,
+}
+
 #[derive(Default)]
 pub struct VQSBuilder<'a> {
     hgrid: Option<&'a Hgrid>,
@@ -16,6 +26,11 @@ pub struct VQSBuilder<'a> {
     nlevels: Option<&'a Vec<usize>>,
     stretching: Option<&'a StretchingFunction<'a>>,
     dz_bottom_min: Option<&'a f64>,
+    max_level_jump: Option<&'a usize>,
+    dz_surface_target: Option<&'a f64>,
+    dz_max: Option<&'a f64>,
+    regions: Option<&'a [VQSRegion<'a>]>,
+    blending_width: Option<&'a f64>,
 }
 
 impl<'a> VQSBuilder<'a> {
@@ -107,25 +122,17 @@ impl<'a> VQSBuilder<'a> {
 
         info!("Stretching function: {:?}", stretching);
 
-        let min_bottom_layer_thickness = match self.dz_bottom_min {
-            Some(value) => {
-                info!("Using provided dz_bottom_min: {:.6}", value);
-                *value
-            }
-            None => {
-                // Use a fraction of the average layer thickness in the shallowest region
-                let shallow_depth = depths[0];
-                let shallow_levels = nlevels[0];
-                let calculated = shallow_depth / (shallow_levels as f64) * 0.5;
-                info!(
-                    "Calculated dz_bottom_min: {:.6} (from shallow_depth={:.3}, shallow_levels={})",
-                    calculated, shallow_depth, shallow_levels
-                );
-                calculated
-            }
-        };
+        let min_bottom_layer_thickness = self.min_bottom_layer_thickness(depths, nlevels);
 
         Self::validate_dz_bottom_min(&min_bottom_layer_thickness)?;
+        VQS::validate_layer_thickness_constraints(
+            self.dz_surface_target.copied(),
+            self.dz_max.copied(),
+        )?;
+        let assignment = match self.regions {
+            Some(regions) if !regions.is_empty() => Some(self.region_assignment()?),
+            _ => None,
+        };
 
         info!("Creating transform...");
         let transform_start = Instant::now();
@@ -180,6 +187,17 @@ impl<'a> VQSBuilder<'a> {
             transform.a_vqs0(),
             &min_bottom_layer_thickness,
         )?;
+        let (sigma_vqs, znd, kbp) = match (&assignment, self.regions) {
+            (Some(assignment), Some(regions)) => {
+                let columns = regions
+                    .iter()
+                    .map(|region| self.build_region_columns(hgrid, region))
+                    .collect::<Result<Vec<_>, _>>()?;
+                info!("Blending {} regions into the base grid", regions.len());
+                blend_columns(hgrid, assignment, (&sigma_vqs, &kbp), &columns, *etal)
+            }
+            _ => (sigma_vqs, znd, kbp),
+        };
 
         let arrays_elapsed = arrays_start.elapsed();
         info!("Arrays built in {:?}", arrays_elapsed);
@@ -221,7 +239,76 @@ impl<'a> VQSBuilder<'a> {
         info!("VQS build completed in {:?}", total_elapsed);
         info!("========================================");
 
-        Ok(VQS::new(sigma_vqs, znd, transform, kbp))
+        let mut vqs = VQS::new(sigma_vqs, znd, transform, kbp);
+        if let Some(max_level_jump) = self.max_level_jump {
+            vqs.smooth_level_counts(hgrid, *max_level_jump)?;
+        }
+        if self.dz_surface_target.is_some() || self.dz_max.is_some() {
+            vqs.constrain_layer_thickness(
+                hgrid,
+                self.dz_surface_target.copied(),
+                self.dz_max.copied(),
+                min_bottom_layer_thickness,
+            )?;
+        }
+        Ok(vqs)
+    }
+
+    /// dz_bottom_min if given, or a fraction of the average layer thickness
+    /// in the shallowest master grid.
+    fn min_bottom_layer_thickness(&self, depths: &[f64], nlevels: &[usize]) -> f64 {
+        match self.dz_bottom_min {
+            Some(value) => {
+                info!("Using provided dz_bottom_min: {:.6}", value);
+                *value
+            }
+            None => {
+                // Use a fraction of the average layer thickness in the shallowest region
+                let shallow_depth = depths[0];
+                let shallow_levels = nlevels[0];
+                let calculated = shallow_depth / (shallow_levels as f64) * 0.5;
+                info!(
+                    "Calculated dz_bottom_min: {:.6} (from shallow_depth={:.3}, shallow_levels={})",
+                    calculated, shallow_depth, shallow_levels
+                );
+                calculated
+            }
+        }
+    }
+
+    /// Sigma array and level counts of `region` over the whole hgrid, with
+    /// the master grids and stretching of the builder where the region does
+    /// not set its own.
+    fn build_region_columns(
+        &self,
+        hgrid: &Hgrid,
+        region: &VQSRegion,
+    ) -> Result<(Array2<f64>, Vec<usize>), VQSBuilderError> {
+        let depths = region
+            .depths
+            .or(self.depths)
+            .ok_or_else(|| VQSBuilderError::UninitializedFieldError("depths".to_string()))?;
+        let nlevels = region
+            .nlevels
+            .or(self.nlevels)
+            .ok_or_else(|| VQSBuilderError::UninitializedFieldError("nlevels".to_string()))?;
+        let stretching = region
+            .stretching
+            .or(self.stretching)
+            .ok_or_else(|| VQSBuilderError::UninitializedFieldError("stretching".to_string()))?;
+        let min_bottom_layer_thickness = self.min_bottom_layer_thickness(depths, nlevels);
+        Self::validate_dz_bottom_min(&min_bottom_layer_thickness)?;
+        let transform = stretching.transform(hgrid, depths, nlevels)?;
+        let (sigma, _, kbp) = Self::build_sigma_vqs(
+            transform.zmas(),
+            hgrid,
+            depths,
+            nlevels,
+            transform.etal(),
+            transform.a_vqs0(),
+            &min_bottom_layer_thickness,
+        )?;
+        Ok((sigma, kbp))
     }
 
     fn build_sigma_vqs(
@@ -277,129 +364,138 @@ impl<'a> VQSBuilder<'a> {
         // Elevation at each node
         let elevations = Array1::from_elem(node_count, *etal);
 
-        let mut shallow_count = 0;
-        let mut deep_count = 0;
-        let _dry_count = 0;
-        let mut error_count = 0;
-        let mut problem_nodes = Vec::new();
-
         info!("Processing {} nodes...", node_count);
 
-        // Process each node
-        for (node_idx, &depth) in depths.iter().enumerate() {
-            if node_idx % 10000 == 0 && node_idx > 0 {
-                info!(
-                    "Progress: {} nodes processed ({} shallow, {} deep)",
-                    node_idx, shallow_count, deep_count
-                );
-            }
-
-            // Log details for first few nodes and any problematic ones
-            let should_log_details = node_idx < 10 ||
+        // Every node owns its sigma, z and kbp column, so they are filled in parallel
+        let outcomes: Vec<Result<NodeKind, VQSBuilderError>> = sigma
+            .axis_iter_mut(Axis(1))
+            .into_par_iter()
+            .zip(z_coords.axis_iter_mut(Axis(1)))
+            .zip(kbp.par_iter_mut())
+            .enumerate()
+            .map(|(node_idx, ((mut sigma, mut z_coords), kbp))| {
+                let depth = depths[node_idx];
+
+                // Log details for first few nodes and any problematic ones
+                let should_log_details = node_idx < 10 ||
                                     (node_idx % 10000 == 0) ||
                                     depth < 0.01 ||  // Very shallow
                                     depth > master_depths[master_depths.len() - 1] * 1.5; // Very deep
 
-            if should_log_details {
-                debug!("Processing node {}: depth = {:.3}m", node_idx, depth);
-            }
-
-            if depth <= 0.0 {
-                // Dry node
-                if node_idx < 5 {
-                    debug!("  Node {} is dry (depth = {:.3})", node_idx, depth);
+                if should_log_details {
+                    debug!("Processing node {}: depth = {:.3}m", node_idx, depth);
                 }
-                kbp[node_idx] = 0;
-                continue;
-            }
-
-            if depth <= master_depths[0] {
-                shallow_count += 1;
 
-                if should_log_details {
-                    debug!(
-                        "  Node {} is shallow (depth {:.3} <= {:.3})",
-                        node_idx, depth, master_depths[0]
-                    );
+                if depth <= 0.0 {
+                    // Dry node
+                    if node_idx < 5 {
+                        debug!("  Node {} is dry (depth = {:.3})", node_idx, depth);
+                    }
+                    *kbp = 0;
+                    return Ok(NodeKind::Dry);
                 }
 
-                // Handle shallow areas
-                Self::process_shallow_node(
-                    node_idx,
-                    depth,
-                    master_levels[0],
-                    a_vqs0,
-                    elevations[node_idx],
-                    &mut sigma,
-                    &mut z_coords,
-                    &mut kbp,
-                );
+                if depth <= master_depths[0] {
+                    if should_log_details {
+                        debug!(
+                            "  Node {} is shallow (depth {:.3} <= {:.3})",
+                            node_idx, depth, master_depths[0]
+                        );
+                    }
 
-                if kbp[node_idx] < 2 {
-                    error!(
-                        "WARNING: Shallow node {} has only {} levels!",
-                        node_idx, kbp[node_idx]
+                    // Handle shallow areas
+                    Self::process_shallow_node(
+                        node_idx,
+                        depth,
+                        master_levels[0],
+                        a_vqs0,
+                        elevations[node_idx],
+                        &mut sigma,
+                        &mut z_coords,
+                        kbp,
                     );
-                    problem_nodes.push((node_idx, depth, kbp[node_idx]));
-                }
-            } else {
-                deep_count += 1;
+                    Ok(NodeKind::Shallow)
+                } else {
+                    if should_log_details {
+                        debug!(
+                            "  Node {} is deep (depth {:.3} > {:.3})",
+                            node_idx, depth, master_depths[0]
+                        );
+                    }
 
-                if should_log_details {
-                    debug!(
-                        "  Node {} is deep (depth {:.3} > {:.3})",
-                        node_idx, depth, master_depths[0]
-                    );
+                    // Handle deeper areas
+                    Self::process_deep_node(
+                        node_idx,
+                        depth,
+                        master_depths,
+                        master_levels,
+                        z_mas,
+                        elevations[node_idx],
+                        min_bottom_layer_thickness,
+                        &mut sigma,
+                        &mut z_coords,
+                        kbp,
+                    )?;
+                    Ok(NodeKind::Deep)
                 }
+            })
+            .collect();
 
-                // Handle deeper areas
-                match Self::process_deep_node(
-                    node_idx,
-                    depth,
-                    master_depths,
-                    master_levels,
-                    z_mas,
-                    elevations[node_idx],
-                    min_bottom_layer_thickness,
-                    &mut sigma,
-                    &mut z_coords,
-                    &mut kbp,
-                ) {
-                    Ok(_) => {
-                        if kbp[node_idx] < 2 {
-                            error!(
-                                "WARNING: Deep node {} has only {} levels!",
-                                node_idx, kbp[node_idx]
-                            );
-                            problem_nodes.push((node_idx, depth, kbp[node_idx]));
-                        }
-                    }
-                    Err(e) => {
-                        error_count += 1;
-                        error!("ERROR processing deep node {}: {}", node_idx, e);
+        let mut shallow_count = 0;
+        let mut deep_count = 0;
+        let mut error_count = 0;
+        let mut first_error = None;
+        let mut problem_nodes = Vec::new();
+
+        for (node_idx, outcome) in outcomes.into_iter().enumerate() {
+            let depth = depths[node_idx];
+            match outcome {
+                Ok(NodeKind::Dry) => {}
+                Ok(kind) => {
+                    let kind = if matches!(kind, NodeKind::Shallow) {
+                        shallow_count += 1;
+                        "Shallow"
+                    } else {
+                        deep_count += 1;
+                        "Deep"
+                    };
+                    if kbp[node_idx] < 2 {
                         error!(
-                            "  Node details: depth={:.3}, elevation={:.3}",
-                            depth, elevations[node_idx]
+                            "WARNING: {} node {} has only {} levels!",
+                            kind, node_idx, kbp[node_idx]
                         );
+                        problem_nodes.push((node_idx, depth, kbp[node_idx]));
+                    }
+                }
+                Err(e) => {
+                    error_count += 1;
+                    error!("ERROR processing deep node {}: {}", node_idx, e);
+                    error!(
+                        "  Node details: depth={:.3}, elevation={:.3}",
+                        depth, elevations[node_idx]
+                    );
 
-                        // Try to find which master grid would be used
-                        for i in 1..master_depths.len() {
-                            if depth > master_depths[i - 1] && depth <= master_depths[i] {
-                                error!("  Would use master grid {}: depths [{:.3}, {:.3}], levels [{}, {}]",
-                                      i, master_depths[i-1], master_depths[i],
-                                      master_levels[i-1], master_levels[i]);
-                            }
+                    // Try to find which master grid would be used
+                    for i in 1..master_depths.len() {
+                        if depth > master_depths[i - 1] && depth <= master_depths[i] {
+                            error!("  Would use master grid {}: depths [{:.3}, {:.3}], levels [{}, {}]",
+                                  i, master_depths[i-1], master_depths[i],
+                                  master_levels[i-1], master_levels[i]);
                         }
+                    }
 
-                        if error_count <= 5 {
-                            // Only return error for first few failures
-                            return Err(e);
-                        }
+                    if first_error.is_none() {
+                        first_error = Some(e);
                     }
                 }
             }
         }
 
+        if let Some(e) = first_error {
+            error!("{} nodes failed", error_count);
+            return Err(e);
+        }
+
         info!("Node processing complete:");
         info!("  - Shallow nodes: {}", shallow_count);
         info!("  - Deep nodes: {}", deep_count);
@@ -462,9 +558,9 @@ impl<'a> VQSBuilder<'a> {
         levels: usize,
         a_vqs0: &f64,
         elevation: f64,
-        sigma: &mut Array2<f64>,
-        z_coords: &mut Array2<f64>,
-        kbp: &mut Vec<usize>,
+        sigma: &mut ArrayViewMut1<f64>,
+        z_coords: &mut ArrayViewMut1<f64>,
+        kbp: &mut usize,
     ) {
         trace!(
             "process_shallow_node: node={}, depth={:.3}, levels={}",
@@ -479,16 +575,16 @@ impl<'a> VQSBuilder<'a> {
                 node_idx, levels
             );
             // Force minimum 2 levels for TRIDAG
-            kbp[node_idx] = 2;
+            *kbp = 2;
 
             // Simple linear distribution for 2 levels
-            sigma[[0, node_idx]] = 0.0; // Surface
-            sigma[[1, node_idx]] = -1.0; // Bottom
+            sigma[0] = 0.0; // Surface
+            sigma[1] = -1.0; // Bottom
 
-            z_coords[[0, node_idx]] = elevation;
-            z_coords[[1, node_idx]] = -depth;
+            z_coords[0] = elevation;
+            z_coords[1] = -depth;
         } else {
-            kbp[node_idx] = levels;
+            *kbp = levels;
 
             for k in 0..levels {
                 // Calculate sigma using quadratic transformation
@@ -497,18 +593,14 @@ impl<'a> VQSBuilder<'a> {
                 let transformed_sigma = a_vqs0 * s * s + (1.0 + a_vqs0) * s;
 
                 // Store sigma value
-                sigma[[k, node_idx]] = transformed_sigma;
+                sigma[k] = transformed_sigma;
 
                 // Calculate and store z-coordinate
-                z_coords[[k, node_idx]] = transformed_sigma * (elevation + depth) + elevation;
+                z_coords[k] = transformed_sigma * (elevation + depth) + elevation;
             }
         }
 
-        trace!(
-            "  Shallow node {} complete: kbp={}",
-            node_idx,
-            kbp[node_idx]
-        );
+        trace!("  Shallow node {} complete: kbp={}", node_idx, *kbp);
     }
 
     /// Process a node in a deeper area (depth > first master depth)
@@ -520,9 +612,9 @@ impl<'a> VQSBuilder<'a> {
         z_mas: &Array2<f64>,
         elevation: f64,
         min_bottom_layer_thickness: &f64,
-        sigma: &mut Array2<f64>,
-        z_coords: &mut Array2<f64>,
-        kbp: &mut Vec<usize>,
+        sigma: &mut ArrayViewMut1<f64>,
+        z_coords: &mut ArrayViewMut1<f64>,
+        kbp: &mut usize,
     ) -> Result<(), VQSBuilderError> {
         trace!("process_deep_node: node={}, depth={:.3}", node_idx, depth);
 
@@ -569,7 +661,7 @@ impl<'a> VQSBuilder<'a> {
 
             if z3 >= -depth + min_bottom_layer_thickness {
                 // Store z-coordinate
-                z_coords[[k, node_idx]] = z3;
+                z_coords[k] = z3;
                 bottom_level = k + 1; // +1 because we want the count, not index
                 last_z3 = z3;
             } else {
@@ -608,48 +700,48 @@ impl<'a> VQSBuilder<'a> {
             warn!("  Node {} has only 1 level - forcing to 2", node_idx);
             bottom_level = 2;
             // Add a mid-level
-            z_coords[[0, node_idx]] = elevation;
-            z_coords[[1, node_idx]] = -depth;
+            z_coords[0] = elevation;
+            z_coords[1] = -depth;
         }
 
-        kbp[node_idx] = bottom_level;
+        *kbp = bottom_level;
 
         // Set bottom z-coordinate to exactly match bathymetry
-        z_coords[[bottom_level - 1, node_idx]] = -depth;
+        z_coords[bottom_level - 1] = -depth;
 
         // Calculate sigma values
-        sigma[[0, node_idx]] = 0.0; // Surface
-        sigma[[bottom_level - 1, node_idx]] = -1.0; // Bottom
+        sigma[0] = 0.0; // Surface
+        sigma[bottom_level - 1] = -1.0; // Bottom
 
         // Calculate intermediate sigma values
         for k in 1..bottom_level - 1 {
-            sigma[[k, node_idx]] = (z_coords[[k, node_idx]] - elevation) / (elevation + depth);
+            sigma[k] = (z_coords[k] - elevation) / (elevation + depth);
         }
 
         // Check for inversions
         for k in 1..bottom_level {
-            if z_coords[[k - 1, node_idx]] <= z_coords[[k, node_idx]] {
+            if z_coords[k - 1] <= z_coords[k] {
                 error!(
                     "  Z-inversion at node {} level {}: z[{}]={:.3} <= z[{}]={:.3}",
                     node_idx,
                     k,
                     k - 1,
-                    z_coords[[k - 1, node_idx]],
+                    z_coords[k - 1],
                     k,
-                    z_coords[[k, node_idx]]
+                    z_coords[k]
                 );
                 return Err(VQSBuilderError::InvertedZ(
                     node_idx + 1,
                     depth,
                     grid_idx,
                     k,
-                    z_coords[[k - 1, node_idx]],
-                    z_coords[[k, node_idx]],
+                    z_coords[k - 1],
+                    z_coords[k],
                 ));
             }
         }
 
-        trace!("  Deep node {} complete: kbp={}", node_idx, kbp[node_idx]);
+        trace!("  Deep node {} complete: kbp={}", node_idx, *kbp);
 
         Ok(())
     }
@@ -706,6 +798,53 @@ impl<'a> VQSBuilder<'a> {
         self
     }
 
+    /// Limit the level count difference between neighbouring nodes, see
+    /// [`VQS::smooth_level_counts`].
+    pub fn max_level_jump(&mut self, max_level_jump: &'a usize) -> &mut Self {
+        self.max_level_jump = Some(max_level_jump);
+        self
+    }
+
+    /// Thickness of the surface layer wherever the water column allows it,
+    /// see [`VQS::constrain_layer_thickness`].
+    pub fn dz_surface_target(&mut self, dz_surface_target: &'a f64) -> &mut Self {
+        self.dz_surface_target = Some(dz_surface_target);
+        self
+    }
+
+    /// Largest allowed layer thickness.
+    pub fn dz_max(&mut self, dz_max: &'a f64) -> &mut Self {
+        self.dz_max = Some(dz_max);
+        self
+    }
+
+    /// Parts of the domain with their own master grids or stretching, see
+    /// [`RegionAssignment`] for how they are blended into the rest.
+    pub fn regions(&mut self, regions: &'a [VQSRegion<'a>]) -> &mut Self {
+        self.regions = Some(regions);
+        self
+    }
+
+    /// Width of the band across region outlines over which grids are
+    /// blended, in hgrid coordinate units. Defaults to 0, a sharp switch.
+    pub fn blending_width(&mut self, blending_width: &'a f64) -> &mut Self {
+        self.blending_width = Some(blending_width);
+        self
+    }
+
+    /// Region of every node for the regions and blending width set so far,
+    /// e.g. to export it with [`RegionAssignment::write_gr3`].
+    pub fn region_assignment(&self) -> Result<RegionAssignment, VQSBuilderError> {
+        let hgrid = self
+            .hgrid
+            .ok_or_else(|| VQSBuilderError::UninitializedFieldError("hgrid".to_string()))?;
+        Ok(RegionAssignment::new(
+            hgrid,
+            self.regions.unwrap_or_default(),
+            self.blending_width.copied().unwrap_or(0.),
+        )?)
+    }
+
     pub fn validate_dz_bottom_min(dz_bottom_min: &f64) -> Result<(), VQSBuilderError> {
         if *dz_bottom_min < 0. {
             error!("Invalid dz_bottom_min: {} (must be >= 0)", dz_bottom_min);
@@ -1,11 +1,11 @@
 // schishmrs-vgrid/src/vqs/vqs_kmeans_builder.rs
 
 use super::errors::VQSKMeansBuilderError;
+use super::regions::VQSRegion;
 use super::vqs::VQS;
 use super::vqs_builder::VQSBuilder;
+use crate::hsm::{master_grids, HsmStrategy, KMeansStrategy, MasterGrids};
 use crate::transforms::StretchingFunction;
-use crate::kmeans_hsm;
-use ndarray::Array;
 use schismrs_hgrid::hgrid::Hgrid;
 
 #[derive(Default)]
@@ -16,20 +16,28 @@ pub struct VQSKMeansBuilder<'a> {
     etal: Option<&'a f64>,
     shallow_levels: Option<&'a usize>,
     dz_bottom_min: Option<&'a f64>,
+    max_level_jump: Option<&'a usize>,
+    regions: Option<&'a [VQSRegion<'a>]>,
+    blending_width: Option<&'a f64>,
     max_levels: Option<&'a usize>,
+    dz_surface_target: Option<&'a f64>,
+    dz_max: Option<&'a f64>,
+    strategy: Option<&'a dyn HsmStrategy>,
 }
 
 impl<'a> VQSKMeansBuilder<'a> {
     pub fn build(&self) -> Result<VQS, VQSKMeansBuilderError> {
+        Ok(self.build_with_master_grids()?.0)
+    }
+
+    /// Build the VQS and also return the master grids it was built from.
+    pub fn build_with_master_grids(&self) -> Result<(VQS, MasterGrids), VQSKMeansBuilderError> {
         let hgrid = self
             .hgrid
             .ok_or_else(|| VQSKMeansBuilderError::UninitializedFieldError("hgrid".to_string()))?;
         let stretching = self.stretching.ok_or_else(|| {
             VQSKMeansBuilderError::UninitializedFieldError("stretching".to_string())
         })?;
-        let nclusters = self.nclusters.ok_or_else(|| {
-            VQSKMeansBuilderError::UninitializedFieldError("nclusters".to_string())
-        })?;
         let etal = self
             .etal
             .ok_or_else(|| VQSKMeansBuilderError::UninitializedFieldError("etal".to_string()))?;
@@ -38,11 +46,9 @@ impl<'a> VQSKMeansBuilder<'a> {
         })?;
         Self::validate_shallow_levels(shallow_levels)?;
         
-        let max_levels = match self.max_levels {
-            Some(max_levels) => *max_levels,
-            None => Self::calculate_max_levels(shallow_levels, nclusters),
-        };
-        Self::validate_max_levels(shallow_levels, &max_levels)?;
+        if let Some(max_levels) = self.max_levels {
+            Self::validate_max_levels(shallow_levels, max_levels)?;
+        }
 
         let dz_bottom_min = match self.dz_bottom_min {
             Some(value) => value.clone(),
@@ -59,24 +65,51 @@ impl<'a> VQSKMeansBuilder<'a> {
                 }
             }
         };
-        let mut hsm = kmeans_hsm(hgrid, nclusters, etal)?;
-        hsm.iter_mut().for_each(|depth| *depth = depth.abs());
-        let mut nlevels = Vec::<usize>::with_capacity(*nclusters);
-        let levels = Array::linspace(*shallow_levels as f64, max_levels as f64, *nclusters);
-        for level in levels.iter() {
-            let mut level = level.round() as usize;
-            if level < *shallow_levels {
-                level = *shallow_levels;
+        let kmeans;
+        let strategy = match self.strategy {
+            Some(strategy) => strategy,
+            None => {
+                let nclusters = self.nclusters.ok_or_else(|| {
+                    VQSKMeansBuilderError::UninitializedFieldError("nclusters".to_string())
+                })?;
+                kmeans = KMeansStrategy {
+                    nclusters: *nclusters,
+                    seed: None,
+                };
+                &kmeans
             }
-            nlevels.push(level);
+        };
+        let master_grids = master_grids(
+            strategy,
+            hgrid,
+            *etal,
+            *shallow_levels,
+            self.max_levels.copied(),
+        )?;
+        let mut builder = VQSBuilder::default();
+        builder
+            .hgrid(hgrid)
+            .depths(&master_grids.depths)
+            .nlevels(&master_grids.nlevels)
+            .stretching(stretching)
+            .dz_bottom_min(&dz_bottom_min);
+        if let Some(max_level_jump) = self.max_level_jump {
+            builder.max_level_jump(max_level_jump);
+        }
+        if let Some(regions) = self.regions {
+            builder.regions(regions);
         }
-        Ok(VQSBuilder::default()
-            .hgrid(&hgrid)
-            .depths(&hsm)
-            .nlevels(&nlevels)
-            .stretching(&stretching)
-            .dz_bottom_min(&dz_bottom_min)
-            .build()?)
+        if let Some(blending_width) = self.blending_width {
+            builder.blending_width(blending_width);
+        }
+        if let Some(dz_surface_target) = self.dz_surface_target {
+            builder.dz_surface_target(dz_surface_target);
+        }
+        if let Some(dz_max) = self.dz_max {
+            builder.dz_max(dz_max);
+        }
+        let vqs = builder.build()?;
+        Ok((vqs, master_grids))
     }
 
     pub fn hgrid(&mut self, hgrid: &'a Hgrid) -> &mut Self {
@@ -109,11 +142,48 @@ impl<'a> VQSKMeansBuilder<'a> {
         self
     }
     
+    pub fn dz_surface_target(&mut self, dz_surface_target: &'a f64) -> &mut Self {
+        self.dz_surface_target = Some(dz_surface_target);
+        self
+    }
+
+    pub fn dz_max(&mut self, dz_max: &'a f64) -> &mut Self {
+        self.dz_max = Some(dz_max);
+        self
+    }
+
     pub fn dz_bottom_min(&mut self, dz_bottom_min: &'a f64) -> &mut Self {
         self.dz_bottom_min = Some(dz_bottom_min);
         self
     }
     
+    /// Limit the level count difference between neighbouring nodes, see
+    /// [`VQS::smooth_level_counts`].
+    pub fn max_level_jump(&mut self, max_level_jump: &'a usize) -> &mut Self {
+        self.max_level_jump = Some(max_level_jump);
+        self
+    }
+
+    /// Parts of the domain with their own master grids or stretching, see
+    /// [`VQSBuilder::regions`].
+    pub fn regions(&mut self, regions: &'a [VQSRegion<'a>]) -> &mut Self {
+        self.regions = Some(regions);
+        self
+    }
+
+    /// See [`VQSBuilder::blending_width`].
+    pub fn blending_width(&mut self, blending_width: &'a f64) -> &mut Self {
+        self.blending_width = Some(blending_width);
+        self
+    }
+
+    /// Choose the master grids with `strategy` instead of unseeded k-means
+    /// with `nclusters` clusters.
+    pub fn strategy(&mut self, strategy: &'a dyn HsmStrategy) -> &mut Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
     fn validate_shallow_levels(shallow_levels: &'a usize) -> Result<(), VQSKMeansBuilderError> {
         if *shallow_levels < 2 {
             return Err(VQSKMeansBuilderError::InvalidShallowLevels);
@@ -121,10 +191,6 @@ impl<'a> VQSKMeansBuilder<'a> {
         Ok(())
     }
 
-    fn calculate_max_levels(shallow_levels: &usize, clusters: &usize) -> usize {
-        shallow_levels + clusters - 1
-    }
-    
     fn validate_max_levels(
         shallow_levels: &usize,
         max_levels: &usize,